@@ -1,4 +1,6 @@
-use std::env;
+use std::{env, fs, path::Path};
+
+use woodstove_lib::{rules::RuleSet, state_machine::StoveConfig};
 
 fn main() {
     // load .env file for build
@@ -25,8 +27,77 @@ fn main() {
         println!("cargo:rustc-env=MQTT_PASS={}", mqtt_pass);
     }
 
+    // optional: GPIO numbers of a piezo buzzer and its mute button, to sound alarms locally
+    for var in ["BUZZER_PIN", "MUTE_PIN"] {
+        if let Ok(pin) = env::var(var) {
+            println!("cargo:rustc-env={}={}", var, pin);
+        }
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+
+    // optional: named thermocouples as "name:cs_pin, ...", and which of them drives the burn
+    // state; unset is a single "stove" channel on GPIO21. The channels in the catalytic
+    // combustor and on the flue pipe follow the catalyst and the creosote risk.
+    for var in [
+        "THERMOCOUPLE_CHANNELS",
+        "PRIMARY_CHANNEL",
+        "CATALYST_CHANNEL",
+        "FLUE_CHANNEL",
+    ] {
+        if let Ok(value) = env::var(var) {
+            println!("cargo:rustc-env={}={}", var, value);
+        }
+        println!("cargo:rerun-if-env-changed={}", var);
+    }
+
+    // optional: creosote index since the last sweep at which to suggest a chimney inspection;
+    // one that isn't a positive number fails the build
+    let var = "CREOSOTE_INSPECTION_THRESHOLD";
+    if let Ok(value) = env::var(var) {
+        match value.trim().parse::<f32>() {
+            Ok(threshold) if threshold.is_finite() && threshold > 0.0 => {
+                println!("cargo:rustc-env={}={}", var, value.trim())
+            }
+            _ => panic!("invalid {} ({}): expected a positive number", var, value),
+        }
+    }
+    println!("cargo:rerun-if-env-changed={}", var);
+
+    // embed custom stove config and burn state rule files, or nothing to use the library's
+    // defaults; one that doesn't parse fails the build rather than the device falling back
+    embed_file("STOVE_CONFIG", "stove.conf", |text| {
+        text.parse::<StoveConfig>()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    });
+    embed_file("STOVE_RULES", "stove.rules", |text| {
+        text.parse::<RuleSet>()
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    });
+
     println!("cargo:rerun-if-changed=.env");
 
     // embuild stuff
     embuild::espidf::sysenv::output();
 }
+
+// copies the file named by `var` into OUT_DIR as `name`, or an empty file if `var` is unset,
+// panicking if `check` rejects it
+fn embed_file(var: &str, name: &str, check: fn(&str) -> Result<(), String>) {
+    let contents = match env::var(var) {
+        Ok(path) => {
+            println!("cargo:rerun-if-changed={}", path);
+            let contents =
+                fs::read_to_string(&path).unwrap_or_else(|e| panic!("reading {}: {}", path, e));
+            if let Err(e) = check(&contents) {
+                panic!("invalid {} ({}): {}", var, path, e);
+            }
+            contents
+        }
+        Err(_) => String::new(),
+    };
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join(name), contents).unwrap();
+    println!("cargo:rerun-if-env-changed={}", var);
+}
@@ -0,0 +1,109 @@
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+use embedded_hal::digital::{ErrorKind, ErrorType, OutputPin};
+use esp_idf_svc::hal::{
+    gpio::{AnyIOPin, AnyOutputPin, Input, PinDriver, Pull},
+    ledc::{config::TimerConfig, LedcDriver, LedcTimerDriver, CHANNEL0, TIMER0},
+    units::*,
+};
+use woodstove_lib::{
+    buzzer::{Buzzer, BuzzerInput},
+    time::Instant,
+};
+
+// around the resonance of a typical passive piezo, for the loudest beep
+const TONE_HZ: u32 = 2_700;
+// how often to check the mute button
+const BUTTON_POLL: Duration = Duration::from_millis(50);
+
+// A PWM channel driving a passive piezo, switched on and off like a pin
+struct Piezo {
+    ledc: LedcDriver<'static>,
+}
+
+impl ErrorType for Piezo {
+    type Error = ErrorKind;
+}
+
+impl OutputPin for Piezo {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.ledc.set_duty(0).map_err(|e| {
+            log::warn!("Failed to silence buzzer: {:?}", e);
+            ErrorKind::Other
+        })
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let half = self.ledc.get_max_duty() / 2;
+        self.ledc.set_duty(half).map_err(|e| {
+            log::warn!("Failed to sound buzzer: {:?}", e);
+            ErrorKind::Other
+        })
+    }
+}
+
+// Sounds local alarms from their own thread, so they keep going whatever the main loop and the
+// network are doing. Send the stove's state to update them. A mute button, if wired between
+// `mute` and ground, snoozes them.
+pub fn spawn(
+    timer: TIMER0,
+    channel: CHANNEL0,
+    pin: AnyOutputPin,
+    mute: Option<AnyIOPin>,
+) -> anyhow::Result<Sender<BuzzerInput>> {
+    let timer = LedcTimerDriver::new(timer, &TimerConfig::new().frequency(TONE_HZ.Hz().into()))?;
+    let piezo = Piezo {
+        ledc: LedcDriver::new(channel, timer, pin)?,
+    };
+    let mute = match mute {
+        Some(pin) => {
+            let mut button = PinDriver::input(pin)?;
+            button.set_pull(Pull::Up)?;
+            Some(button)
+        }
+        None => None,
+    };
+
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("buzzer".into())
+        .stack_size(4096)
+        .spawn(move || run(piezo, mute, rx))?;
+    Ok(tx)
+}
+
+fn run(
+    piezo: Piezo,
+    mute: Option<PinDriver<'static, AnyIOPin, Input>>,
+    inputs: Receiver<BuzzerInput>,
+) {
+    let mut buzzer = Buzzer::new(piezo, Instant::now());
+    let mut was_pressed = false;
+    loop {
+        let now = Instant::now();
+        if let Some(button) = &mute {
+            let pressed = button.is_low();
+            if pressed && !was_pressed && buzzer.sounding(now).is_some() {
+                buzzer.snooze(now);
+                log::info!("Buzzer snoozed");
+            }
+            was_pressed = pressed;
+        }
+
+        // the pin logs its own errors
+        let wait = buzzer.poll(now).unwrap_or(Duration::from_secs(1));
+        let wait = match mute {
+            Some(_) => wait.min(BUTTON_POLL),
+            None => wait,
+        };
+        match inputs.recv_timeout(wait) {
+            Ok(input) => buzzer.update(Instant::now(), input),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use woodstove_lib::ota::ImageDigest;
+
+// Commands received on the MQTT command topic
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    AcknowledgeAlarm,
+    // the chimney has been swept, so the creosote index starts over
+    ChimneySwept,
+    // download the firmware image at this HTTPS URL and install it if it has this SHA-256
+    Update { url: String, digest: ImageDigest },
+}
+
+impl Command {
+    pub fn parse(payload: &[u8]) -> Option<Command> {
+        let text = std::str::from_utf8(payload).ok()?.trim();
+
+        // `ota <url> <sha256>`; without the digest there's nothing to check the image against
+        if let Some(args) = text.strip_prefix("ota ") {
+            let mut args = args.split_whitespace();
+            let url = args.next()?.to_string();
+            let digest = args.next()?.parse().ok()?;
+            if args.next().is_some() {
+                return None;
+            }
+            return Some(Command::Update { url, digest });
+        }
+
+        match text {
+            "ack_alarm" => Some(Command::AcknowledgeAlarm),
+            "chimney_swept" => Some(Command::ChimneySwept),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use std::{
+    sync::mpsc::{self, Receiver, RecvTimeoutError, Sender},
+    thread,
+    time::Duration,
+};
+
+use esp_idf_svc::hal::gpio::{Gpio8, Output, PinDriver};
+use woodstove_lib::{
+    led::{LedStatus, StatusLed},
+    time::Instant,
+};
+
+type LedPin = PinDriver<'static, Gpio8, Output>;
+
+// Plays status patterns on the LED from its own thread, so blocking WiFi and MQTT calls in the
+// main loop don't freeze it. Send a status to change what it shows.
+pub fn spawn(pin: LedPin) -> std::io::Result<Sender<LedStatus>> {
+    let (tx, rx) = mpsc::channel();
+    thread::Builder::new()
+        .name("status_led".into())
+        .stack_size(4096)
+        .spawn(move || run(pin, rx))?;
+    Ok(tx)
+}
+
+fn run(pin: LedPin, statuses: Receiver<LedStatus>) {
+    // the first thing the main task does is connect to WiFi
+    let mut led = StatusLed::new(pin, LedStatus::WifiConnecting, Instant::now());
+    loop {
+        let wait = match led.poll(Instant::now()) {
+            Ok(wait) => wait,
+            Err(e) => {
+                log::warn!("Failed to drive status LED: {:?}", e);
+                Duration::from_secs(1)
+            }
+        };
+        match statuses.recv_timeout(wait) {
+            Ok(status) => led.set_status(status, Instant::now()),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
@@ -1,6 +1,17 @@
+mod buzzer;
+mod command;
+mod led;
 mod mqtt;
+mod ota;
+mod persist;
+mod pins;
+#[cfg(feature = "display")]
+mod screen;
+mod thermocouple;
+mod watchdog;
 mod wifi;
 
+use command::Command;
 use esp_idf_svc::{
     hal::{
         delay::FreeRtos,
@@ -10,21 +21,119 @@ use esp_idf_svc::{
             config::{Config, DriverConfig, Mode, Phase},
             *,
         },
+        task::watchdog::{TWDTConfig, TWDTDriver},
         units::*,
     },
+    nvs::EspDefaultNvsPartition,
+    ota::{EspOta, SlotState},
     sys::EspError,
 };
 use max31855::{Max31855, Unit};
 use mqtt::WoodstoveMQTT;
-use woodstove_lib::{StoveStateMachine, Temperature};
+use persist::{CatalystStore, CreosoteStore, SnapshotStore};
+use std::time::Duration;
+use watchdog::HealthStore;
+#[cfg(feature = "display")]
+use woodstove_lib::display::{DisplayView, ReloadHint};
+use woodstove_lib::{
+    alarm::{AlarmEvent, AlarmInput},
+    buzzer::BuzzerInput,
+    catalyst::CatalystMonitor,
+    channel,
+    creosote::{CreosoteConfig, CreosoteTracker},
+    filter::FilterOutcome,
+    health::{HealthDecision, LoopReport},
+    led::{LedInput, LedStatus},
+    ota::{UpdateValidator, ValidationDecision},
+    rate::EmaEstimator,
+    rules::RuleSet,
+    runaway::RunawayEvent,
+    snapshot,
+    state_machine::StoveConfig,
+    time::Instant,
+    AlarmMonitor, EnergyTracker, HealthSupervisor, RunawayDetector, StoveStateMachine, Temperature,
+};
 
 const WIFI_SSID: &str = env!("WIFI_SSID");
 const WIFI_PASSWORD: &str = env!("WIFI_PASSWORD");
 const MQTT_ENDPOINT: &str = env!("MQTT_ENDPOINT");
 const MQTT_USER: &str = env!("MQTT_USER");
 const MQTT_PASS: &str = env!("MQTT_PASS");
+// unset leaves out the local buzzer / its mute button
+const BUZZER_PIN: Option<&str> = option_env!("BUZZER_PIN");
+const MUTE_PIN: Option<&str> = option_env!("MUTE_PIN");
+// unset is a single "stove" thermocouple on GPIO21 / the first channel
+const THERMOCOUPLE_CHANNELS: Option<&str> = option_env!("THERMOCOUPLE_CHANNELS");
+const PRIMARY_CHANNEL: Option<&str> = option_env!("PRIMARY_CHANNEL");
+// unset leaves out catalyst monitoring
+const CATALYST_CHANNEL: Option<&str> = option_env!("CATALYST_CHANNEL");
+// unset leaves out creosote tracking
+const FLUE_CHANNEL: Option<&str> = option_env!("FLUE_CHANNEL");
+// unset is the library's default
+const CREOSOTE_INSPECTION_THRESHOLD: Option<&str> = option_env!("CREOSOTE_INSPECTION_THRESHOLD");
 
 const LOOP_DELAY_MS: u32 = 10_000;
+// how often to try joining the network again while it's down
+const WIFI_RETRY_INTERVAL: Duration = Duration::from_secs(30);
+// a few loop periods
+const WATCHDOG_TIMEOUT: Duration = Duration::from_secs(60);
+// how often to save the state machine snapshot between transitions; NVS is flash, so not
+// every loop
+const SNAPSHOT_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// stove config and burn state rules from the files named by STOVE_CONFIG and STOVE_RULES at
+// build time, empty if unset
+const STOVE_CONFIG: &str = include_str!(concat!(env!("OUT_DIR"), "/stove.conf"));
+const STOVE_RULES: &str = include_str!(concat!(env!("OUT_DIR"), "/stove.rules"));
+
+fn gpio_number(var: &str, value: Option<&str>) -> Option<i32> {
+    match value?.trim().parse() {
+        Ok(pin) => Some(pin),
+        Err(e) => {
+            log::error!("Invalid {} ({}), leaving it out", var, e);
+            None
+        }
+    }
+}
+
+// whether `pin`, from `var`, is one the board can spare and not one of `in_use`
+fn free_pin(var: &str, pin: i32, in_use: &[i32]) -> bool {
+    let free = channel::usable_pin(pin) && !in_use.contains(&pin);
+    if !free {
+        log::error!(
+            "Invalid {} (GPIO{} is unusable or in use), leaving it out",
+            var,
+            pin
+        );
+    }
+    free
+}
+
+// build.rs has already checked both files parse
+fn load_rules() -> RuleSet {
+    if STOVE_RULES.trim().is_empty() {
+        return RuleSet::default();
+    }
+    log::info!("Loaded custom burn state rules");
+    STOVE_RULES
+        .parse()
+        .expect("STOVE_RULES checked at build time")
+}
+
+fn load_config() -> StoveConfig {
+    let config = if STOVE_CONFIG.trim().is_empty() {
+        StoveConfig::default()
+    } else {
+        log::info!("Loaded custom stove config");
+        STOVE_CONFIG
+            .parse()
+            .expect("STOVE_CONFIG checked at build time")
+    };
+    StoveConfig {
+        rules: load_rules(),
+        ..config
+    }
+}
 
 fn log_publish_result(name: &str, result: Result<u32, EspError>) {
     match result {
@@ -33,6 +142,16 @@ fn log_publish_result(name: &str, result: Result<u32, EspError>) {
     }
 }
 
+fn log_alarm_event(event: &AlarmEvent) {
+    match event {
+        AlarmEvent::Raised(alarm) | AlarmEvent::Renotify(alarm) | AlarmEvent::Escalated(alarm) => {
+            log::warn!("Alarm ({}): {}", alarm.severity, alarm.cause)
+        }
+        AlarmEvent::Acknowledged(alarm) => log::info!("Alarm acknowledged: {}", alarm.cause),
+        AlarmEvent::Cleared(alarm) => log::info!("Alarm cleared: {}", alarm.cause),
+    }
+}
+
 fn main() -> anyhow::Result<()> {
     // It is necessary to call this function once. Otherwise, some patches to the runtime
     // implemented by esp-idf-sys might not link properly. See https://github.com/esp-rs/esp-idf-template/issues/71
@@ -44,8 +163,26 @@ fn main() -> anyhow::Result<()> {
     // setup SPI for thermocouple reads
     let peripherals = Peripherals::take().unwrap();
 
-    // setup error led
-    let mut status_led = PinDriver::output(peripherals.pins.gpio8)?;
+    // status led, blinking out what the device and stove are up to
+    let status_led = led::spawn(PinDriver::output(peripherals.pins.gpio8)?)?;
+
+    // a glanceable screen by the stove; the monitor carries on without it
+    #[cfg(feature = "display")]
+    let mut screen = match screen::Screen::new(
+        peripherals.i2c0,
+        peripherals.pins.gpio11,
+        peripherals.pins.gpio12,
+    ) {
+        Ok(screen) => Some(screen),
+        Err(e) => {
+            log::error!("Display unavailable: {:?}", e);
+            None
+        }
+    };
+
+    // the buzzer and its mute button, which the thermocouples keep clear of
+    let buzzer_pin = gpio_number("BUZZER_PIN", BUZZER_PIN);
+    let mute_pin = gpio_number("MUTE_PIN", MUTE_PIN);
 
     let freq: Hertz = 4.MHz().into();
     let mode = Mode {
@@ -68,65 +205,627 @@ fn main() -> anyhow::Result<()> {
     )
     .unwrap();
 
-    let mut cs = PinDriver::output(peripherals.pins.gpio21).unwrap(); // CS (D10/GPIO21)
+    // one CS pin per thermocouple, each filtered and faulted on its own
+    let stove_config = load_config();
+    let in_use: Vec<i32> = pins::FIRMWARE
+        .into_iter()
+        .chain(buzzer_pin)
+        .chain(mute_pin)
+        .collect();
+    let channels = thermocouple::channel_list(THERMOCOUPLE_CHANNELS, PRIMARY_CHANNEL, &in_use);
+    let mut thermocouples = thermocouple::setup(&channels, stove_config.sensor_fault_after)?;
+    let primary = channels.primary();
 
-    log::info!("SPI and CS configured successfully!");
+    log::info!(
+        "SPI and CS configured successfully! {} channel(s), {} drives the burn state",
+        thermocouples.len(),
+        thermocouples[primary].name
+    );
 
-    // Setup wifi
-    let mut wifi_handler = wifi::WifiHandler::new(peripherals.modem, WIFI_SSID, WIFI_PASSWORD)?;
-    wifi_handler.connect()?;
-    log::info!("wifi connected");
+    // local alarms, for when nobody is watching MQTT or the network is down; the monitor carries
+    // on without them if their pins aren't free
+    let mut claimed: Vec<i32> = pins::FIRMWARE
+        .into_iter()
+        .chain(channels.channels().iter().map(|channel| channel.cs_pin))
+        .collect();
+    let buzzer_pin = buzzer_pin.filter(|&pin| free_pin("BUZZER_PIN", pin, &claimed));
+    claimed.extend(buzzer_pin);
+    let mute_pin = mute_pin.filter(|&pin| free_pin("MUTE_PIN", pin, &claimed));
+    let alarm_buzzer = match buzzer_pin {
+        // the pins are only known at build time, so can't be taken from `peripherals`
+        Some(pin) => match buzzer::spawn(
+            peripherals.ledc.timer0,
+            peripherals.ledc.channel0,
+            unsafe { AnyOutputPin::new(pin) },
+            mute_pin.map(|pin| unsafe { AnyIOPin::new(pin) }),
+        ) {
+            Ok(buzzer) => Some(buzzer),
+            Err(e) => {
+                log::error!("Buzzer unavailable: {:?}", e);
+                None
+            }
+        },
+        None => None,
+    };
 
-    // Setup mqtt
-    let mut mqtt_handler =
-        WoodstoveMQTT::new("woodstove_monitor", MQTT_ENDPOINT, MQTT_USER, MQTT_PASS)?;
+    // work out why we (re)started before anything else can fail
+    let nvs = EspDefaultNvsPartition::take()?;
+    let mut health_store = HealthStore::new(nvs.clone())?;
+    let mut snapshot_store = SnapshotStore::new(nvs.clone())?;
+    let mut catalyst_store = CatalystStore::new(nvs.clone())?;
+    let mut creosote_store = CreosoteStore::new(nvs.clone())?;
+    let (reset_reason, crash_count) = health_store.record_boot()?;
+    log::info!(
+        "Started after {} reset, crash count {}",
+        reset_reason,
+        crash_count
+    );
+    let mut health = HealthSupervisor::new(crash_count);
+    let mut crash_count_cleared = false;
+
+    // freshly updated firmware runs on trial until it has connected and read the sensor
+    let mut ota = EspOta::new()?;
+    let running_slot = ota.get_running_slot()?;
+    let firmware_version = running_slot
+        .firmware
+        .as_ref()
+        .map_or(env!("CARGO_PKG_VERSION"), |firmware| {
+            firmware.version.as_str()
+        })
+        .to_string();
+    let firmware_partition = running_slot.label.to_string();
+    let mut update_validator =
+        (running_slot.state == SlotState::Unverified).then(UpdateValidator::new);
+    log::info!(
+        "Running firmware {} from {}{}",
+        firmware_version,
+        firmware_partition,
+        if update_validator.is_some() {
+            ", pending validation"
+        } else {
+            ""
+        }
+    );
+
+    // a hang anywhere from here on, e.g. in a blocking WiFi/MQTT call or SPI transaction,
+    // restarts the device
+    let mut twdt = TWDTDriver::new(
+        peripherals.twdt,
+        &TWDTConfig {
+            duration: WATCHDOG_TIMEOUT,
+            panic_on_trigger: true,
+            ..Default::default()
+        },
+    )?;
+    let mut watchdog = twdt.watch_current_task()?;
+
+    let unit = stove_config.display_unit;
+    log::info!("Showing temperatures in {}", unit);
 
     // setup the state machine
-    let mut stove_state_machine = StoveStateMachine::new();
+    let mut stove_state_machine =
+        StoveStateMachine::with_estimator(stove_config, EmaEstimator::new(0.3));
+    // pick up mid-burn after a crash, if the snapshot is recent enough to still hold
+    match snapshot_store.load() {
+        Ok(Some(saved)) if persist::clock_survived(reset_reason) => {
+            match saved.fresh_age(persist::rtc_clock(), snapshot::MAX_AGE) {
+                Some(age) => {
+                    stove_state_machine.restore(&saved, Instant::now(), age);
+                    log::info!("Restored {} from {}s ago", saved.state, age.as_secs());
+                }
+                None => log::info!("Stored snapshot is stale, starting idle"),
+            }
+        }
+        Ok(_) => {}
+        Err(e) => log::warn!("Failed to load snapshot: {:?}", e),
+    }
+    let mut last_snapshot: Option<Instant> = None;
+    // the catalytic combustor's channel and lifecycle, if it has a probe
+    let mut catalyst = match CATALYST_CHANNEL.map(str::trim) {
+        None => None,
+        Some(name) => match thermocouples.iter().position(|t| t.name == name) {
+            Some(index) => {
+                let mut monitor = CatalystMonitor::new();
+                match catalyst_store.load() {
+                    Ok(time) => monitor.set_catalyst_time(time),
+                    Err(e) => log::warn!("Failed to load catalyst hours: {:?}", e),
+                }
+                log::info!(
+                    "Monitoring the catalyst on {}, {:.1} hours so far",
+                    name,
+                    monitor.catalyst_hours()
+                );
+                Some((index, monitor))
+            }
+            None => {
+                log::error!(
+                    "Invalid CATALYST_CHANNEL ({} isn't a channel), leaving it out",
+                    name
+                );
+                None
+            }
+        },
+    };
+    let mut last_catalyst_save: Option<Instant> = None;
+    // creosote risk from the flue pipe's channel, if it has a probe
+    let mut creosote = match FLUE_CHANNEL.map(str::trim) {
+        None => None,
+        Some(name) => match thermocouples.iter().position(|t| t.name == name) {
+            Some(index) => {
+                let mut config = CreosoteConfig::default();
+                if let Some(threshold) = CREOSOTE_INSPECTION_THRESHOLD {
+                    config.inspection_threshold = threshold
+                        .parse()
+                        .expect("CREOSOTE_INSPECTION_THRESHOLD checked at build time");
+                }
+                let inspection_threshold = config.inspection_threshold;
+                let mut tracker = CreosoteTracker::with_config(config);
+                match creosote_store.load() {
+                    Ok(Some(mut history)) => {
+                        // the RTC clock started over, and there's no telling how long the power
+                        // was off, so the current day starts again from now
+                        if !persist::clock_survived(reset_reason) {
+                            history.day_started = None;
+                        }
+                        tracker.restore(history);
+                    }
+                    Ok(None) => {}
+                    Err(e) => log::warn!("Failed to load creosote index: {:?}", e),
+                }
+                log::info!(
+                    "Tracking creosote on {}, index {:.1} since the last sweep, inspection at {:.1}",
+                    name,
+                    tracker.since_sweep(),
+                    inspection_threshold
+                );
+                Some((index, tracker))
+            }
+            None => {
+                log::error!(
+                    "Invalid FLUE_CHANNEL ({} isn't a channel), leaving it out",
+                    name
+                );
+                None
+            }
+        },
+    };
+    let mut last_creosote_save: Option<Instant> = None;
+    let mut energy_tracker = EnergyTracker::new();
+    let mut alarm_monitor = AlarmMonitor::new();
+    let mut runaway_detector = RunawayDetector::new();
+
+    // the network comes up last, so the sensor and alarms never wait on it; the monitor runs
+    // without WiFi, and the main loop keeps retrying until it's up
+    let mut wifi_handler =
+        match wifi::WifiHandler::new(peripherals.modem, nvs, WIFI_SSID, WIFI_PASSWORD) {
+            Ok(mut handler) => {
+                if let Err(e) = handler.connect() {
+                    log::warn!("Failed to start WiFi connect: {:?}", e);
+                }
+                Some(handler)
+            }
+            Err(e) => {
+                log::error!("WiFi unavailable: {:?}", e);
+                None
+            }
+        };
+    let mut last_wifi_attempt = Instant::now();
+
+    // Setup mqtt
+    let mut mqtt_handler = WoodstoveMQTT::new(
+        "woodstove_monitor",
+        MQTT_ENDPOINT,
+        MQTT_USER,
+        MQTT_PASS,
+        unit,
+    )?;
+
+    // publish the cleared alarm so a stale retained alarm from before a reboot goes away
+    log_publish_result("alarm", mqtt_handler.publish_alarm(None));
+    log_publish_result("unit", mqtt_handler.publish_unit());
+    log_publish_result(
+        "health",
+        mqtt_handler.publish_health(reset_reason, crash_count),
+    );
+    log_publish_result(
+        "firmware",
+        mqtt_handler.publish_firmware(
+            &firmware_version,
+            &firmware_partition,
+            update_validator.is_some(),
+        ),
+    );
 
     loop {
-        match Max31855::read_thermocouple(&mut spi, &mut cs, Unit::Celsius) {
-            Ok(temp_c) => {
-                let temp = Temperature::from_celsius(temp_c);
+        // the LED shows WiFi connecting for as long as this goes on
+        let wifi_connected = wifi_handler
+            .as_ref()
+            .is_some_and(|wifi| wifi.is_connected());
+        if let Some(wifi) = wifi_handler.as_mut() {
+            if !wifi_connected && Instant::now() - last_wifi_attempt >= WIFI_RETRY_INTERVAL {
+                log::info!("WiFi down, reconnecting");
+                last_wifi_attempt = Instant::now();
+                if let Err(e) = wifi.connect() {
+                    log::warn!("Failed to start WiFi connect: {:?}", e);
+                }
+            }
+        }
 
-                // publish temperature
-                log_publish_result("temperature", mqtt_handler.publish_temperature(&temp));
+        for command in mqtt_handler.poll_commands() {
+            match command {
+                Command::AcknowledgeAlarm => {
+                    if let Some(event) = alarm_monitor.acknowledge() {
+                        log_alarm_event(&event);
+                        log_publish_result(
+                            "alarm",
+                            mqtt_handler.publish_alarm(alarm_monitor.active()),
+                        );
+                    }
+                }
+                Command::ChimneySwept => match creosote.as_mut() {
+                    Some((_, tracker)) => {
+                        tracker.chimney_swept();
+                        log::info!("Chimney swept, creosote index reset");
+                        if let Err(e) = creosote_store.save(&tracker.history()) {
+                            log::warn!("Failed to save creosote index: {:?}", e);
+                        }
+                        log_publish_result("creosote", mqtt_handler.publish_creosote(tracker));
+                    }
+                    None => log::warn!("Ignoring chimney_swept without a FLUE_CHANNEL"),
+                },
+                Command::Update { url, digest } => {
+                    // the bootloader won't take another image until this one is validated
+                    if update_validator.is_some() {
+                        log::warn!("Ignoring update until the running firmware is validated");
+                        continue;
+                    }
+                    log::info!("Updating firmware from {}", url);
+                    match ota::update(&mut ota, &url, &digest, || {
+                        watchdog.feed().ok();
+                    }) {
+                        Ok(()) => {
+                            // carry the burn over into the new firmware
+                            if let Some(snapshot) = stove_state_machine
+                                .snapshot_at(Instant::now(), persist::rtc_clock())
+                            {
+                                snapshot_store.save(&snapshot).ok();
+                            }
+                            log::info!("Update installed, restarting");
+                            esp_idf_svc::hal::reset::restart();
+                        }
+                        Err(e) => {
+                            log::error!("Update failed: {:?}", e);
+                            if let Err(mqtt_err) =
+                                mqtt_handler.publish_error(format!("Update failed: {}", e))
+                            {
+                                log::warn!("Failed to publish update error: {:?}", mqtt_err);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        // read every channel; a fault on one doesn't stop the others
+        let mut outcomes = Vec::with_capacity(thermocouples.len());
+        for thermocouple in thermocouples.iter_mut() {
+            let name = thermocouple.name;
+            // a failing amplifier can also hand back a value no type K probe can read
+            let reading =
+                Max31855::read_thermocouple(&mut spi, &mut thermocouple.cs, Unit::Celsius)
+                    .map_err(|e| format!("{:?}", e))
+                    .and_then(|temp_c| {
+                        Temperature::try_from_celsius(temp_c).map_err(|e| e.to_string())
+                    });
 
-                // update state machine
-                let state_changed = stove_state_machine.update(temp);
+            let outcome = match reading {
+                // drop bad frames before they reach the state machine
+                Ok(reading) => match thermocouple.reader.reading(Instant::now(), reading) {
+                    FilterOutcome::Rejected(reason) => {
+                        log::warn!(
+                            "Rejected {} reading of {:.1}: {:?}",
+                            name,
+                            reading.display_in(unit),
+                            reason
+                        );
+                        Ok(FilterOutcome::Rejected(reason))
+                    }
+                    accepted => Ok(accepted),
+                },
+                Err(e) => {
+                    let error_msg = format!("Sensor error ({}): {}", name, e);
 
-                let state_string = stove_state_machine.current_state().to_string();
+                    if let Err(mqtt_err) = mqtt_handler.publish_error(error_msg.clone()) {
+                        log::warn!("Failed to publish sensor error: {:?}", mqtt_err);
+                    }
 
+                    log::error!("{}", error_msg);
+
+                    if thermocouple.reader.report_fault() {
+                        log::warn!("Channel {} faulted", name);
+                    }
+                    Err(e)
+                }
+            };
+            log_publish_result(
+                "channel",
+                mqtt_handler.publish_channel(name, &thermocouple.reader),
+            );
+            outcomes.push(outcome);
+        }
+
+        // follow the catalyst through light-off and advise on the bypass and air
+        if let Some((index, monitor)) = catalyst.as_mut() {
+            if let Ok(FilterOutcome::Accepted(temp)) = outcomes[*index] {
+                let now = Instant::now();
+                let before = monitor.state();
+                if let Some(advice) = monitor.update(now, temp) {
+                    log::warn!("Catalyst: {}", advice);
+                    log_publish_result(
+                        "catalyst advice",
+                        mqtt_handler.publish_catalyst_advice(advice),
+                    );
+                }
+                let state_changed = monitor.state() != before;
                 if state_changed {
-                    log::info!("State changed to: {}", state_string);
+                    log::info!("Catalyst state changed to: {}", monitor.state());
                 }
+                log_publish_result("catalyst", mqtt_handler.publish_catalyst(monitor));
+
+                // hours only change while it burns; like the snapshot, not every loop
+                let save_due =
+                    last_catalyst_save.map_or(true, |saved| now - saved >= SNAPSHOT_INTERVAL);
+                if state_changed || (monitor.state().is_burning() && save_due) {
+                    match catalyst_store.save(monitor.catalyst_time()) {
+                        Ok(()) => last_catalyst_save = Some(now),
+                        Err(e) => log::warn!("Failed to save catalyst hours: {:?}", e),
+                    }
+                }
+            }
+        }
+
+        // build up the creosote risk while the flue runs cool during a burn
+        if let Some((index, tracker)) = creosote.as_mut() {
+            if let Ok(FilterOutcome::Accepted(flue)) = outcomes[*index] {
+                let now = Instant::now();
+                // days are counted on the RTC clock, so they carry on across resets
+                let clock = Instant::from_duration(persist::rtc_clock());
+                if let Some(notice) =
+                    tracker.update(clock, flue, stove_state_machine.current_state())
+                {
+                    log::warn!("Creosote: {}", notice);
+                    log_publish_result(
+                        "creosote notice",
+                        mqtt_handler.publish_creosote_notice(notice),
+                    );
+                }
+                log_publish_result("creosote", mqtt_handler.publish_creosote(tracker));
+
+                // like the snapshot, not every loop
+                let save_due =
+                    last_creosote_save.map_or(true, |saved| now - saved >= SNAPSHOT_INTERVAL);
+                if save_due {
+                    match creosote_store.save(&tracker.history()) {
+                        Ok(()) => last_creosote_save = Some(now),
+                        Err(e) => log::warn!("Failed to save creosote index: {:?}", e),
+                    }
+                }
+            }
+        }
+
+        let outcome = outcomes.swap_remove(primary);
+        let sensor_ok = outcome.is_ok();
+
+        match outcome {
+            Ok(outcome) => {
+                match outcome {
+                    FilterOutcome::Accepted(temp) => {
+                        // publish temperature
+                        log_publish_result("temperature", mqtt_handler.publish_temperature(&temp));
+
+                        // update state machine
+                        let state_changed = stove_state_machine.update(temp);
+
+                        let rate = stove_state_machine.rate_of_change();
+                        match rate {
+                            Some(rate) => log::info!(
+                                "Temperature: {:.1} ({:.1})",
+                                temp.display_in(unit),
+                                rate.display_in(unit)
+                            ),
+                            None => log::info!("Temperature: {:.1}", temp.display_in(unit)),
+                        }
+                        log_publish_result("reading", mqtt_handler.publish_reading(&temp, rate));
 
+                        let state_string = stove_state_machine.current_state().to_string();
+
+                        if state_changed {
+                            log::info!("State changed to: {}", state_string);
+                        }
+
+                        // save on every transition, and now and then in between
+                        let now = Instant::now();
+                        let snapshot_due =
+                            last_snapshot.map_or(true, |saved| now - saved >= SNAPSHOT_INTERVAL);
+                        if state_changed || snapshot_due {
+                            if let Some(snapshot) =
+                                stove_state_machine.snapshot_at(now, persist::rtc_clock())
+                            {
+                                match snapshot_store.save(&snapshot) {
+                                    Ok(()) => last_snapshot = Some(now),
+                                    Err(e) => log::warn!("Failed to save snapshot: {:?}", e),
+                                }
+                            }
+                        }
+
+                        log_publish_result(
+                            "state",
+                            mqtt_handler.publish_state(stove_state_machine.current_state()),
+                        );
+
+                        // watch for a chimney fire
+                        match runaway_detector.update(
+                            Instant::now(),
+                            temp,
+                            stove_state_machine.rate_of_change(),
+                        ) {
+                            Some(RunawayEvent::Detected) => {
+                                log::error!("Runaway temperature rise detected")
+                            }
+                            Some(RunawayEvent::Cleared) => log::info!("Runaway condition cleared"),
+                            None => {}
+                        }
+
+                        // raise, re-notify or clear alarms
+                        let alarm_input = AlarmInput {
+                            state: stove_state_machine.current_state(),
+                            temperature: temp,
+                            rate: stove_state_machine.rate_of_change(),
+                            runaway: runaway_detector.is_detected(),
+                        };
+                        if let Some(event) = alarm_monitor.update(Instant::now(), alarm_input) {
+                            log_alarm_event(&event);
+                            log_publish_result(
+                                "alarm",
+                                mqtt_handler.publish_alarm(alarm_monitor.active()),
+                            );
+                        }
+
+                        // estimate heat output
+                        energy_tracker.update(
+                            Instant::now(),
+                            temp,
+                            stove_state_machine.current_state(),
+                        );
+                        log_publish_result("energy", mqtt_handler.publish_energy(&energy_tracker));
+
+                        // predict when the fire needs reloading / goes out
+                        log_publish_result(
+                            "predictions",
+                            mqtt_handler.publish_predictions(
+                                stove_state_machine.predicted_time_to_reload(),
+                                stove_state_machine.predicted_time_to_idle(),
+                            ),
+                        );
+
+                        // publish time in state every 6th loop
+                        log_publish_result(
+                            "time in state",
+                            mqtt_handler.publish_time_in_state(stove_state_machine.time_in_state()),
+                        );
+
+                        // publish status
+                        log_publish_result("status", mqtt_handler.publish_status());
+                    }
+                    // already logged with its channel
+                    FilterOutcome::Rejected(_) => {}
+                }
+                log_publish_result(
+                    "filter stats",
+                    mqtt_handler.publish_filter_stats(thermocouples[primary].reader.stats()),
+                );
+            }
+            Err(_) => {
+                // stop trusting the last known state after a few failures in a row; the
+                // channel has already reset its filter
+                if stove_state_machine.report_sensor_fault() {
+                    log::warn!("State changed to: {}", stove_state_machine.current_state());
+                }
                 log_publish_result(
                     "state",
                     mqtt_handler.publish_state(stove_state_machine.current_state()),
                 );
-
-                // publish time in state every 6th loop
                 log_publish_result(
                     "time in state",
                     mqtt_handler.publish_time_in_state(stove_state_machine.time_in_state()),
                 );
-
-                // publish status
-                log_publish_result("status", mqtt_handler.publish_status());
-
-                status_led.set_low().ok();
             }
-            Err(e) => {
-                let error_msg = format!("Sensor error: {:?}", e);
+        }
 
-                if let Err(mqtt_err) = mqtt_handler.publish_error(error_msg.clone()) {
-                    log::warn!("Failed to publish sensor error: {:?}", mqtt_err);
-                }
+        status_led
+            .send(LedStatus::select(LedInput {
+                wifi_connected,
+                mqtt_connected: mqtt_handler.is_connected(),
+                state: stove_state_machine.current_state(),
+                reload_suggested: stove_state_machine.should_reload(),
+            }))
+            .ok();
+
+        #[cfg(feature = "display")]
+        if let Some(screen) = screen.as_mut() {
+            let reload = if stove_state_machine.should_reload() {
+                ReloadHint::Now
+            } else {
+                stove_state_machine
+                    .predicted_time_to_reload()
+                    .map_or(ReloadHint::None, |p| ReloadHint::In(p.remaining))
+            };
+            screen.show(&DisplayView {
+                temperature: stove_state_machine.last_temperature(),
+                unit,
+                state: stove_state_machine.current_state(),
+                time_in_state: stove_state_machine.time_in_state(),
+                rate: stove_state_machine.rate_of_change(),
+                reload,
+                wifi_connected,
+                mqtt_connected: mqtt_handler.is_connected(),
+            });
+        }
 
-                status_led.set_high().ok();
+        if let Some(alarm_buzzer) = &alarm_buzzer {
+            alarm_buzzer
+                .send(BuzzerInput {
+                    state: stove_state_machine.current_state(),
+                    runaway: runaway_detector.is_detected(),
+                })
+                .ok();
+        }
 
-                log::error!("Sensor error: {:?}", e);
+        // only a pass that got all the way here feeds the watchdog
+        let report = LoopReport {
+            sensor_ok,
+            connected: mqtt_handler.is_connected(),
+        };
+        match health.check(Instant::now(), report) {
+            HealthDecision::Feed => {
+                if let Err(e) = watchdog.feed() {
+                    log::warn!("Failed to feed watchdog: {:?}", e);
+                }
+            }
+            HealthDecision::Reboot(reason) => health_store.reboot(reason),
+        }
+        if let Some(validator) = update_validator.as_mut() {
+            match validator.check(Instant::now(), report) {
+                ValidationDecision::Pending => {}
+                ValidationDecision::MarkValid => match ota.mark_running_slot_valid() {
+                    Ok(()) => {
+                        log::info!("Firmware {} validated", firmware_version);
+                        update_validator = None;
+                        log_publish_result(
+                            "firmware",
+                            mqtt_handler.publish_firmware(
+                                &firmware_version,
+                                &firmware_partition,
+                                false,
+                            ),
+                        );
+                    }
+                    Err(e) => log::warn!("Failed to mark firmware valid: {:?}", e),
+                },
+                ValidationDecision::RollBack => {
+                    log::error!(
+                        "Firmware {} failed validation, rolling back",
+                        firmware_version
+                    );
+                    let e = ota.mark_running_slot_invalid_and_reboot();
+                    log::error!("Rollback failed: {:?}", e);
+                }
+            }
+        }
+        if !crash_count_cleared && health.is_stable(Instant::now()) {
+            match health_store.clear_crash_count() {
+                Ok(()) => crash_count_cleared = true,
+                Err(e) => log::warn!("Failed to clear crash count: {:?}", e),
             }
         }
 
@@ -1,19 +1,62 @@
-use std::time::Duration;
+use std::{
+    sync::mpsc::{self, Receiver},
+    time::Duration,
+};
 
 use esp_idf_svc::{
-    mqtt::client::{EspMqttClient, MqttClientConfiguration, QoS},
+    mqtt::client::{EspMqttClient, EventPayload, MqttClientConfiguration, QoS},
     sys::EspError,
 };
-use woodstove_lib::Temperature;
+use woodstove_lib::{
+    alarm::Alarm,
+    catalyst::{CatalystAdvice, CatalystMonitor},
+    channel::ChannelReader,
+    creosote::{CreosoteNotice, CreosoteTracker},
+    energy::EnergyTotals,
+    filter::FilterStats,
+    health::ResetReason,
+    prediction::Prediction,
+    temperature::{RateOfChange, TemperatureUnit},
+    EnergyTracker, Temperature,
+};
+
+use crate::command::Command;
 
 const TEMP_TOPIC: &str = "woodstove/temperature";
+const UNIT_TOPIC: &str = "woodstove/unit";
+const READING_TOPIC: &str = "woodstove/reading";
 const STATE_TOPIC: &str = "woodstove/state";
 const TIME_IN_STATE_TOPIC: &str = "woodstove/time_in_state";
 const STATUS_TOPIC: &str = "woodstove/status";
 const ERROR_TOPIC: &str = "woodstove/error";
+const ENERGY_TOPIC: &str = "woodstove/energy";
+const ALARM_TOPIC: &str = "woodstove/alarm";
+const TIME_TO_RELOAD_TOPIC: &str = "woodstove/predicted_time_to_reload";
+const TIME_TO_IDLE_TOPIC: &str = "woodstove/predicted_time_to_idle";
+const FILTER_TOPIC: &str = "woodstove/filter";
+const HEALTH_TOPIC: &str = "woodstove/health";
+const FIRMWARE_TOPIC: &str = "woodstove/firmware";
+const COMMAND_TOPIC: &str = "woodstove/command";
+const CATALYST_TOPIC: &str = "woodstove/catalyst";
+const CATALYST_ADVICE_TOPIC: &str = "woodstove/catalyst/advice";
+const CREOSOTE_TOPIC: &str = "woodstove/creosote";
+const CREOSOTE_NOTICE_TOPIC: &str = "woodstove/creosote/notice";
+// followed by the channel name
+const CHANNEL_TOPIC: &str = "woodstove/channel";
+
+// Messages forwarded from the client's event callback to the main loop
+enum Inbound {
+    Connected,
+    Disconnected,
+    Command(Command),
+}
 
 pub struct WoodstoveMQTT<'a> {
     client: EspMqttClient<'a>,
+    inbound: Receiver<Inbound>,
+    connected: bool,
+    // what plain temperature topics are published in
+    unit: TemperatureUnit,
 }
 
 impl<'a> WoodstoveMQTT<'a> {
@@ -22,6 +65,7 @@ impl<'a> WoodstoveMQTT<'a> {
         mqtt_endpoint: &str,
         username: &str,
         password: &str,
+        unit: TemperatureUnit,
     ) -> Result<Self, EspError> {
         let config = MqttClientConfiguration {
             client_id: Some(client_id),
@@ -30,17 +74,188 @@ impl<'a> WoodstoveMQTT<'a> {
             ..Default::default()
         };
 
-        let (client, _) = EspMqttClient::new(mqtt_endpoint, &config)?;
+        let (tx, rx) = mpsc::channel();
+        let client = EspMqttClient::new_cb(mqtt_endpoint, &config, move |event| {
+            let inbound = match event.payload() {
+                EventPayload::Connected(_) => Some(Inbound::Connected),
+                EventPayload::Disconnected => Some(Inbound::Disconnected),
+                EventPayload::Received {
+                    topic: Some(COMMAND_TOPIC),
+                    data,
+                    ..
+                } => {
+                    let command = Command::parse(data);
+                    if command.is_none() {
+                        log::warn!("Ignoring unknown command: {:?}", data);
+                    }
+                    command.map(Inbound::Command)
+                }
+                _ => None,
+            };
+
+            if let Some(inbound) = inbound {
+                tx.send(inbound).ok();
+            }
+        })?;
+
+        Ok(Self {
+            client,
+            inbound: rx,
+            connected: false,
+            unit,
+        })
+    }
+
+    // Handles connection events since the last call and returns any commands received.
+    // The command topic is (re)subscribed here, as subscribing needs a live connection.
+    pub fn poll_commands(&mut self) -> Vec<Command> {
+        let mut commands = Vec::new();
+
+        while let Ok(inbound) = self.inbound.try_recv() {
+            match inbound {
+                Inbound::Connected => {
+                    self.connected = true;
+                    if let Err(e) = self.client.subscribe(COMMAND_TOPIC, QoS::AtLeastOnce) {
+                        log::warn!("Failed to subscribe to commands: {:?}", e);
+                    }
+                }
+                Inbound::Disconnected => self.connected = false,
+                Inbound::Command(command) => commands.push(command),
+            }
+        }
+
+        commands
+    }
 
-        Ok(Self { client })
+    pub fn is_connected(&self) -> bool {
+        self.connected
     }
 
+    // the plain number, in the display unit
     pub fn publish_temperature(&mut self, temp: &Temperature) -> Result<u32, EspError> {
         self.client.publish(
             TEMP_TOPIC,
             QoS::AtMostOnce,
             false, // temp is constantly changing, so we *don't* want it retained
-            temp.fahrenheit().to_string().as_bytes(),
+            temp.in_unit(self.unit).to_string().as_bytes(),
+        )
+    }
+
+    // the symbol of the display unit, for whatever reads the plain topics
+    pub fn publish_unit(&mut self) -> Result<u32, EspError> {
+        self.client.publish(
+            UNIT_TOPIC,
+            QoS::AtLeastOnce,
+            true,
+            self.unit.symbol().as_bytes(),
+        )
+    }
+
+    // temperature and rate in canonical units, tagged like the library's serde format, plus
+    // the same in the display unit
+    pub fn publish_reading(
+        &mut self,
+        temp: &Temperature,
+        rate: Option<RateOfChange>,
+    ) -> Result<u32, EspError> {
+        // null until there is a rate estimate
+        let (rate, rate_per_min) = match rate {
+            Some(r) => (
+                format!("{{\"c_per_s\":{:.4}}}", r.celsius_per_second()),
+                format!("{:.2}", r.per_minute_in(self.unit)),
+            ),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        let payload = format!(
+            "{{\"temperature\":{{\"c\":{:.2}}},\"rate\":{},\"display\":{{\"unit\":\"{}\",\"temperature\":{:.1},\"rate_per_min\":{}}}}}",
+            temp.celsius(),
+            rate,
+            self.unit.symbol(),
+            temp.in_unit(self.unit),
+            rate_per_min,
+        );
+
+        self.client
+            .publish(READING_TOPIC, QoS::AtMostOnce, false, payload.as_bytes())
+    }
+
+    // one thermocouple channel, in canonical units plus the display unit; the temperature is
+    // null while the channel is faulted or before its first good reading
+    pub fn publish_channel(&mut self, name: &str, reader: &ChannelReader) -> Result<u32, EspError> {
+        let (temperature, display) = match reader.temperature() {
+            Some(t) => (
+                format!("{{\"c\":{:.2}}}", t.celsius()),
+                format!("{:.1}", t.in_unit(self.unit)),
+            ),
+            None => ("null".to_string(), "null".to_string()),
+        };
+        let payload = format!(
+            "{{\"faulted\":{},\"temperature\":{},\"display\":{{\"unit\":\"{}\",\"temperature\":{}}}}}",
+            reader.is_faulted(),
+            temperature,
+            self.unit.symbol(),
+            display,
+        );
+
+        self.client.publish(
+            &format!("{}/{}", CHANNEL_TOPIC, name),
+            QoS::AtMostOnce,
+            false,
+            payload.as_bytes(),
+        )
+    }
+
+    pub fn publish_catalyst(&mut self, catalyst: &CatalystMonitor) -> Result<u32, EspError> {
+        let payload = format!(
+            "{{\"state\":\"{}\",\"hours\":{:.1},\"replacement_due\":{}}}",
+            catalyst.state(),
+            catalyst.catalyst_hours(),
+            catalyst.replacement_due(),
+        );
+
+        self.client.publish(
+            CATALYST_TOPIC,
+            QoS::AtLeastOnce,
+            true, // keeps the hours visible between burns
+            payload.as_bytes(),
+        )
+    }
+
+    // sent once, when the advice first applies
+    pub fn publish_catalyst_advice(&mut self, advice: CatalystAdvice) -> Result<u32, EspError> {
+        self.client.publish(
+            CATALYST_ADVICE_TOPIC,
+            QoS::AtLeastOnce,
+            false,
+            advice.to_string().as_bytes(),
+        )
+    }
+
+    // creosote risk index, in weighted hours with the flue in the creosote band
+    pub fn publish_creosote(&mut self, creosote: &CreosoteTracker) -> Result<u32, EspError> {
+        let payload = format!(
+            "{{\"burn\":{:.2},\"rolling_30d\":{:.2},\"since_sweep\":{:.2},\"inspection_due\":{}}}",
+            creosote.burn(),
+            creosote.rolling(),
+            creosote.since_sweep(),
+            creosote.inspection_due(),
+        );
+
+        self.client.publish(
+            CREOSOTE_TOPIC,
+            QoS::AtLeastOnce,
+            true, // builds up over months, so keep it visible
+            payload.as_bytes(),
+        )
+    }
+
+    // sent once, when the index since the last sweep passes the threshold
+    pub fn publish_creosote_notice(&mut self, notice: CreosoteNotice) -> Result<u32, EspError> {
+        self.client.publish(
+            CREOSOTE_NOTICE_TOPIC,
+            QoS::AtLeastOnce,
+            false,
+            notice.to_string().as_bytes(),
         )
     }
 
@@ -62,6 +277,106 @@ impl<'a> WoodstoveMQTT<'a> {
         )
     }
 
+    pub fn publish_energy(&mut self, energy: &EnergyTracker) -> Result<u32, EspError> {
+        let payload = format!(
+            "{{\"session\":{},\"day\":{},\"session_active\":{}}}",
+            energy_json(&energy.session()),
+            energy_json(&energy.day()),
+            energy.session_active(),
+        );
+
+        self.client
+            .publish(ENERGY_TOPIC, QoS::AtMostOnce, false, payload.as_bytes())
+    }
+
+    pub fn publish_alarm(&mut self, alarm: Option<&Alarm>) -> Result<u32, EspError> {
+        let payload = match alarm {
+            Some(alarm) => format!(
+                "{{\"active\":true,\"cause\":\"{}\",\"severity\":\"{}\",\"acknowledged\":{},\"notifications\":{}}}",
+                alarm.cause, alarm.severity, alarm.acknowledged, alarm.notifications,
+            ),
+            None => "{\"active\":false}".to_string(),
+        };
+
+        self.client.publish(
+            ALARM_TOPIC,
+            QoS::AtLeastOnce,
+            true, // the alarm stays visible until it clears
+            payload.as_bytes(),
+        )
+    }
+
+    pub fn publish_predictions(
+        &mut self,
+        time_to_reload: Option<Prediction>,
+        time_to_idle: Option<Prediction>,
+    ) -> Result<u32, EspError> {
+        self.client.publish(
+            TIME_TO_RELOAD_TOPIC,
+            QoS::AtMostOnce,
+            false,
+            prediction_json(time_to_reload).as_bytes(),
+        )?;
+        self.client.publish(
+            TIME_TO_IDLE_TOPIC,
+            QoS::AtMostOnce,
+            false,
+            prediction_json(time_to_idle).as_bytes(),
+        )
+    }
+
+    pub fn publish_filter_stats(&mut self, stats: FilterStats) -> Result<u32, EspError> {
+        let payload = format!(
+            "{{\"accepted\":{},\"rejected\":{},\"rejected_slew\":{},\"rejected_outlier\":{}}}",
+            stats.accepted,
+            stats.rejected(),
+            stats.rejected_slew,
+            stats.rejected_outlier,
+        );
+
+        self.client
+            .publish(FILTER_TOPIC, QoS::AtMostOnce, false, payload.as_bytes())
+    }
+
+    // why the device last started, and how many crashes in a row led up to it
+    pub fn publish_health(
+        &mut self,
+        last_reset_reason: ResetReason,
+        crash_count: u32,
+    ) -> Result<u32, EspError> {
+        let payload = format!(
+            "{{\"last_reset_reason\":\"{}\",\"crash_count\":{}}}",
+            last_reset_reason, crash_count,
+        );
+
+        self.client.publish(
+            HEALTH_TOPIC,
+            QoS::AtLeastOnce,
+            true, // only changes on boot
+            payload.as_bytes(),
+        )
+    }
+
+    // the running firmware, the OTA slot it runs from, and whether it has yet to prove itself
+    pub fn publish_firmware(
+        &mut self,
+        version: &str,
+        partition: &str,
+        pending_validation: bool,
+    ) -> Result<u32, EspError> {
+        let payload = format!(
+            "{{\"version\":\"{}\",\"partition\":\"{}\",\"pending_validation\":{}}}",
+            version, partition, pending_validation,
+        );
+
+        self.client.publish(
+            FIRMWARE_TOPIC,
+            QoS::AtLeastOnce,
+            true, // only changes on boot and once validated
+            payload.as_bytes(),
+        )
+    }
+
     pub fn publish_status(&mut self) -> Result<u32, EspError> {
         self.client
             .publish(STATUS_TOPIC, QoS::AtMostOnce, false, b"online")
@@ -72,3 +387,24 @@ impl<'a> WoodstoveMQTT<'a> {
             .publish(ERROR_TOPIC, QoS::AtMostOnce, false, error_msg.as_bytes())
     }
 }
+
+fn energy_json(totals: &EnergyTotals) -> String {
+    format!(
+        "{{\"kwh\":{:.3},\"btu\":{:.0},\"wood_kg\":{:.2}}}",
+        totals.heat_output.kilowatt_hours(),
+        totals.heat_output.btu(),
+        totals.wood_kg,
+    )
+}
+
+// null outside coaling, or before there is enough of the cool-down to fit
+fn prediction_json(prediction: Option<Prediction>) -> String {
+    match prediction {
+        Some(p) => format!(
+            "{{\"seconds\":{},\"confidence\":{:.2}}}",
+            p.remaining.as_secs(),
+            p.confidence,
+        ),
+        None => "null".to_string(),
+    }
+}
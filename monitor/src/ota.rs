@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use esp_idf_svc::{
+    http::{
+        client::{Configuration, EspHttpConnection},
+        Method,
+    },
+    ota::{EspOta, EspOtaUpdate},
+    sys,
+};
+use sha2::{Digest, Sha256};
+use woodstove_lib::ota::ImageDigest;
+
+const CHUNK_SIZE: usize = 4096;
+
+// Downloads the image at `url` into the idle OTA slot and, if it hashes to `expected`, makes it
+// the boot partition; the caller restarts into it. `progress` runs after every chunk, e.g. to
+// feed the watchdog.
+pub fn update(
+    ota: &mut EspOta,
+    url: &str,
+    expected: &ImageDigest,
+    mut progress: impl FnMut(),
+) -> anyhow::Result<()> {
+    if !url.starts_with("https://") {
+        bail!("refusing to update from a non-HTTPS URL");
+    }
+
+    let mut conn = EspHttpConnection::new(&Configuration {
+        crt_bundle_attach: Some(sys::esp_crt_bundle_attach),
+        timeout: Some(Duration::from_secs(30)),
+        ..Default::default()
+    })?;
+    conn.initiate_request(Method::Get, url, &[])?;
+    conn.initiate_response()?;
+    if conn.status() != 200 {
+        bail!("download failed with HTTP {}", conn.status());
+    }
+
+    let mut update = ota.initiate_update()?;
+    let downloaded = download(&mut conn, &mut update, &mut progress).and_then(|(size, digest)| {
+        if digest != *expected {
+            bail!("image has SHA-256 {}, expected {}", digest, expected);
+        }
+        Ok(size)
+    });
+    match downloaded {
+        Ok(size) => {
+            // checks the image before switching the boot partition to it
+            update.complete()?;
+            log::info!("Installed {} byte firmware image", size);
+            Ok(())
+        }
+        Err(e) => {
+            update.abort().ok();
+            Err(e)
+        }
+    }
+}
+
+fn download(
+    conn: &mut EspHttpConnection,
+    update: &mut EspOtaUpdate<'_>,
+    progress: &mut impl FnMut(),
+) -> anyhow::Result<(usize, ImageDigest)> {
+    // on the heap, the main task stack is small
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut size = 0;
+    let mut hasher = Sha256::new();
+    loop {
+        let read = conn.read(&mut buf)?;
+        if read == 0 {
+            return Ok((size, ImageDigest(hasher.finalize().into())));
+        }
+        update.write(&buf[..read])?;
+        hasher.update(&buf[..read]);
+        size += read;
+        progress();
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use esp_idf_svc::{
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    sys::{self, EspError},
+};
+use woodstove_lib::{creosote::CreosoteHistory, health::ResetReason, snapshot::Snapshot};
+
+const NAMESPACE: &str = "stove";
+const SNAPSHOT_KEY: &str = "snapshot";
+const CATALYST_KEY: &str = "catalyst_s";
+const CREOSOTE_KEY: &str = "creosote_hist";
+
+// The state machine snapshot, kept in NVS so a crash mid-burn doesn't start over from idle
+pub struct SnapshotStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl SnapshotStore {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self, EspError> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, NAMESPACE, true)?,
+        })
+    }
+
+    pub fn save(&mut self, snapshot: &Snapshot) -> Result<(), EspError> {
+        self.nvs.set_blob(SNAPSHOT_KEY, &snapshot.encode())
+    }
+
+    // the stored snapshot, if there is one and it decodes
+    pub fn load(&mut self) -> Result<Option<Snapshot>, EspError> {
+        let mut buf = [0u8; Snapshot::ENCODED_LEN];
+        let Some(bytes) = self.nvs.get_blob(SNAPSHOT_KEY, &mut buf)? else {
+            return Ok(None);
+        };
+        match Snapshot::decode(bytes) {
+            Ok(snapshot) => Ok(Some(snapshot)),
+            Err(e) => {
+                log::warn!("Ignoring stored snapshot: {}", e);
+                Ok(None)
+            }
+        }
+    }
+}
+
+// Catalyst hours, kept in NVS as seconds so replacement scheduling survives reboots
+pub struct CatalystStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl CatalystStore {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self, EspError> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, NAMESPACE, true)?,
+        })
+    }
+
+    pub fn save(&mut self, catalyst_time: Duration) -> Result<(), EspError> {
+        self.nvs.set_u64(CATALYST_KEY, catalyst_time.as_secs())
+    }
+
+    // zero on a fresh device
+    pub fn load(&mut self) -> Result<Duration, EspError> {
+        Ok(Duration::from_secs(
+            self.nvs.get_u64(CATALYST_KEY)?.unwrap_or(0),
+        ))
+    }
+}
+
+// The creosote index since the chimney was last swept and the days of the rolling index, kept
+// in NVS as they build up over months
+pub struct CreosoteStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl CreosoteStore {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self, EspError> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, NAMESPACE, true)?,
+        })
+    }
+
+    pub fn save(&mut self, history: &CreosoteHistory) -> Result<(), EspError> {
+        self.nvs.set_blob(CREOSOTE_KEY, &history.encode())
+    }
+
+    // the stored history, if there is one and it decodes
+    pub fn load(&mut self) -> Result<Option<CreosoteHistory>, EspError> {
+        let mut buf = [0u8; CreosoteHistory::ENCODED_LEN];
+        let Some(bytes) = self.nvs.get_blob(CREOSOTE_KEY, &mut buf)? else {
+            return Ok(None);
+        };
+        match CreosoteHistory::decode(bytes) {
+            Ok(history) => Ok(Some(history)),
+            Err(e) => {
+                log::warn!("Ignoring stored creosote history: {}", e);
+                Ok(None)
+            }
+        }
+    }
+}
+
+// The RTC timer, which keeps counting through software, panic, watchdog and brownout resets
+// but starts over on power-on or an external reset
+pub fn rtc_clock() -> Duration {
+    Duration::from_micros(unsafe { sys::esp_rtc_get_time_us() })
+}
+
+// whether the RTC clock kept running through this reset, so a snapshot's age can be trusted
+pub fn clock_survived(reason: ResetReason) -> bool {
+    reason.is_crash() || reason == ResetReason::Software
+}
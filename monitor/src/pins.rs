@@ -0,0 +1,5 @@
+// GPIOs the firmware drives itself, so pins only known at build time (the thermocouple chip
+// selects, the buzzer) can be checked against them before they're driven
+
+// the status LED, the display's I2C and the thermocouples' SPI bus
+pub const FIRMWARE: [i32; 6] = [8, 11, 12, 38, 47, 48];
@@ -0,0 +1,42 @@
+use esp_idf_svc::hal::{
+    gpio::{Gpio11, Gpio12},
+    i2c::{I2cConfig, I2cDriver, I2C0},
+    units::*,
+};
+use ssd1306::{mode::BufferedGraphicsMode, prelude::*, I2CDisplayInterface, Ssd1306};
+use woodstove_lib::display::{self, DisplayView};
+
+type Display = Ssd1306<
+    I2CInterface<I2cDriver<'static>>,
+    DisplaySize128x64,
+    BufferedGraphicsMode<DisplaySize128x64>,
+>;
+
+// The SSD1306 OLED next to the stove
+pub struct Screen {
+    display: Display,
+}
+
+impl Screen {
+    pub fn new(i2c: I2C0, sda: Gpio11, scl: Gpio12) -> anyhow::Result<Self> {
+        let i2c = I2cDriver::new(i2c, sda, scl, &I2cConfig::new().baudrate(400.kHz().into()))?;
+        let mut display = Ssd1306::new(
+            I2CDisplayInterface::new(i2c),
+            DisplaySize128x64,
+            DisplayRotation::Rotate0,
+        )
+        .into_buffered_graphics_mode();
+        display
+            .init()
+            .map_err(|e| anyhow::anyhow!("initializing display: {:?}", e))?;
+        Ok(Self { display })
+    }
+
+    pub fn show(&mut self, view: &DisplayView) {
+        // drawing only touches the buffer; sending it can fail
+        display::draw(view, &mut self.display).ok();
+        if let Err(e) = self.display.flush() {
+            log::warn!("Failed to update display: {:?}", e);
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use esp_idf_svc::{
+    hal::gpio::{AnyOutputPin, Output, PinDriver},
+    sys::EspError,
+};
+use woodstove_lib::channel::{usable_pin, ChannelList, ChannelReader};
+
+// what a board wired like the original single-probe build has
+const DEFAULT_CHANNELS: &str = "stove:21";
+
+type CsPin = PinDriver<'static, AnyOutputPin, Output>;
+
+// One named MAX31855 on the shared SPI bus
+pub struct Thermocouple {
+    pub name: &'static str,
+    pub cs: CsPin,
+    pub reader: ChannelReader,
+}
+
+// the channels from THERMOCOUPLE_CHANNELS and PRIMARY_CHANNEL, falling back to the default
+// channel / the first one when they don't make sense, or a chip select is on a pin the board
+// lacks or that's in `in_use`
+pub fn channel_list(
+    channels: Option<&'static str>,
+    primary: Option<&'static str>,
+    in_use: &[i32],
+) -> ChannelList<'static> {
+    let default = || ChannelList::parse(DEFAULT_CHANNELS).unwrap();
+    let parse =
+        |text| ChannelList::parse(text).and_then(|list| list.check_pins(in_use, usable_pin));
+    let list = match channels.map(parse) {
+        None => default(),
+        Some(Ok(list)) => list,
+        Some(Err(e)) => {
+            log::error!(
+                "Invalid THERMOCOUPLE_CHANNELS ({}), using {}",
+                e,
+                DEFAULT_CHANNELS
+            );
+            default()
+        }
+    };
+    match primary.map(|name| list.with_primary(name)) {
+        None => list,
+        Some(Ok(list)) => list,
+        Some(Err(e)) => {
+            log::error!(
+                "Invalid PRIMARY_CHANNEL ({}), using {}",
+                e,
+                list.channels()[list.primary()].name
+            );
+            list
+        }
+    }
+}
+
+pub fn setup(list: &ChannelList<'static>, fault_after: u32) -> Result<Vec<Thermocouple>, EspError> {
+    list.channels()
+        .iter()
+        .map(|spec| {
+            // the pins are only known at build time, so can't be taken from `peripherals`
+            let mut cs = PinDriver::output(unsafe { AnyOutputPin::new(spec.cs_pin) })?;
+            // deselected, so it stays off the shared data line until read
+            cs.set_high()?;
+            Ok(Thermocouple {
+                name: spec.name,
+                cs,
+                reader: ChannelReader::new(fault_after),
+            })
+        })
+        .collect()
+}
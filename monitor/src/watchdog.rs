@@ -0,0 +1,75 @@
+use esp_idf_svc::{
+    nvs::{EspDefaultNvsPartition, EspNvs, NvsDefault},
+    sys::{self, EspError},
+};
+use woodstove_lib::health::{RebootReason, ResetReason};
+
+const NAMESPACE: &str = "health";
+const CRASH_COUNT_KEY: &str = "crash_count";
+// set just before a supervisor reboot, so the next boot can tell it from any other restart
+const REBOOT_REASON_KEY: &str = "reboot_reason";
+
+// Reset reason and crash count kept in NVS across reboots
+pub struct HealthStore {
+    nvs: EspNvs<NvsDefault>,
+}
+
+impl HealthStore {
+    pub fn new(partition: EspDefaultNvsPartition) -> Result<Self, EspError> {
+        Ok(Self {
+            nvs: EspNvs::new(partition, NAMESPACE, true)?,
+        })
+    }
+
+    // works out why this boot happened and counts it; returns the reason and the new count
+    pub fn record_boot(&mut self) -> Result<(ResetReason, u32), EspError> {
+        let reason = match reset_reason() {
+            ResetReason::Software => self
+                .take_reboot_reason()?
+                .map_or(ResetReason::Software, ResetReason::Requested),
+            reason => reason,
+        };
+
+        let count = reason.next_crash_count(self.nvs.get_u32(CRASH_COUNT_KEY)?.unwrap_or(0));
+        self.nvs.set_u32(CRASH_COUNT_KEY, count)?;
+        Ok((reason, count))
+    }
+
+    pub fn clear_crash_count(&mut self) -> Result<(), EspError> {
+        self.nvs.set_u32(CRASH_COUNT_KEY, 0)
+    }
+
+    // records why and restarts; doesn't return
+    pub fn reboot(&mut self, reason: RebootReason) -> ! {
+        if let Err(e) = self.nvs.set_str(REBOOT_REASON_KEY, reason.name()) {
+            log::warn!("Failed to record reboot reason: {:?}", e);
+        }
+        log::error!("Rebooting: {}", reason);
+        esp_idf_svc::hal::reset::restart()
+    }
+
+    fn take_reboot_reason(&mut self) -> Result<Option<RebootReason>, EspError> {
+        let mut buf = [0u8; 32];
+        let reason = self
+            .nvs
+            .get_str(REBOOT_REASON_KEY, &mut buf)?
+            .and_then(|name| name.parse().ok());
+        self.nvs.remove(REBOOT_REASON_KEY)?;
+        Ok(reason)
+    }
+}
+
+fn reset_reason() -> ResetReason {
+    match unsafe { sys::esp_reset_reason() } {
+        sys::esp_reset_reason_t_ESP_RST_POWERON => ResetReason::PowerOn,
+        sys::esp_reset_reason_t_ESP_RST_EXT => ResetReason::External,
+        sys::esp_reset_reason_t_ESP_RST_SW => ResetReason::Software,
+        sys::esp_reset_reason_t_ESP_RST_PANIC => ResetReason::Panic,
+        sys::esp_reset_reason_t_ESP_RST_INT_WDT => ResetReason::InterruptWatchdog,
+        sys::esp_reset_reason_t_ESP_RST_TASK_WDT => ResetReason::TaskWatchdog,
+        sys::esp_reset_reason_t_ESP_RST_WDT => ResetReason::OtherWatchdog,
+        sys::esp_reset_reason_t_ESP_RST_BROWNOUT => ResetReason::Brownout,
+        sys::esp_reset_reason_t_ESP_RST_DEEPSLEEP => ResetReason::DeepSleep,
+        _ => ResetReason::Unknown,
+    }
+}
@@ -1,6 +1,6 @@
 use esp_idf_svc::{
     eventloop::EspSystemEventLoop,
-    hal::{delay::FreeRtos, modem::Modem},
+    hal::modem::Modem,
     nvs::EspDefaultNvsPartition,
     sys::EspError,
     wifi::{BlockingWifi, ClientConfiguration, Configuration, EspWifi},
@@ -11,9 +11,13 @@ pub struct WifiHandler<'a> {
 }
 
 impl<'a> WifiHandler<'a> {
-    pub fn new(modem: Modem, wifi_ssid: &str, wifi_password: &str) -> Result<Self, EspError> {
+    pub fn new(
+        modem: Modem,
+        nvs: EspDefaultNvsPartition,
+        wifi_ssid: &str,
+        wifi_password: &str,
+    ) -> Result<Self, EspError> {
         let sys_loop = EspSystemEventLoop::take()?;
-        let nvs = EspDefaultNvsPartition::take()?;
 
         let mut wifi = BlockingWifi::wrap(
             EspWifi::new(modem, sys_loop.clone(), Some(nvs))?,
@@ -31,11 +35,14 @@ impl<'a> WifiHandler<'a> {
         Ok(Self { wifi: wifi })
     }
 
+    // starts joining the network without waiting for it, so the sensor and alarms carry on
+    // meanwhile; `is_connected` tells when it's up, and a failed attempt is retried by calling
+    // this again
     pub fn connect(&mut self) -> Result<(), EspError> {
-        self.wifi.connect()?;
+        self.wifi.wifi_mut().connect()
+    }
 
-        // give the connection a moment
-        FreeRtos::delay_ms(10_000);
-        Ok(())
+    pub fn is_connected(&self) -> bool {
+        self.wifi.is_connected().unwrap_or(false)
     }
 }
@@ -0,0 +1,133 @@
+// k-means over (temperature °F, rate °F/min) points, scaled so both axes count equally
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Point {
+    pub temp_f: f32,
+    pub rate_f_per_min: f32,
+}
+
+const MAX_ITERATIONS: usize = 100;
+
+// Assigns every point to one of `initial.len()` clusters, starting from the given centroids.
+// Returns the cluster index of each point.
+pub fn kmeans(points: &[Point], initial: &[Point]) -> Vec<usize> {
+    let (temp_scale, rate_scale) = (
+        spread(points, |p| p.temp_f),
+        spread(points, |p| p.rate_f_per_min),
+    );
+    let scaled = |p: &Point| (p.temp_f / temp_scale, p.rate_f_per_min / rate_scale);
+
+    let mut centroids: Vec<(f32, f32)> = initial.iter().map(scaled).collect();
+    let mut assignment = vec![usize::MAX; points.len()];
+
+    for _ in 0..MAX_ITERATIONS {
+        let mut moved = false;
+        for (slot, point) in assignment.iter_mut().zip(points) {
+            let (t, r) = scaled(point);
+            let nearest = centroids
+                .iter()
+                .enumerate()
+                .map(|(i, (ct, cr))| (i, (t - ct).powi(2) + (r - cr).powi(2)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map(|(i, _)| i)
+                .unwrap();
+            if *slot != nearest {
+                *slot = nearest;
+                moved = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+
+        for (i, centroid) in centroids.iter_mut().enumerate() {
+            let members: Vec<(f32, f32)> = points
+                .iter()
+                .zip(&assignment)
+                .filter(|(_, a)| **a == i)
+                .map(|(p, _)| scaled(p))
+                .collect();
+            // an empty cluster keeps its centroid
+            if !members.is_empty() {
+                let n = members.len() as f32;
+                *centroid = (
+                    members.iter().map(|m| m.0).sum::<f32>() / n,
+                    members.iter().map(|m| m.1).sum::<f32>() / n,
+                );
+            }
+        }
+    }
+
+    assignment
+}
+
+// standard deviation, or 1 for a flat axis so it doesn't divide by zero
+fn spread(points: &[Point], axis: impl Fn(&Point) -> f32) -> f32 {
+    let n = points.len() as f32;
+    let mean = points.iter().map(&axis).sum::<f32>() / n;
+    let variance = points.iter().map(|p| (axis(p) - mean).powi(2)).sum::<f32>() / n;
+    if variance > 0.0 { variance.sqrt() } else { 1.0 }
+}
+
+// the value `fraction` of the way through the sorted values
+pub fn percentile(values: &[f32], fraction: f32) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let index = ((sorted.len() - 1) as f32 * fraction.clamp(0.0, 1.0)).round() as usize;
+    sorted[index]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn point(temp_f: f32, rate_f_per_min: f32) -> Point {
+        Point {
+            temp_f,
+            rate_f_per_min,
+        }
+    }
+
+    #[test]
+    fn separates_obvious_groups() {
+        let points = [
+            point(70.0, 0.0),
+            point(72.0, 0.5),
+            point(500.0, 0.0),
+            point(510.0, -0.5),
+            point(300.0, 20.0),
+            point(310.0, 25.0),
+        ];
+
+        let assignment = kmeans(
+            &points,
+            &[point(70.0, 0.0), point(500.0, 0.0), point(300.0, 20.0)],
+        );
+
+        assert_eq!(assignment, vec![0, 0, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn rate_counts_as_much_as_temperature() {
+        // same temperature, told apart only by how fast it's moving
+        let points = [
+            point(300.0, -8.0),
+            point(305.0, -9.0),
+            point(300.0, 15.0),
+            point(295.0, 14.0),
+        ];
+
+        let assignment = kmeans(&points, &[point(300.0, -8.0), point(300.0, 15.0)]);
+
+        assert_eq!(assignment, vec![0, 0, 1, 1]);
+    }
+
+    #[test]
+    fn percentiles() {
+        let values = [5.0, 1.0, 3.0, 2.0, 4.0];
+
+        assert_eq!(percentile(&values, 0.0), 1.0);
+        assert_eq!(percentile(&values, 0.5), 3.0);
+        assert_eq!(percentile(&values, 1.0), 5.0);
+    }
+}
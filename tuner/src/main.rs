@@ -0,0 +1,103 @@
+// Proposes a StoveConfig tuned to one stove from recorded burns, and shows how it would classify
+// them compared with the current config.
+//
+//   woodstove_tuner [--current <config>] [--out <config>] <trace.csv>...
+//
+// Traces are CSV files of `seconds,fahrenheit` readings covering whole burns, cold to cold.
+
+mod cluster;
+mod report;
+mod trace;
+mod tune;
+
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use woodstove_lib::state_machine::StoveConfig;
+
+use trace::Trace;
+
+struct Args {
+    current: Option<PathBuf>,
+    out: Option<PathBuf>,
+    traces: Vec<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        current: None,
+        out: None,
+        traces: Vec::new(),
+    };
+    let mut iter = std::env::args().skip(1);
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--current" => args.current = Some(iter.next().ok_or("--current needs a path")?.into()),
+            "--out" => args.out = Some(iter.next().ok_or("--out needs a path")?.into()),
+            "-h" | "--help" => return Err(String::new()),
+            _ => args.traces.push(arg.into()),
+        }
+    }
+
+    if args.traces.is_empty() {
+        return Err("no traces given".to_string());
+    }
+    Ok(args)
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let current = match &args.current {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?
+            .parse::<StoveConfig>()
+            .map_err(|e| format!("{}: {}", path.display(), e))?,
+        None => StoveConfig::default(),
+    };
+
+    let traces = args
+        .traces
+        .iter()
+        .map(|path| Trace::load(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let regimes = tune::find_regimes(&traces)?;
+    let proposal = tune::propose(&current, &regimes)?;
+
+    for trace in &traces {
+        eprintln!("{}", report::compare(trace, &current, &proposal.config));
+    }
+    for note in &proposal.notes {
+        eprintln!("note: {}", note);
+    }
+
+    let text = format!(
+        "# proposed by woodstove_tuner from {} trace(s)\n{}",
+        traces.len(),
+        proposal.config
+    );
+    match &args.out {
+        Some(path) => {
+            fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+            eprintln!("wrote {}", path.display());
+        }
+        None => print!("{}", text),
+    }
+
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let result = parse_args().and_then(run);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if !e.is_empty() {
+                eprintln!("error: {}", e);
+            }
+            eprintln!(
+                "usage: woodstove_tuner [--current <config>] [--out <config>] <trace.csv>..."
+            );
+            ExitCode::FAILURE
+        }
+    }
+}
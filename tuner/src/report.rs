@@ -0,0 +1,133 @@
+use std::{fmt::Write, time::Duration};
+
+use woodstove_lib::{
+    BurnState, StoveStateMachine, rate::EmaEstimator, state_machine::StoveConfig, time::Instant,
+};
+
+use crate::trace::Trace;
+
+// How a config classified one trace
+#[derive(Debug)]
+pub struct Replay {
+    // seconds spent in each state, in `BurnState::ALL` order
+    pub seconds_in: [f32; BurnState::ALL.len()],
+    // the states passed through, in order
+    pub path: Vec<BurnState>,
+}
+
+impl Replay {
+    pub fn seconds_in(&self, state: BurnState) -> f32 {
+        let index = BurnState::ALL.iter().position(|s| *s == state).unwrap();
+        self.seconds_in[index]
+    }
+}
+
+pub fn replay(trace: &Trace, config: &StoveConfig) -> Replay {
+    let mut sm = StoveStateMachine::with_estimator(config.clone(), EmaEstimator::new(0.3));
+    let start = Instant::ZERO;
+    let mut seconds_in = [0.0; BurnState::ALL.len()];
+    let mut path = vec![sm.current_state()];
+    let mut previous: Option<f32> = None;
+
+    for (seconds, temp) in &trace.samples {
+        // time up to this reading counts toward the state the machine was in
+        if let Some(previous) = previous {
+            let index = BurnState::ALL
+                .iter()
+                .position(|s| *s == sm.current_state())
+                .unwrap();
+            seconds_in[index] += seconds - previous;
+        }
+        previous = Some(*seconds);
+
+        sm.update_at(*temp, start + Duration::from_secs_f32(*seconds));
+        if path.last() != Some(&sm.current_state()) {
+            path.push(sm.current_state());
+        }
+    }
+
+    Replay { seconds_in, path }
+}
+
+// side by side minutes in each state, and the path through them, under both configs
+pub fn compare(trace: &Trace, current: &StoveConfig, proposed: &StoveConfig) -> String {
+    let before = replay(trace, current);
+    let after = replay(trace, proposed);
+    let mut out = String::new();
+
+    let _ = writeln!(
+        out,
+        "{} ({:.0} min)",
+        trace.name,
+        trace.duration_seconds() / 60.0
+    );
+    let _ = writeln!(out, "  {:<14} {:>9} {:>9}", "state", "current", "proposed");
+    for state in BurnState::ALL {
+        let (b, a) = (before.seconds_in(state), after.seconds_in(state));
+        if b > 0.0 || a > 0.0 {
+            let _ = writeln!(
+                out,
+                "  {:<14} {:>7.0}m {:>7.0}m",
+                state.to_string(),
+                b / 60.0,
+                a / 60.0
+            );
+        }
+    }
+    let _ = writeln!(out, "  current:  {}", path_string(&before.path));
+    let _ = writeln!(out, "  proposed: {}", path_string(&after.path));
+
+    out
+}
+
+fn path_string(path: &[BurnState]) -> String {
+    path.iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tune::{find_regimes, propose};
+
+    #[test]
+    fn default_config_follows_a_normal_burn() {
+        let replay = replay(&Trace::synthetic(1.0), &StoveConfig::default());
+
+        assert_eq!(replay.path.first(), Some(&BurnState::Idle));
+        assert!(replay.path.contains(&BurnState::Startup));
+        assert!(replay.seconds_in(BurnState::ActiveBurn) > 30.0 * 60.0);
+        assert!(replay.path.contains(&BurnState::Coaling));
+    }
+
+    #[test]
+    fn tuned_config_sees_the_burn_a_distant_probe_misses() {
+        let traces = [Trace::synthetic(0.55), Trace::synthetic(0.5)];
+        let current = StoveConfig::default();
+        let proposed = propose(&current, &find_regimes(&traces).unwrap())
+            .unwrap()
+            .config;
+
+        let before = replay(&traces[0], &current);
+        let after = replay(&traces[0], &proposed);
+
+        // the probe never reads 400°F, so the defaults never see an active burn
+        assert_eq!(before.seconds_in(BurnState::ActiveBurn), 0.0);
+        assert!(after.seconds_in(BurnState::ActiveBurn) > 30.0 * 60.0);
+        assert!(after.path.contains(&BurnState::Coaling));
+    }
+
+    #[test]
+    fn comparison_lists_both_configs() {
+        let trace = Trace::synthetic(1.0);
+        let config = StoveConfig::default();
+
+        let text = compare(&trace, &config, &config);
+
+        assert!(text.starts_with("synthetic x1 (260 min)"), "{}", text);
+        assert!(text.contains("active_burn"));
+        assert!(text.contains("proposed: idle -> startup"));
+    }
+}
@@ -0,0 +1,156 @@
+use std::{fs, path::Path};
+
+use woodstove_lib::Temperature;
+
+// far longer than any burn, and well inside what a `Duration` or `Instant` can hold
+const MAX_SECONDS: f32 = 366.0 * 24.0 * 60.0 * 60.0;
+
+// A recorded burn: readings as (seconds from the start, temperature)
+pub struct Trace {
+    pub name: String,
+    pub samples: Vec<(f32, Temperature)>,
+}
+
+impl Trace {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let name = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_else(|| path.display().to_string());
+        Trace::parse(name, &text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    // CSV of `seconds,fahrenheit`, one reading per line; a header line is skipped
+    pub fn parse(name: String, text: &str) -> Result<Self, String> {
+        let mut samples = Vec::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut fields = line.split(',').map(str::trim);
+            let parsed = match (fields.next(), fields.next()) {
+                (Some(seconds), Some(fahrenheit)) => seconds
+                    .parse::<f32>()
+                    .ok()
+                    .zip(fahrenheit.parse::<f32>().ok()),
+                _ => None,
+            };
+            match parsed {
+                // the tuner turns seconds into a `Duration`, which can't be negative or huge
+                Some((seconds, fahrenheit))
+                    if !((0.0..=MAX_SECONDS).contains(&seconds) && fahrenheit.is_finite()) =>
+                {
+                    return Err(format!(
+                        "line {}: seconds must be 0 to {} and readings finite",
+                        i + 1,
+                        MAX_SECONDS
+                    ));
+                }
+                Some((seconds, fahrenheit)) => {
+                    samples.push((seconds, Temperature::from_fahrenheit(fahrenheit)))
+                }
+                None if samples.is_empty() && i == 0 => continue, // header
+                None => return Err(format!("line {}: expected `seconds,fahrenheit`", i + 1)),
+            }
+        }
+
+        if samples.len() < 2 {
+            return Err("needs at least two readings".to_string());
+        }
+        if samples.windows(2).any(|w| w[1].0 <= w[0].0) {
+            return Err("readings must be in time order".to_string());
+        }
+
+        Ok(Trace { name, samples })
+    }
+
+    pub fn duration_seconds(&self) -> f32 {
+        self.samples.last().map(|s| s.0).unwrap_or(0.0) - self.samples[0].0
+    }
+
+    // a whole burn every 10 seconds: cold, lit, burning, coaling and cold again, with the
+    // probe seeing `scale` of the heat above room temperature
+    #[cfg(test)]
+    pub fn synthetic(scale: f32) -> Self {
+        const AMBIENT: f32 = 70.0;
+        let peak = 480.0 * scale;
+        let mut noise: u32 = 7;
+
+        let samples = (0..=260 * 6)
+            .map(|i| {
+                let minute = i as f32 / 6.0;
+                let excess = if minute < 20.0 {
+                    0.0
+                } else if minute < 35.0 {
+                    peak * (minute - 20.0) / 15.0
+                } else if minute < 95.0 {
+                    peak + 10.0 * scale * (minute * std::f32::consts::TAU / 20.0).sin()
+                } else {
+                    peak * (-(minute - 95.0) * 60.0 / 3000.0).exp()
+                };
+                noise = noise.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                let jitter = ((noise >> 8) as f32 / (1u32 << 24) as f32 - 0.5) * 2.0;
+                (
+                    minute * 60.0,
+                    Temperature::from_fahrenheit(AMBIENT + excess + jitter),
+                )
+            })
+            .collect();
+
+        Trace {
+            name: format!("synthetic x{}", scale),
+            samples,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_csv_with_header() {
+        let trace = Trace::parse(
+            "burn".to_string(),
+            "seconds,fahrenheit\n0,70.5\n10,71\n\n20,72\n",
+        )
+        .unwrap();
+
+        assert_eq!(trace.samples.len(), 3);
+        assert_eq!(trace.samples[2].0, 20.0);
+        assert_eq!(trace.duration_seconds(), 20.0);
+    }
+
+    #[test]
+    fn rejects_bad_lines() {
+        let err = Trace::parse("burn".to_string(), "0,70\n10,warm\n")
+            .err()
+            .unwrap();
+
+        assert!(err.starts_with("line 2"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_out_of_order_readings() {
+        assert!(Trace::parse("burn".to_string(), "0,70\n20,71\n10,72\n").is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_and_non_finite_values() {
+        for (text, line) in [
+            ("-10,70\n0,80\n10,90\n", 1),
+            ("0,70\nNaN,80\n10,90\n", 2),
+            ("0,70\n10,80\ninf,90\n", 3),
+            ("0,70\n10,inf\n", 2),
+            ("seconds,fahrenheit\n0,70\n10,NaN\n", 3),
+            ("0,70\n1e20,80\n", 2),
+        ] {
+            let err = Trace::parse("burn".to_string(), text).err().unwrap();
+
+            assert!(err.starts_with(&format!("line {}:", line)), "{}", err);
+        }
+    }
+}
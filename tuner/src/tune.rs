@@ -0,0 +1,247 @@
+use std::time::Duration;
+
+use woodstove_lib::{
+    Temperature,
+    rate::{EmaEstimator, RateEstimator},
+    state_machine::StoveConfig,
+    temperature::{RateOfChange, TemperatureDelta},
+    time::Instant,
+};
+
+use crate::{
+    cluster::{Point, kmeans, percentile},
+    trace::Trace,
+};
+
+// fewer readings than this in a regime says more about the traces than about the stove
+const MIN_REGIME_POINTS: usize = 10;
+
+// Readings grouped by what the fire was doing
+#[derive(Debug)]
+pub struct Regimes {
+    pub idle: Vec<Point>,
+    pub rising: Vec<Point>,
+    pub active: Vec<Point>,
+    pub falling: Vec<Point>,
+}
+
+// each reading with the rate the device would have estimated for it
+pub fn rated_points(trace: &Trace) -> Vec<Point> {
+    let mut estimator = EmaEstimator::new(0.3);
+    let start = Instant::ZERO;
+
+    trace
+        .samples
+        .iter()
+        .filter_map(|(seconds, temp)| {
+            estimator.update(start + Duration::from_secs_f32(*seconds), *temp);
+            estimator.rate().map(|rate| Point {
+                temp_f: temp.fahrenheit(),
+                rate_f_per_min: rate.fahrenheit_per_minute(),
+            })
+        })
+        .collect()
+}
+
+// clusters every reading into cold, heating up, burning and dying down
+pub fn find_regimes(traces: &[Trace]) -> Result<Regimes, String> {
+    let points: Vec<Point> = traces.iter().flat_map(rated_points).collect();
+    if points.is_empty() {
+        return Err("no readings".to_string());
+    }
+
+    // start each cluster from the most extreme reading of its kind
+    let extreme = |key: fn(&Point) -> f32| {
+        *points
+            .iter()
+            .max_by(|a, b| key(a).total_cmp(&key(b)))
+            .unwrap()
+    };
+    let initial = [
+        extreme(|p| -p.temp_f),
+        extreme(|p| p.rate_f_per_min),
+        extreme(|p| p.temp_f),
+        extreme(|p| -p.rate_f_per_min),
+    ];
+    let assignment = kmeans(&points, &initial);
+
+    let members = |cluster: usize| -> Vec<Point> {
+        points
+            .iter()
+            .zip(&assignment)
+            .filter(|(_, a)| **a == cluster)
+            .map(|(p, _)| *p)
+            .collect()
+    };
+    let regimes = Regimes {
+        idle: members(0),
+        rising: members(1),
+        active: members(2),
+        falling: members(3),
+    };
+
+    let counts = [
+        ("cold", regimes.idle.len()),
+        ("heating up", regimes.rising.len()),
+        ("burning", regimes.active.len()),
+        ("dying down", regimes.falling.len()),
+    ];
+    if let Some((name, _)) = counts.iter().find(|(_, n)| *n < MIN_REGIME_POINTS) {
+        return Err(format!(
+            "too few readings of the stove {}; the traces should cover whole burns, from cold to cold",
+            name
+        ));
+    }
+
+    Ok(regimes)
+}
+
+fn temps(points: &[Point]) -> Vec<f32> {
+    points.iter().map(|p| p.temp_f).collect()
+}
+
+fn rates(points: &[Point]) -> Vec<f32> {
+    points.iter().map(|p| p.rate_f_per_min).collect()
+}
+
+fn fahrenheit(f: f32) -> Temperature {
+    Temperature::from_fahrenheit(f)
+}
+
+fn per_minute(f: f32) -> RateOfChange {
+    RateOfChange::new_per_minute(TemperatureDelta::from_fahrenheit(f), 1.0)
+}
+
+// A config tuned to the regimes, with notes on anything left as it was
+pub struct Proposal {
+    pub config: StoveConfig,
+    pub notes: Vec<String>,
+}
+
+pub fn propose(current: &StoveConfig, regimes: &Regimes) -> Result<Proposal, String> {
+    let idle = temps(&regimes.idle);
+    let rising = temps(&regimes.rising);
+    let active = temps(&regimes.active);
+    let falling = temps(&regimes.falling);
+    let mut notes = Vec::new();
+
+    // the cold regime also takes in the slow end of each cool-down, so room temperature is
+    // its coldest readings
+    let ambient = percentile(&idle, 0.05);
+    // between the warmest the stove sits when cold and where fires get going
+    let idle_threshold = (percentile(&idle, 0.9) + percentile(&rising, 0.1)) / 2.0;
+    let active_threshold = percentile(&active, 0.25);
+    let active_exit = percentile(&active, 0.1).min(active_threshold - 20.0);
+    // half way down the cool-down is about when there's room for more wood
+    let reload = percentile(&falling, 0.5);
+    let coals_out = percentile(&falling, 0.1);
+
+    let rising_fast = ((percentile(&rates(&regimes.active), 0.9)
+        + percentile(&rates(&regimes.rising), 0.5))
+        / 2.0)
+        .max(1.0);
+    let falling_rate = ((percentile(&rates(&regimes.active), 0.1)
+        + percentile(&rates(&regimes.falling), 0.5))
+        / 2.0)
+        .min(-0.5);
+
+    let mut config = StoveConfig {
+        ambient: fahrenheit(ambient),
+        idle_threshold: fahrenheit(idle_threshold),
+        coals_out_threshold: fahrenheit(coals_out),
+        reload_threshold: fahrenheit(reload),
+        active_exit_threshold: fahrenheit(active_exit),
+        active_threshold: fahrenheit(active_threshold),
+        rising_fast_rate: per_minute(rising_fast),
+        falling_rate: per_minute(falling_rate),
+        ..current.clone()
+    };
+
+    // a door-open drop has to stay well clear of an ordinary cool-down
+    if config.falling_fast_rate >= config.falling_rate {
+        config.falling_fast_rate = per_minute(falling_rate * 3.0);
+        notes.push("falling_fast_rate moved below the new falling_rate".to_string());
+    }
+
+    // normal burns never overheat, so there's nothing to learn those from
+    notes.push(format!(
+        "overheat thresholds kept at {:.0}F / {:.0}F",
+        current.overheat_threshold.fahrenheit(),
+        current.overheat_exit_threshold.fahrenheit()
+    ));
+    let hottest = percentile(&active, 0.99);
+    if fahrenheit(hottest) >= config.overheat_exit_threshold {
+        notes.push(format!(
+            "normal burns reach {:.0}F, above overheat_exit_threshold; overheat may trigger on them",
+            hottest
+        ));
+    }
+
+    config
+        .validate()
+        .map_err(|e| format!("proposed config is inconsistent: {}", e))?;
+    Ok(Proposal { config, notes })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proposes_a_valid_config_for_the_default_stove() {
+        let traces = [Trace::synthetic(1.0), Trace::synthetic(0.9)];
+
+        let regimes = find_regimes(&traces).unwrap();
+        let proposal = propose(&StoveConfig::default(), &regimes).unwrap();
+
+        let config = proposal.config;
+        assert!((config.ambient.fahrenheit() - 70.0).abs() < 5.0);
+        assert!(config.active_threshold.fahrenheit() > 400.0);
+        assert!(config.idle_threshold < config.active_threshold);
+    }
+
+    #[test]
+    fn lowers_thresholds_for_a_distant_probe() {
+        // the probe sees a bit over half of the heat above room temperature
+        let traces = [Trace::synthetic(0.55), Trace::synthetic(0.5)];
+
+        let regimes = find_regimes(&traces).unwrap();
+        let proposal = propose(&StoveConfig::default(), &regimes).unwrap();
+
+        let config = proposal.config;
+        assert!(
+            config.active_threshold.fahrenheit() < 330.0,
+            "{}",
+            config.active_threshold.fahrenheit()
+        );
+        assert!(config.reload_threshold.fahrenheit() < 250.0);
+    }
+
+    #[test]
+    fn keeps_overheat_thresholds() {
+        let current = StoveConfig::default();
+        let regimes = find_regimes(&[Trace::synthetic(1.0)]).unwrap();
+
+        let proposal = propose(&current, &regimes).unwrap();
+
+        assert_eq!(
+            proposal.config.overheat_threshold,
+            current.overheat_threshold
+        );
+        assert!(proposal.notes.iter().any(|n| n.contains("overheat")));
+    }
+
+    #[test]
+    fn needs_whole_burns() {
+        // just the stove sitting cold
+        let trace = Trace::parse(
+            "cold".to_string(),
+            &(0..100)
+                .map(|i| format!("{},70\n", i * 10))
+                .collect::<String>(),
+        )
+        .unwrap();
+
+        assert!(find_regimes(&[trace]).is_err());
+    }
+}
@@ -0,0 +1,511 @@
+use core::{fmt::Display, time::Duration};
+
+use crate::{
+    state_machine::BurnState,
+    temperature::{RateOfChange, Temperature, TemperatureDelta},
+    time::Instant,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Critical,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Critical => write!(f, "critical"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlarmCause {
+    Overheat,
+    RapidRise,
+    Runaway,
+}
+
+impl AlarmCause {
+    // severity an alarm starts at before any escalation
+    pub fn severity(&self) -> Severity {
+        match self {
+            AlarmCause::Overheat => Severity::Warning,
+            AlarmCause::RapidRise => Severity::Critical,
+            AlarmCause::Runaway => Severity::Critical,
+        }
+    }
+
+    // a runaway is worse than a fast rise even though both are critical
+    fn outranks(&self, other: AlarmCause) -> bool {
+        *self == AlarmCause::Runaway && other != AlarmCause::Runaway
+    }
+}
+
+impl Display for AlarmCause {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AlarmCause::Overheat => write!(f, "overheat"),
+            AlarmCause::RapidRise => write!(f, "rapid_rise"),
+            AlarmCause::Runaway => write!(f, "runaway"),
+        }
+    }
+}
+
+pub struct AlarmConfig {
+    // Rate of rise that raises an alarm regardless of burn state
+    pub extreme_rise_rate: RateOfChange,
+    // Temperature and rate both have to drop below these before an alarm clears
+    pub clear_threshold: Temperature,
+    pub clear_rate: RateOfChange,
+
+    // Re-notification interval, doubling after every notification up to the maximum
+    pub renotify_initial: Duration,
+    pub renotify_max: Duration,
+    // Unacknowledged notifications before a warning is escalated to critical
+    pub escalate_after: u32,
+}
+
+impl Default for AlarmConfig {
+    fn default() -> Self {
+        Self {
+            extreme_rise_rate: RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(50.0),
+                1.0,
+            ),
+            clear_threshold: Temperature::from_fahrenheit(550.0),
+            clear_rate: RateOfChange::new_per_minute(TemperatureDelta::from_fahrenheit(20.0), 1.0),
+            renotify_initial: Duration::from_secs(2 * 60),
+            renotify_max: Duration::from_secs(15 * 60),
+            escalate_after: 3,
+        }
+    }
+}
+
+// Readings the alarm conditions are evaluated against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlarmInput {
+    pub state: BurnState,
+    pub temperature: Temperature,
+    pub rate: Option<RateOfChange>,
+    // whether the runaway detector currently sees a chimney fire
+    pub runaway: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Alarm {
+    pub cause: AlarmCause,
+    pub severity: Severity,
+    pub acknowledged: bool,
+    pub raised_at: Instant,
+    // times the alarm has been sent out, including the first
+    pub notifications: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlarmEvent {
+    Raised(Alarm),
+    Renotify(Alarm),
+    Escalated(Alarm),
+    Acknowledged(Alarm),
+    Cleared(Alarm),
+}
+
+pub struct AlarmMonitor {
+    config: AlarmConfig,
+    active: Option<Alarm>,
+    next_notify: Option<Instant>,
+}
+
+impl AlarmMonitor {
+    pub fn new() -> Self {
+        AlarmMonitor::with_config(AlarmConfig::default())
+    }
+
+    pub fn with_config(config: AlarmConfig) -> Self {
+        AlarmMonitor {
+            config,
+            active: None,
+            next_notify: None,
+        }
+    }
+
+    pub fn active(&self) -> Option<&Alarm> {
+        self.active.as_ref()
+    }
+
+    pub fn update(&mut self, now: Instant, input: AlarmInput) -> Option<AlarmEvent> {
+        let cause = self.evaluate(&input);
+
+        let Some(mut alarm) = self.active else {
+            // nothing active, raise if anything is wrong
+            let cause = cause?;
+            let alarm = Alarm {
+                cause,
+                severity: cause.severity(),
+                acknowledged: false,
+                raised_at: now,
+                notifications: 1,
+            };
+            self.active = Some(alarm);
+            self.next_notify = Some(now + self.config.renotify_initial);
+            return Some(AlarmEvent::Raised(alarm));
+        };
+
+        match cause {
+            // a worse cause replaces the current one and needs a fresh acknowledgement
+            Some(cause) if cause.severity() > alarm.severity || cause.outranks(alarm.cause) => {
+                alarm.cause = cause;
+                alarm.severity = cause.severity();
+                alarm.acknowledged = false;
+                alarm.notifications += 1;
+                self.active = Some(alarm);
+                self.next_notify = Some(now + self.config.renotify_initial);
+                Some(AlarmEvent::Escalated(alarm))
+            }
+            Some(_) => self.renotify(now),
+            None => {
+                if self.cleared(&input) {
+                    self.active = None;
+                    self.next_notify = None;
+                    Some(AlarmEvent::Cleared(alarm))
+                } else {
+                    // still inside the hysteresis band
+                    self.renotify(now)
+                }
+            }
+        }
+    }
+
+    pub fn acknowledge(&mut self) -> Option<AlarmEvent> {
+        let alarm = self.active.as_mut()?;
+        if alarm.acknowledged {
+            return None;
+        }
+
+        alarm.acknowledged = true;
+        self.next_notify = None;
+        Some(AlarmEvent::Acknowledged(*alarm))
+    }
+
+    // the most severe condition present in the readings
+    fn evaluate(&self, input: &AlarmInput) -> Option<AlarmCause> {
+        let rising_fast = input
+            .rate
+            .is_some_and(|r| r > self.config.extreme_rise_rate);
+
+        if input.runaway {
+            Some(AlarmCause::Runaway)
+        } else if rising_fast {
+            Some(AlarmCause::RapidRise)
+        } else if input.state == BurnState::Overheat {
+            Some(AlarmCause::Overheat)
+        } else {
+            None
+        }
+    }
+
+    fn cleared(&self, input: &AlarmInput) -> bool {
+        input.temperature < self.config.clear_threshold
+            && input.rate.is_none_or(|r| r < self.config.clear_rate)
+    }
+
+    fn renotify(&mut self, now: Instant) -> Option<AlarmEvent> {
+        let alarm = self.active.as_mut()?;
+        let due = self.next_notify?;
+        if now < due {
+            return None;
+        }
+
+        alarm.notifications += 1;
+        let doublings = (alarm.notifications - 1).min(16);
+        let interval = self
+            .config
+            .renotify_initial
+            .saturating_mul(1 << doublings)
+            .min(self.config.renotify_max);
+        self.next_notify = Some(now + interval);
+
+        let escalate =
+            alarm.severity == Severity::Warning && alarm.notifications > self.config.escalate_after;
+        if escalate {
+            alarm.severity = Severity::Critical;
+            Some(AlarmEvent::Escalated(*alarm))
+        } else {
+            Some(AlarmEvent::Renotify(*alarm))
+        }
+    }
+}
+
+impl Default for AlarmMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(state: BurnState, temp_f: f32, rate_f_per_min: f32) -> AlarmInput {
+        AlarmInput {
+            state,
+            temperature: Temperature::from_fahrenheit(temp_f),
+            rate: Some(RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(rate_f_per_min),
+                1.0,
+            )),
+            runaway: false,
+        }
+    }
+
+    fn runaway(temp_f: f32, rate_f_per_min: f32) -> AlarmInput {
+        AlarmInput {
+            runaway: true,
+            ..input(BurnState::Overheat, temp_f, rate_f_per_min)
+        }
+    }
+
+    fn overheating() -> AlarmInput {
+        input(BurnState::Overheat, 750.0, 0.0)
+    }
+
+    fn secs(start: Instant, s: u64) -> Instant {
+        start + Duration::from_secs(s)
+    }
+
+    #[test]
+    fn quiet_while_burning_normally() {
+        let mut monitor = AlarmMonitor::new();
+
+        let event = monitor.update(Instant::now(), input(BurnState::ActiveBurn, 500.0, 2.0));
+
+        assert_eq!(event, None);
+        assert!(monitor.active().is_none());
+    }
+
+    #[test]
+    fn raises_on_overheat() {
+        let mut monitor = AlarmMonitor::new();
+
+        let event = monitor.update(Instant::now(), overheating());
+
+        match event {
+            Some(AlarmEvent::Raised(alarm)) => {
+                assert_eq!(alarm.cause, AlarmCause::Overheat);
+                assert_eq!(alarm.severity, Severity::Warning);
+                assert!(!alarm.acknowledged);
+            }
+            other => panic!("expected a raised alarm, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn raises_critical_on_extreme_rise() {
+        let mut monitor = AlarmMonitor::new();
+
+        let event = monitor.update(Instant::now(), input(BurnState::ActiveBurn, 500.0, 80.0));
+
+        match event {
+            Some(AlarmEvent::Raised(alarm)) => {
+                assert_eq!(alarm.cause, AlarmCause::RapidRise);
+                assert_eq!(alarm.severity, Severity::Critical);
+            }
+            other => panic!("expected a raised alarm, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn does_not_raise_twice() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, overheating());
+
+        let event = monitor.update(secs(start, 10), overheating());
+
+        assert_eq!(event, None);
+    }
+
+    #[test]
+    fn renotifies_at_escalating_intervals() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, overheating());
+
+        // step through 30 minutes, noting when notifications go out
+        let mut notified_at = Vec::new();
+        for t in (10..=30 * 60).step_by(10) {
+            if let Some(AlarmEvent::Renotify(_) | AlarmEvent::Escalated(_)) =
+                monitor.update(secs(start, t), overheating())
+            {
+                notified_at.push(t);
+            }
+        }
+
+        // 2 minutes, then 4, then 8, then capped at 15
+        assert_eq!(notified_at, vec![120, 360, 840, 1740]);
+    }
+
+    #[test]
+    fn escalates_unacknowledged_warning() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, overheating());
+
+        let mut escalated = None;
+        for t in (10..=30 * 60).step_by(10) {
+            if let Some(AlarmEvent::Escalated(alarm)) =
+                monitor.update(secs(start, t), overheating())
+            {
+                escalated = Some(alarm);
+                break;
+            }
+        }
+
+        let alarm = escalated.expect("expected the alarm to escalate");
+        assert_eq!(alarm.severity, Severity::Critical);
+        assert_eq!(alarm.notifications, 4);
+    }
+
+    #[test]
+    fn acknowledgement_stops_renotification() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, overheating());
+
+        let ack = monitor.acknowledge();
+        assert!(matches!(ack, Some(AlarmEvent::Acknowledged(_))));
+
+        for t in (10..=60 * 60).step_by(10) {
+            assert_eq!(monitor.update(secs(start, t), overheating()), None);
+        }
+        assert!(monitor.active().unwrap().acknowledged);
+    }
+
+    #[test]
+    fn acknowledging_twice_is_a_no_op() {
+        let mut monitor = AlarmMonitor::new();
+        monitor.update(Instant::now(), overheating());
+        monitor.acknowledge();
+
+        assert_eq!(monitor.acknowledge(), None);
+    }
+
+    #[test]
+    fn acknowledging_without_alarm_is_a_no_op() {
+        let mut monitor = AlarmMonitor::new();
+
+        assert_eq!(monitor.acknowledge(), None);
+    }
+
+    #[test]
+    fn worse_cause_escalates_acknowledged_alarm() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, overheating());
+        monitor.acknowledge();
+
+        let event = monitor.update(secs(start, 10), input(BurnState::Overheat, 800.0, 80.0));
+
+        match event {
+            Some(AlarmEvent::Escalated(alarm)) => {
+                assert_eq!(alarm.cause, AlarmCause::RapidRise);
+                assert_eq!(alarm.severity, Severity::Critical);
+                assert!(!alarm.acknowledged);
+            }
+            other => panic!("expected an escalation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn runaway_is_distinct_from_overheat() {
+        let mut monitor = AlarmMonitor::new();
+
+        let event = monitor.update(Instant::now(), runaway(900.0, 40.0));
+
+        match event {
+            Some(AlarmEvent::Raised(alarm)) => {
+                assert_eq!(alarm.cause, AlarmCause::Runaway);
+                assert_eq!(alarm.severity, Severity::Critical);
+            }
+            other => panic!("expected a raised alarm, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn runaway_replaces_rapid_rise() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, input(BurnState::Overheat, 750.0, 80.0));
+        monitor.acknowledge();
+
+        let event = monitor.update(secs(start, 10), runaway(800.0, 80.0));
+
+        match event {
+            Some(AlarmEvent::Escalated(alarm)) => {
+                assert_eq!(alarm.cause, AlarmCause::Runaway);
+                assert!(!alarm.acknowledged);
+            }
+            other => panic!("expected an escalation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn holds_until_below_clear_threshold() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, overheating());
+        monitor.acknowledge();
+
+        // the state machine has dropped back to active burn, but it is still hot
+        let event = monitor.update(secs(start, 10), input(BurnState::ActiveBurn, 580.0, -5.0));
+        assert_eq!(event, None);
+        assert!(monitor.active().is_some());
+
+        let event = monitor.update(secs(start, 20), input(BurnState::ActiveBurn, 540.0, -5.0));
+        assert!(matches!(event, Some(AlarmEvent::Cleared(_))));
+        assert!(monitor.active().is_none());
+    }
+
+    #[test]
+    fn keeps_renotifying_inside_hysteresis_band() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, overheating());
+
+        let event = monitor.update(secs(start, 120), input(BurnState::ActiveBurn, 580.0, -5.0));
+
+        assert!(matches!(event, Some(AlarmEvent::Renotify(_))));
+    }
+
+    #[test]
+    fn rapid_rise_holds_until_rate_drops() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, input(BurnState::Startup, 300.0, 80.0));
+
+        // below the raise rate but not yet below the clear rate
+        let event = monitor.update(secs(start, 10), input(BurnState::Startup, 350.0, 30.0));
+        assert_eq!(event, None);
+
+        let event = monitor.update(secs(start, 20), input(BurnState::Startup, 360.0, 5.0));
+        assert!(matches!(event, Some(AlarmEvent::Cleared(_))));
+    }
+
+    #[test]
+    fn raises_again_after_clearing() {
+        let mut monitor = AlarmMonitor::new();
+        let start = Instant::now();
+        monitor.update(start, overheating());
+        monitor.update(secs(start, 10), input(BurnState::ActiveBurn, 500.0, -5.0));
+
+        let event = monitor.update(secs(start, 20), overheating());
+
+        match event {
+            Some(AlarmEvent::Raised(alarm)) => assert_eq!(alarm.notifications, 1),
+            other => panic!("expected a raised alarm, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use core::time::Duration;
+
+use embedded_hal::digital::OutputPin;
+
+use crate::{
+    led::{self, Sequence, Step, off, on},
+    state_machine::BurnState,
+    time::Instant,
+};
+
+const RUNAWAY: [Step; 2] = [on(150), off(150)];
+const OVERHEAT: [Step; 2] = [on(500), off(500)];
+const SENSOR_FAULT: [Step; 2] = [on(100), off(9900)];
+const SILENT: [Step; 1] = [off(1000)];
+
+// What the buzzer sounds for, most urgent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum BuzzerAlarm {
+    // rapid beeping
+    Runaway,
+    // steady half-second beeps
+    Overheat,
+    // a chirp every ten seconds
+    SensorFault,
+}
+
+impl BuzzerAlarm {
+    pub fn pattern(&self) -> &'static [Step] {
+        match self {
+            BuzzerAlarm::Runaway => &RUNAWAY,
+            BuzzerAlarm::Overheat => &OVERHEAT,
+            BuzzerAlarm::SensorFault => &SENSOR_FAULT,
+        }
+    }
+}
+
+pub struct BuzzerConfig {
+    // how long the mute button silences the buzzer for
+    pub snooze: Duration,
+    // a sensor fault has to last this long before it sounds; it's not a danger in itself
+    pub sensor_fault_after: Duration,
+}
+
+impl Default for BuzzerConfig {
+    fn default() -> Self {
+        Self {
+            snooze: Duration::from_secs(10 * 60),
+            sensor_fault_after: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuzzerInput {
+    pub state: BurnState,
+    pub runaway: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Snooze {
+    alarm: BuzzerAlarm,
+    until: Instant,
+}
+
+// Local alarm sounder that works without the network. Sounds the most urgent alarm that applies
+// on any `OutputPin`, e.g. a PWM channel that drives a piezo while high. Snoozing silences it
+// for a while, unless something more urgent comes up.
+pub struct Buzzer<P: OutputPin> {
+    pin: P,
+    config: BuzzerConfig,
+    alarm: Option<BuzzerAlarm>,
+    fault_since: Option<Instant>,
+    snooze: Option<Snooze>,
+    sequence: Sequence,
+}
+
+impl<P: OutputPin> Buzzer<P> {
+    pub fn new(pin: P, now: Instant) -> Self {
+        Self::with_config(pin, BuzzerConfig::default(), now)
+    }
+
+    pub fn with_config(pin: P, config: BuzzerConfig, now: Instant) -> Self {
+        Self {
+            pin,
+            config,
+            alarm: None,
+            fault_since: None,
+            snooze: None,
+            sequence: Sequence::new(now),
+        }
+    }
+
+    // the alarm that applies, whether or not it's snoozed
+    pub fn alarm(&self) -> Option<BuzzerAlarm> {
+        self.alarm
+    }
+
+    // the alarm being sounded, if any
+    pub fn sounding(&self, now: Instant) -> Option<BuzzerAlarm> {
+        let alarm = self.alarm?;
+        match self.snooze {
+            // only something more urgent than what was snoozed breaks through
+            Some(snooze) if now < snooze.until && alarm >= snooze.alarm => None,
+            _ => Some(alarm),
+        }
+    }
+
+    pub fn update(&mut self, now: Instant, input: BuzzerInput) {
+        self.fault_since = match input.state {
+            BurnState::SensorFault => Some(self.fault_since.unwrap_or(now)),
+            _ => None,
+        };
+
+        let before = self.sounding(now);
+        self.alarm = if input.runaway {
+            Some(BuzzerAlarm::Runaway)
+        } else if input.state == BurnState::Overheat {
+            Some(BuzzerAlarm::Overheat)
+        } else if self
+            .fault_since
+            .is_some_and(|since| now - since >= self.config.sensor_fault_after)
+        {
+            Some(BuzzerAlarm::SensorFault)
+        } else {
+            None
+        };
+
+        if self.sounding(now) != before {
+            self.sequence = Sequence::new(now);
+        }
+    }
+
+    // the mute button; silences whatever is sounding for the snooze time
+    pub fn snooze(&mut self, now: Instant) {
+        if let Some(alarm) = self.sounding(now) {
+            self.snooze = Some(Snooze {
+                alarm,
+                until: now + self.config.snooze,
+            });
+            self.sequence = Sequence::new(now);
+        }
+    }
+
+    pub fn is_snoozed(&self, now: Instant) -> bool {
+        self.snooze.is_some_and(|snooze| now < snooze.until)
+    }
+
+    // drives the pin for the step due at `now`; returns how long until the next step
+    pub fn poll(&mut self, now: Instant) -> Result<Duration, P::Error> {
+        let pattern = self
+            .sounding(now)
+            .map_or(&SILENT[..], |alarm| alarm.pattern());
+        let (step, wait) = self.sequence.poll(pattern, now);
+        led::apply(&mut self.pin, step)?;
+        // a snooze running out starts the alarm again
+        let wait = match self.snooze {
+            Some(snooze) if now < snooze.until => wait.min(snooze.until - now),
+            _ => wait,
+        };
+        Ok(wait)
+    }
+
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TestPin, at};
+
+    const BURNING: BuzzerInput = BuzzerInput {
+        state: BurnState::ActiveBurn,
+        runaway: false,
+    };
+    const OVERHEATING: BuzzerInput = BuzzerInput {
+        state: BurnState::Overheat,
+        runaway: false,
+    };
+    const FAULT: BuzzerInput = BuzzerInput {
+        state: BurnState::SensorFault,
+        runaway: false,
+    };
+
+    fn buzzer() -> Buzzer<TestPin> {
+        Buzzer::new(TestPin::default(), at(0))
+    }
+
+    #[test]
+    fn quiet_while_all_is_well() {
+        let mut buzzer = buzzer();
+        buzzer.update(at(0), BURNING);
+
+        assert_eq!(buzzer.sounding(at(0)), None);
+        buzzer.poll(at(0)).unwrap();
+        assert!(!buzzer.pin.high);
+    }
+
+    #[test]
+    fn sounds_overheat() {
+        let mut buzzer = buzzer();
+        buzzer.update(at(10), OVERHEATING);
+
+        assert_eq!(buzzer.sounding(at(10)), Some(BuzzerAlarm::Overheat));
+        assert_eq!(buzzer.poll(at(10)), Ok(Duration::from_millis(500)));
+        assert!(buzzer.pin.high);
+
+        buzzer.update(at(20), BURNING);
+        buzzer.poll(at(20)).unwrap();
+        assert!(!buzzer.pin.high);
+    }
+
+    #[test]
+    fn runaway_comes_first() {
+        let mut buzzer = buzzer();
+        buzzer.update(
+            at(0),
+            BuzzerInput {
+                runaway: true,
+                ..OVERHEATING
+            },
+        );
+
+        assert_eq!(buzzer.sounding(at(0)), Some(BuzzerAlarm::Runaway));
+    }
+
+    #[test]
+    fn sensor_fault_sounds_only_once_sustained() {
+        let mut buzzer = buzzer();
+
+        buzzer.update(at(0), FAULT);
+        buzzer.update(at(4 * 60), FAULT);
+        assert_eq!(buzzer.sounding(at(4 * 60)), None);
+
+        buzzer.update(at(5 * 60), FAULT);
+        assert_eq!(buzzer.sounding(at(5 * 60)), Some(BuzzerAlarm::SensorFault));
+
+        // a good reading starts the count over
+        buzzer.update(at(5 * 60 + 10), BURNING);
+        buzzer.update(at(5 * 60 + 20), FAULT);
+        assert_eq!(buzzer.sounding(at(5 * 60 + 20)), None);
+    }
+
+    #[test]
+    fn snooze_silences_until_it_runs_out() {
+        let mut buzzer = buzzer();
+        buzzer.update(at(0), OVERHEATING);
+
+        buzzer.snooze(at(30));
+        assert!(buzzer.is_snoozed(at(30)));
+        assert_eq!(buzzer.sounding(at(30)), None);
+        // wakes up when the snooze runs out
+        let almost = Instant::from_millis((30 + 10 * 60) * 1000 - 300);
+        assert_eq!(buzzer.poll(almost), Ok(Duration::from_millis(300)));
+        assert!(!buzzer.pin.high);
+
+        buzzer.update(at(30 + 10 * 60), OVERHEATING);
+        assert_eq!(
+            buzzer.sounding(at(30 + 10 * 60)),
+            Some(BuzzerAlarm::Overheat)
+        );
+    }
+
+    #[test]
+    fn more_urgent_alarm_breaks_through_snooze() {
+        let mut buzzer = buzzer();
+        buzzer.update(at(0), OVERHEATING);
+        buzzer.snooze(at(10));
+
+        buzzer.update(
+            at(20),
+            BuzzerInput {
+                runaway: true,
+                ..OVERHEATING
+            },
+        );
+        assert_eq!(buzzer.sounding(at(20)), Some(BuzzerAlarm::Runaway));
+        buzzer.poll(at(20)).unwrap();
+        assert!(buzzer.pin.high);
+    }
+
+    #[test]
+    fn snoozing_nothing_does_nothing() {
+        let mut buzzer = buzzer();
+        buzzer.update(at(0), BURNING);
+        buzzer.snooze(at(0));
+
+        assert!(!buzzer.is_snoozed(at(0)));
+        buzzer.update(at(10), OVERHEATING);
+        assert_eq!(buzzer.sounding(at(10)), Some(BuzzerAlarm::Overheat));
+    }
+}
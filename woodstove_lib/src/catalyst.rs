@@ -0,0 +1,448 @@
+use core::{fmt::Display, time::Duration};
+
+use crate::{temperature::Temperature, time::Instant};
+
+const SECONDS_PER_HOUR: f32 = 3600.0;
+
+// Thresholds for a catalytic combustor, read by a probe in or just behind the catalyst
+pub struct CatalystConfig {
+    // the catalyst lights off above this; the bypass has to stay open until it does
+    pub light_off: Temperature,
+    // how long it has to stay lit before closing the bypass won't choke it out
+    pub light_off_hold: Duration,
+    // below this the catalyst has gone out
+    pub cold_below: Temperature,
+    // an active catalyst cooling below this is about to stall, and warming back above
+    // `stall_clear` has recovered
+    pub stall: Temperature,
+    pub stall_clear: Temperature,
+    // hotter than this damages the catalyst, until it cools below `overtemp_clear`
+    pub overtemp: Temperature,
+    pub overtemp_clear: Temperature,
+    // intervals longer than this (e.g. a sensor outage) aren't counted towards catalyst hours
+    pub max_sample_gap: Duration,
+    // catalyst hours before it's due for replacement
+    pub service_life: Duration,
+}
+
+impl Default for CatalystConfig {
+    fn default() -> Self {
+        Self {
+            light_off: Temperature::from_fahrenheit(500.0),
+            light_off_hold: Duration::from_secs(2 * 60),
+            cold_below: Temperature::from_fahrenheit(450.0),
+            stall: Temperature::from_fahrenheit(600.0),
+            stall_clear: Temperature::from_fahrenheit(650.0),
+            overtemp: Temperature::from_fahrenheit(1600.0),
+            overtemp_clear: Temperature::from_fahrenheit(1500.0),
+            max_sample_gap: Duration::from_secs(5 * 60),
+            service_life: Duration::from_secs(10_000 * 3600),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalystState {
+    // not lit; the bypass should be open
+    Cold,
+    // past light-off, but not for long enough to close the bypass
+    LightOff,
+    // lit and burning the smoke
+    Active,
+    // hot enough to damage the catalyst
+    Overtemp,
+}
+
+impl CatalystState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CatalystState::Cold => "cold",
+            CatalystState::LightOff => "light_off",
+            CatalystState::Active => "active",
+            CatalystState::Overtemp => "overtemp",
+        }
+    }
+
+    // whether the catalyst is lit, and so wearing
+    pub fn is_burning(&self) -> bool {
+        matches!(self, CatalystState::Active | CatalystState::Overtemp)
+    }
+}
+
+impl Display for CatalystState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+// What to do with the stove's controls, given when it first applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CatalystAdvice {
+    // the catalyst has lit off and held, so the smoke can go through it
+    CloseBypass,
+    // the catalyst is cooling towards going out and needs more air to stay lit
+    OpenAir,
+    // the catalyst is hot enough to be damaged and needs less air to cool down
+    CutAir,
+}
+
+impl Display for CatalystAdvice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CatalystAdvice::CloseBypass => write!(f, "close bypass now"),
+            CatalystAdvice::OpenAir => write!(f, "open air"),
+            CatalystAdvice::CutAir => write!(f, "overtemp, cut air"),
+        }
+    }
+}
+
+// Follows a catalytic combustor through light-off, active burning and overtemp alongside the
+// stove's own state machine, advising on the bypass and air, and adding up the hours the
+// catalyst has been burning for replacement scheduling.
+pub struct CatalystMonitor {
+    config: CatalystConfig,
+    state: CatalystState,
+    state_since: Instant,
+    // warn of a stall once the catalyst has been properly hot, and only once until it is again
+    stall_armed: bool,
+    // whether this burn has been told to close the bypass yet; a fast fire can skip light-off
+    // and go straight to overtemp, so it isn't always on leaving light-off
+    bypass_advised: bool,
+    last_update: Option<Instant>,
+    catalyst_time: Duration,
+}
+
+impl CatalystMonitor {
+    pub fn new() -> Self {
+        CatalystMonitor::with_config(CatalystConfig::default())
+    }
+
+    pub fn with_config(config: CatalystConfig) -> Self {
+        CatalystMonitor {
+            config,
+            state: CatalystState::Cold,
+            state_since: Instant::ZERO,
+            stall_armed: false,
+            bypass_advised: false,
+            last_update: None,
+            catalyst_time: Duration::ZERO,
+        }
+    }
+
+    pub fn state(&self) -> CatalystState {
+        self.state
+    }
+
+    pub fn time_in_state(&self, now: Instant) -> Duration {
+        now - self.state_since
+    }
+
+    // carries the hours over from before a reboot
+    pub fn set_catalyst_time(&mut self, time: Duration) {
+        self.catalyst_time = time;
+    }
+
+    // total time the catalyst has been burning
+    pub fn catalyst_time(&self) -> Duration {
+        self.catalyst_time
+    }
+
+    pub fn catalyst_hours(&self) -> f32 {
+        self.catalyst_time.as_secs_f32() / SECONDS_PER_HOUR
+    }
+
+    pub fn replacement_due(&self) -> bool {
+        self.catalyst_time >= self.config.service_life
+    }
+
+    // returns advice when it first applies
+    pub fn update(&mut self, now: Instant, temp: Temperature) -> Option<CatalystAdvice> {
+        if let Some(last) = self.last_update {
+            let elapsed = now - last;
+            if self.state.is_burning() && elapsed <= self.config.max_sample_gap {
+                self.catalyst_time += elapsed;
+            }
+        }
+        self.last_update = Some(now);
+
+        let next = self.next_state(now, temp);
+        let mut advice = None;
+        if next != self.state {
+            match next {
+                CatalystState::Active if !self.bypass_advised => {
+                    self.bypass_advised = true;
+                    advice = Some(CatalystAdvice::CloseBypass);
+                }
+                CatalystState::Overtemp => advice = Some(CatalystAdvice::CutAir),
+                _ => {}
+            }
+            if !next.is_burning() {
+                self.stall_armed = false;
+                self.bypass_advised = false;
+            }
+            self.state = next;
+            self.state_since = now;
+        }
+
+        if self.state == CatalystState::Active {
+            if self.stall_armed && temp < self.config.stall {
+                self.stall_armed = false;
+                advice = advice.or(Some(CatalystAdvice::OpenAir));
+            } else if temp > self.config.stall_clear {
+                self.stall_armed = true;
+            }
+        }
+
+        advice
+    }
+
+    fn next_state(&self, now: Instant, temp: Temperature) -> CatalystState {
+        let c = &self.config;
+        if temp > c.overtemp {
+            return CatalystState::Overtemp;
+        }
+
+        match self.state {
+            CatalystState::Cold if temp >= c.light_off => CatalystState::LightOff,
+            CatalystState::LightOff | CatalystState::Active if temp < c.cold_below => {
+                CatalystState::Cold
+            }
+            CatalystState::LightOff if self.time_in_state(now) >= c.light_off_hold => {
+                CatalystState::Active
+            }
+            CatalystState::Overtemp if temp < c.overtemp_clear => CatalystState::Active,
+            state => state,
+        }
+    }
+}
+
+impl Default for CatalystMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{SAMPLE_PERIOD, at, minute, trace};
+
+    #[derive(Debug, Default)]
+    struct Replay {
+        // sample index and the state entered there
+        states: Vec<(usize, CatalystState)>,
+        advice: Vec<(usize, CatalystAdvice)>,
+    }
+
+    // replays a trace of °F readings every 10 seconds
+    fn replay(monitor: &mut CatalystMonitor, trace: &[f32]) -> Replay {
+        let mut replay = Replay::default();
+        for (i, temp_f) in trace.iter().enumerate() {
+            let now = Instant::from_duration(Duration::from_secs(i as u64 * SAMPLE_PERIOD));
+            let before = monitor.state();
+            if let Some(advice) = monitor.update(now, Temperature::from_fahrenheit(*temp_f)) {
+                replay.advice.push((i, advice));
+            }
+            if monitor.state() != before {
+                replay.states.push((i, monitor.state()));
+            }
+        }
+        replay
+    }
+
+    #[test]
+    fn whole_burn_lights_off_and_goes_out() {
+        // kindling, light-off around 10 minutes in, a long cruise, then burning down
+        let burn = trace(&[
+            (0.0, 70.0),
+            (10.0, 500.0),
+            (30.0, 900.0),
+            (120.0, 700.0),
+            (200.0, 300.0),
+        ]);
+
+        let replay = replay(&mut CatalystMonitor::new(), &burn);
+
+        let states: Vec<CatalystState> = replay.states.iter().map(|(_, s)| *s).collect();
+        assert_eq!(
+            states,
+            [
+                CatalystState::LightOff,
+                CatalystState::Active,
+                CatalystState::Cold
+            ]
+        );
+        let (light_off, _) = replay.states[0];
+        let (active, _) = replay.states[1];
+        assert_eq!(minute(light_off), 10.0);
+        assert_eq!(minute(active), 12.0);
+
+        // close the bypass on going active, and open the air as it cools towards stalling
+        let advice: Vec<CatalystAdvice> = replay.advice.iter().map(|(_, a)| *a).collect();
+        assert_eq!(
+            advice,
+            [CatalystAdvice::CloseBypass, CatalystAdvice::OpenAir]
+        );
+        assert_eq!(replay.advice[0].0, active);
+        let stall = minute(replay.advice[1].0);
+        assert!((140.0..141.0).contains(&stall), "open air at {} min", stall);
+    }
+
+    #[test]
+    fn failed_light_off_never_advises_closing_the_bypass() {
+        // just touches light-off, then the kindling burns out within the hold
+        let fizzle = trace(&[(0.0, 70.0), (10.0, 510.0), (11.0, 510.0), (11.5, 300.0)]);
+
+        let replay = replay(&mut CatalystMonitor::new(), &fizzle);
+
+        let states: Vec<CatalystState> = replay.states.iter().map(|(_, s)| *s).collect();
+        assert_eq!(states, [CatalystState::LightOff, CatalystState::Cold]);
+        assert!(replay.advice.is_empty(), "{:?}", replay.advice);
+    }
+
+    #[test]
+    fn overtemp_and_back() {
+        // runs away past 1600°F, then cools below 1500°F
+        let hot = trace(&[(0.0, 700.0), (5.0, 700.0), (15.0, 1700.0), (25.0, 1400.0)]);
+
+        let replay = replay(&mut CatalystMonitor::new(), &hot);
+
+        let states: Vec<CatalystState> = replay.states.iter().map(|(_, s)| *s).collect();
+        assert_eq!(
+            states,
+            [
+                CatalystState::LightOff,
+                CatalystState::Active,
+                CatalystState::Overtemp,
+                CatalystState::Active
+            ]
+        );
+        let (overtemp, _) = replay.states[2];
+        assert!((14.0..14.5).contains(&minute(overtemp)));
+        // the bypass is closed once, and cooling back to active doesn't repeat it
+        assert_eq!(
+            replay.advice,
+            [
+                (replay.states[1].0, CatalystAdvice::CloseBypass),
+                (overtemp, CatalystAdvice::CutAir)
+            ]
+        );
+    }
+
+    #[test]
+    fn straight_to_overtemp_still_closes_the_bypass() {
+        let mut monitor = CatalystMonitor::new();
+
+        assert_eq!(
+            monitor.update(at(0), Temperature::from_fahrenheit(70.0)),
+            None
+        );
+        assert_eq!(
+            monitor.update(at(10), Temperature::from_fahrenheit(1700.0)),
+            Some(CatalystAdvice::CutAir)
+        );
+        assert_eq!(monitor.state(), CatalystState::Overtemp);
+        assert_eq!(
+            monitor.update(at(20), Temperature::from_fahrenheit(1650.0)),
+            None
+        );
+
+        assert_eq!(
+            monitor.update(at(30), Temperature::from_fahrenheit(1400.0)),
+            Some(CatalystAdvice::CloseBypass)
+        );
+        assert_eq!(monitor.state(), CatalystState::Active);
+        assert_eq!(
+            monitor.update(at(40), Temperature::from_fahrenheit(1400.0)),
+            None
+        );
+    }
+
+    #[test]
+    fn each_burn_gets_its_own_bypass_advice() {
+        let mut monitor = CatalystMonitor::new();
+        // one burn that goes out, then a relight
+        let burns = trace(&[
+            (0.0, 700.0),
+            (5.0, 700.0),
+            (6.0, 300.0),
+            (10.0, 300.0),
+            (11.0, 700.0),
+            (16.0, 700.0),
+        ]);
+
+        let replay = replay(&mut monitor, &burns);
+
+        let close_bypass = replay
+            .advice
+            .iter()
+            .filter(|(_, a)| *a == CatalystAdvice::CloseBypass)
+            .count();
+        assert_eq!(close_bypass, 2, "{:?}", replay.advice);
+    }
+
+    #[test]
+    fn stall_warning_holds_through_noise() {
+        let mut monitor = CatalystMonitor::new();
+        // active, then hovering around the stall temperature
+        let mut hover = trace(&[(0.0, 700.0), (5.0, 700.0), (10.0, 600.0), (30.0, 600.0)]);
+        for (i, temp) in hover.iter_mut().enumerate().skip(61) {
+            *temp += if i % 2 == 0 { 10.0 } else { -10.0 };
+        }
+
+        let replay = replay(&mut monitor, &hover);
+
+        let open_air = replay
+            .advice
+            .iter()
+            .filter(|(_, a)| *a == CatalystAdvice::OpenAir)
+            .count();
+        assert_eq!(open_air, 1, "{:?}", replay.advice);
+    }
+
+    #[test]
+    fn counts_catalyst_hours_only_while_burning() {
+        let mut monitor = CatalystMonitor::new();
+        // two minutes of light-off, then an hour active, then cold for an hour
+        let burn = trace(&[(0.0, 600.0), (62.0, 600.0), (63.0, 300.0), (123.0, 300.0)]);
+
+        replay(&mut monitor, &burn);
+
+        // active from minute 2 until it drops below 450°F during minute 62
+        let hours = monitor.catalyst_hours();
+        assert!((hours - 1.0).abs() < 0.02, "{} h", hours);
+    }
+
+    #[test]
+    fn gaps_are_not_counted() {
+        let mut monitor = CatalystMonitor::new();
+        let at = |minutes: u64| Instant::from_duration(Duration::from_secs(minutes * 60));
+        let hot = Temperature::from_fahrenheit(800.0);
+        monitor.update(at(0), hot);
+        monitor.update(at(3), hot);
+        assert_eq!(monitor.state(), CatalystState::Active);
+
+        // the sensor drops out for an hour
+        monitor.update(at(63), hot);
+        monitor.update(at(64), hot);
+
+        assert_eq!(monitor.catalyst_time(), Duration::from_secs(60));
+    }
+
+    #[test]
+    fn replacement_falls_due() {
+        let mut monitor = CatalystMonitor::new();
+        monitor.set_catalyst_time(Duration::from_secs(9_999 * 3600));
+        assert!(!monitor.replacement_due());
+
+        let at = |minutes: u64| Instant::from_duration(Duration::from_secs(minutes * 60));
+        let hot = Temperature::from_fahrenheit(800.0);
+        monitor.update(at(0), hot);
+        monitor.update(at(2), hot);
+        for minute in 3..=62 {
+            monitor.update(at(minute), hot);
+        }
+
+        assert!(monitor.replacement_due());
+    }
+}
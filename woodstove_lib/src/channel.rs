@@ -0,0 +1,369 @@
+use core::fmt::Display;
+
+use crate::{
+    SampleFilter,
+    filter::{FilterOutcome, FilterStats},
+    temperature::Temperature,
+    time::Instant,
+};
+
+// Thermocouples sharing the SPI bus, each on its own chip select
+pub const MAX_CHANNELS: usize = 4;
+// Channel names end up in MQTT topics
+pub const MAX_NAME_LEN: usize = 16;
+
+// One thermocouple: what to call it, and the GPIO its chip select is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ChannelSpec<'a> {
+    pub name: &'a str,
+    pub cs_pin: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelError {
+    Empty,
+    TooManyChannels,
+    // `entry` counts from 1
+    Malformed { entry: usize },
+    BadName { entry: usize },
+    BadPin { entry: usize },
+    DuplicateName { entry: usize },
+    DuplicatePin { entry: usize },
+    // the pin is wired to something else on the board
+    PinInUse { entry: usize },
+    UnknownPrimary,
+}
+
+impl Display for ChannelError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ChannelError::Empty => write!(f, "no channels"),
+            ChannelError::TooManyChannels => write!(f, "more than {} channels", MAX_CHANNELS),
+            ChannelError::Malformed { entry } => {
+                write!(f, "channel {}: expected name:pin", entry)
+            }
+            ChannelError::BadName { entry } => write!(
+                f,
+                "channel {}: names are up to {} letters, digits, '_' or '-'",
+                entry, MAX_NAME_LEN
+            ),
+            ChannelError::BadPin { entry } => write!(f, "channel {}: bad GPIO number", entry),
+            ChannelError::DuplicateName { entry } => {
+                write!(f, "channel {}: name already used", entry)
+            }
+            ChannelError::DuplicatePin { entry } => {
+                write!(f, "channel {}: pin already used", entry)
+            }
+            ChannelError::PinInUse { entry } => {
+                write!(f, "channel {}: pin is used by something else", entry)
+            }
+            ChannelError::UnknownPrimary => write!(f, "primary channel isn't in the list"),
+        }
+    }
+}
+
+// The thermocouple channels, e.g. "stovetop:21, flue:9, catalyst:10", and which of them drives
+// the state machine; the first unless picked with `with_primary`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelList<'a> {
+    channels: [ChannelSpec<'a>; MAX_CHANNELS],
+    len: usize,
+    primary: usize,
+}
+
+impl<'a> ChannelList<'a> {
+    pub fn parse(text: &'a str) -> Result<Self, ChannelError> {
+        let mut list = ChannelList {
+            channels: [ChannelSpec::default(); MAX_CHANNELS],
+            len: 0,
+            primary: 0,
+        };
+
+        for (i, entry) in text.split(',').map(str::trim).enumerate() {
+            let entry_number = i + 1;
+            if entry.is_empty() {
+                continue;
+            }
+            if list.len == MAX_CHANNELS {
+                return Err(ChannelError::TooManyChannels);
+            }
+
+            let (name, pin) = entry.split_once(':').ok_or(ChannelError::Malformed {
+                entry: entry_number,
+            })?;
+            let (name, pin) = (name.trim(), pin.trim());
+            if !valid_name(name) {
+                return Err(ChannelError::BadName {
+                    entry: entry_number,
+                });
+            }
+            let cs_pin = pin
+                .parse()
+                .ok()
+                .filter(|pin| *pin >= 0)
+                .ok_or(ChannelError::BadPin {
+                    entry: entry_number,
+                })?;
+
+            if list.channels().iter().any(|c| c.name == name) {
+                return Err(ChannelError::DuplicateName {
+                    entry: entry_number,
+                });
+            }
+            if list.channels().iter().any(|c| c.cs_pin == cs_pin) {
+                return Err(ChannelError::DuplicatePin {
+                    entry: entry_number,
+                });
+            }
+
+            list.channels[list.len] = ChannelSpec { name, cs_pin };
+            list.len += 1;
+        }
+
+        if list.len == 0 {
+            return Err(ChannelError::Empty);
+        }
+        Ok(list)
+    }
+
+    pub fn with_primary(self, name: &str) -> Result<Self, ChannelError> {
+        let primary = self
+            .channels()
+            .iter()
+            .position(|c| c.name == name.trim())
+            .ok_or(ChannelError::UnknownPrimary)?;
+        Ok(ChannelList { primary, ..self })
+    }
+
+    // checks every chip select is a GPIO the board can give it (`usable`) and isn't one of
+    // `in_use`
+    pub fn check_pins(
+        self,
+        in_use: &[i32],
+        usable: impl Fn(i32) -> bool,
+    ) -> Result<Self, ChannelError> {
+        for (i, channel) in self.channels().iter().enumerate() {
+            let entry = i + 1;
+            if !usable(channel.cs_pin) {
+                return Err(ChannelError::BadPin { entry });
+            }
+            if in_use.contains(&channel.cs_pin) {
+                return Err(ChannelError::PinInUse { entry });
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn channels(&self) -> &[ChannelSpec<'a>] {
+        &self.channels[..self.len]
+    }
+
+    // index into `channels` of the one that drives the state machine
+    pub fn primary(&self) -> usize {
+        self.primary
+    }
+}
+
+// Whether the ESP32-S3 can spare `pin` for a chip select, buzzer or button: GPIO0 to GPIO48, less
+// the 22 to 25 it doesn't have, the 26 to 37 wired to the module's flash and PSRAM, USB's 19 and
+// 20, and the strapping pins 0, 3, 45 and 46, which would change how it boots
+pub fn usable_pin(pin: i32) -> bool {
+    (0..=48).contains(&pin) && !matches!(pin, 0 | 3 | 19 | 20 | 22..=37 | 45 | 46)
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+// Filtering and fault tracking for one channel, so a broken flue probe doesn't take the others
+// down with it. A channel is faulted after `fault_after` failed reads in a row, and recovers on
+// the next good one.
+pub struct ChannelReader {
+    filter: SampleFilter,
+    fault_after: u32,
+    consecutive_faults: u32,
+    last: Option<Temperature>,
+}
+
+impl ChannelReader {
+    pub fn new(fault_after: u32) -> Self {
+        Self {
+            filter: SampleFilter::new(),
+            fault_after,
+            consecutive_faults: 0,
+            last: None,
+        }
+    }
+
+    pub fn reading(&mut self, now: Instant, temp: Temperature) -> FilterOutcome {
+        let outcome = self.filter.filter(now, temp);
+        if let FilterOutcome::Accepted(temp) = outcome {
+            self.consecutive_faults = 0;
+            self.last = Some(temp);
+        }
+        outcome
+    }
+
+    // a failed read; returns true if this one faulted the channel
+    pub fn report_fault(&mut self) -> bool {
+        self.consecutive_faults = self.consecutive_faults.saturating_add(1);
+        if self.consecutive_faults != self.fault_after.max(1) {
+            return false;
+        }
+        // readings from before the fault say nothing about what comes after it
+        self.filter.reset();
+        self.last = None;
+        true
+    }
+
+    pub fn is_faulted(&self) -> bool {
+        self.consecutive_faults >= self.fault_after.max(1)
+    }
+
+    // the last good reading, none while faulted
+    pub fn temperature(&self) -> Option<Temperature> {
+        self.last
+    }
+
+    pub fn stats(&self) -> FilterStats {
+        self.filter.stats()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+
+    #[test]
+    fn parses_channels() {
+        let list = ChannelList::parse("stovetop:21, flue:9 ,catalyst: 10,").unwrap();
+
+        assert_eq!(
+            list.channels(),
+            [
+                ChannelSpec {
+                    name: "stovetop",
+                    cs_pin: 21
+                },
+                ChannelSpec {
+                    name: "flue",
+                    cs_pin: 9
+                },
+                ChannelSpec {
+                    name: "catalyst",
+                    cs_pin: 10
+                },
+            ]
+        );
+        assert_eq!(list.primary(), 0);
+    }
+
+    #[test]
+    fn picks_primary_by_name() {
+        let list = ChannelList::parse("stovetop:21,flue:9")
+            .and_then(|list| list.with_primary("flue"))
+            .unwrap();
+        assert_eq!(list.channels()[list.primary()].name, "flue");
+
+        assert_eq!(
+            ChannelList::parse("stovetop:21").and_then(|list| list.with_primary("flue")),
+            Err(ChannelError::UnknownPrimary)
+        );
+    }
+
+    #[test]
+    fn rejects_bad_lists() {
+        for (text, err) in [
+            ("", ChannelError::Empty),
+            (" , ", ChannelError::Empty),
+            ("a:1,b:2,c:3,d:4,e:5", ChannelError::TooManyChannels),
+            ("stovetop", ChannelError::Malformed { entry: 1 }),
+            ("a:1,flue pipe:2", ChannelError::BadName { entry: 2 }),
+            ("a:1,flue/pipe:2", ChannelError::BadName { entry: 2 }),
+            ("a:one", ChannelError::BadPin { entry: 1 }),
+            ("a:-1", ChannelError::BadPin { entry: 1 }),
+            ("a:1,a:2", ChannelError::DuplicateName { entry: 2 }),
+            ("a:1,b:1", ChannelError::DuplicatePin { entry: 2 }),
+        ] {
+            assert_eq!(ChannelList::parse(text), Err(err), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_pins_the_board_uses_or_lacks() {
+        let exists = |pin| (0..=48).contains(&pin);
+        let check =
+            |text| ChannelList::parse(text).and_then(|list| list.check_pins(&[8, 11], exists));
+
+        assert!(check("stovetop:21,flue:9").is_ok());
+        assert_eq!(
+            check("stovetop:21,flue:8"),
+            Err(ChannelError::PinInUse { entry: 2 })
+        );
+        assert_eq!(check("stovetop:49"), Err(ChannelError::BadPin { entry: 1 }));
+    }
+
+    #[test]
+    fn only_spare_pins_are_usable() {
+        let rejected: Vec<i32> = (-1..=49).filter(|&pin| !usable_pin(pin)).collect();
+
+        assert_eq!(
+            rejected,
+            [
+                -1, 0, 3, 19, 20, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37,
+                45, 46, 49
+            ]
+        );
+    }
+
+    #[test]
+    fn faults_after_failures_in_a_row() {
+        let mut reader = ChannelReader::new(3);
+        reader.reading(Instant::ZERO, Temperature::from_celsius(200.0));
+
+        assert!(!reader.report_fault());
+        assert!(!reader.report_fault());
+        assert_eq!(reader.temperature(), Some(Temperature::from_celsius(200.0)));
+        assert!(reader.report_fault());
+        assert!(reader.is_faulted());
+        assert_eq!(reader.temperature(), None);
+        // only the first failure past the limit counts as faulting it
+        assert!(!reader.report_fault());
+    }
+
+    #[test]
+    fn good_reading_clears_the_fault() {
+        let mut reader = ChannelReader::new(1);
+        reader.reading(Instant::ZERO, Temperature::from_celsius(200.0));
+        assert!(reader.report_fault());
+
+        // the filter starts over, so a very different reading is taken as is
+        let now = Instant::ZERO + Duration::from_secs(10);
+        assert_eq!(
+            reader.reading(now, Temperature::from_celsius(20.0)),
+            FilterOutcome::Accepted(Temperature::from_celsius(20.0))
+        );
+        assert!(!reader.is_faulted());
+        assert_eq!(reader.temperature(), Some(Temperature::from_celsius(20.0)));
+    }
+
+    #[test]
+    fn rejected_reading_keeps_the_last_good_one() {
+        let mut reader = ChannelReader::new(3);
+        let start = Instant::ZERO;
+        reader.reading(start, Temperature::from_celsius(200.0));
+
+        let outcome = reader.reading(
+            start + Duration::from_secs(10),
+            Temperature::from_celsius(900.0),
+        );
+        assert!(matches!(outcome, FilterOutcome::Rejected(_)));
+        assert_eq!(reader.temperature(), Some(Temperature::from_celsius(200.0)));
+        assert_eq!(reader.stats().rejected(), 1);
+    }
+}
@@ -0,0 +1,396 @@
+use core::{fmt::Display, str::FromStr};
+
+use crate::{
+    rules::{parse_duration, parse_temperature},
+    state_machine::StoveConfig,
+    temperature::{RateOfChange, TemperatureDelta},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigError {
+    // `line` counts from 1
+    Parse {
+        line: usize,
+        reason: &'static str,
+    },
+    // `lower` has to be below `upper` for the states to make sense
+    Order {
+        lower: &'static str,
+        upper: &'static str,
+    },
+    // NaN or infinite, which every comparison gets wrong
+    NotFinite {
+        key: &'static str,
+    },
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ConfigError::Parse { line, reason } => write!(f, "line {}: {}", line, reason),
+            ConfigError::Order { lower, upper } => {
+                write!(f, "{} has to be below {}", lower, upper)
+            }
+            ConfigError::NotFinite { key } => write!(f, "{} has to be a finite number", key),
+        }
+    }
+}
+
+impl StoveConfig {
+    // checks the thresholds and timings are finite and in an order the rules can work with
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let temperatures = [
+            ("ambient", self.ambient),
+            ("idle_threshold", self.idle_threshold),
+            ("coals_out_threshold", self.coals_out_threshold),
+            ("reload_threshold", self.reload_threshold),
+            ("active_exit_threshold", self.active_exit_threshold),
+            ("active_threshold", self.active_threshold),
+            ("overheat_exit_threshold", self.overheat_exit_threshold),
+            ("overheat_threshold", self.overheat_threshold),
+        ];
+        for (key, temp) in temperatures {
+            if !temp.celsius().is_finite() {
+                return Err(ConfigError::NotFinite { key });
+            }
+        }
+        for pair in temperatures.windows(2) {
+            if pair[0].1 >= pair[1].1 {
+                return Err(ConfigError::Order {
+                    lower: pair[0].0,
+                    upper: pair[1].0,
+                });
+            }
+        }
+
+        let zero = RateOfChange::from_celsius_per_second(0.0);
+        let rates = [
+            ("falling_fast_rate", self.falling_fast_rate),
+            ("falling_rate", self.falling_rate),
+            ("zero", zero),
+            ("rising_fast_rate", self.rising_fast_rate),
+        ];
+        for (key, rate) in rates.into_iter().chain([("stable_rate", self.stable_rate)]) {
+            if !rate.celsius_per_second().is_finite() {
+                return Err(ConfigError::NotFinite { key });
+            }
+        }
+        for pair in rates.windows(2) {
+            if pair[0].1 >= pair[1].1 {
+                return Err(ConfigError::Order {
+                    lower: pair[0].0,
+                    upper: pair[1].0,
+                });
+            }
+        }
+
+        if self.min_sample_interval >= self.max_sample_interval {
+            return Err(ConfigError::Order {
+                lower: "min_sample_interval",
+                upper: "max_sample_interval",
+            });
+        }
+        if self.sensor_fault_after == 0 {
+            return Err(ConfigError::Order {
+                lower: "zero",
+                upper: "sensor_fault_after",
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for StoveConfig {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let temperatures = [
+            ("idle_threshold", self.idle_threshold),
+            ("active_threshold", self.active_threshold),
+            ("active_exit_threshold", self.active_exit_threshold),
+            ("overheat_threshold", self.overheat_threshold),
+            ("overheat_exit_threshold", self.overheat_exit_threshold),
+            ("reload_threshold", self.reload_threshold),
+            ("coals_out_threshold", self.coals_out_threshold),
+            ("ambient", self.ambient),
+        ];
+        for (key, temp) in temperatures {
+            writeln!(f, "{} = {:.1}F", key, temp.fahrenheit())?;
+        }
+
+        let rates = [
+            ("rising_fast_rate", self.rising_fast_rate),
+            ("falling_rate", self.falling_rate),
+            ("falling_fast_rate", self.falling_fast_rate),
+            ("stable_rate", self.stable_rate),
+        ];
+        for (key, rate) in rates {
+            writeln!(f, "{} = {:.2}F/min", key, rate.fahrenheit_per_minute())?;
+        }
+
+        writeln!(f, "sensor_fault_after = {}", self.sensor_fault_after)?;
+        writeln!(
+            f,
+            "min_sample_interval = {}s",
+            self.min_sample_interval.as_secs()
+        )?;
+        writeln!(
+            f,
+            "max_sample_interval = {}s",
+            self.max_sample_interval.as_secs()
+        )?;
+        writeln!(f, "display_unit = {}", self.display_unit)
+    }
+}
+
+// The thresholds and timings of a `StoveConfig` as text, one `key = value` per line with `#`
+// comments. Keys that are left out keep their defaults. Temperatures are written like `450F` or
+// `230C`, rates like `5F/min`, durations like `90s`, `5m` or `1h`, and the display unit as
+// `celsius`, `fahrenheit` or `kelvin`. The transition rules are not part of it; they have their
+// own file format in `rules`.
+impl FromStr for StoveConfig {
+    type Err = ConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = StoveConfig::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let text = line.split('#').next().unwrap_or("").trim();
+            if text.is_empty() {
+                continue;
+            }
+            set_value(&mut config, text).map_err(|reason| ConfigError::Parse {
+                line: i + 1,
+                reason,
+            })?;
+        }
+
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn set_value(config: &mut StoveConfig, text: &str) -> Result<(), &'static str> {
+    let (key, value) = text.split_once('=').ok_or("expected `key = value`")?;
+    let value = value.trim();
+
+    match key.trim() {
+        "idle_threshold" => config.idle_threshold = parse_temperature(value)?,
+        "active_threshold" => config.active_threshold = parse_temperature(value)?,
+        "active_exit_threshold" => config.active_exit_threshold = parse_temperature(value)?,
+        "overheat_threshold" => config.overheat_threshold = parse_temperature(value)?,
+        "overheat_exit_threshold" => config.overheat_exit_threshold = parse_temperature(value)?,
+        "reload_threshold" => config.reload_threshold = parse_temperature(value)?,
+        "coals_out_threshold" => config.coals_out_threshold = parse_temperature(value)?,
+        "ambient" => config.ambient = parse_temperature(value)?,
+        "rising_fast_rate" => config.rising_fast_rate = parse_rate(value)?,
+        "falling_rate" => config.falling_rate = parse_rate(value)?,
+        "falling_fast_rate" => config.falling_fast_rate = parse_rate(value)?,
+        "stable_rate" => config.stable_rate = parse_rate(value)?,
+        "sensor_fault_after" => {
+            config.sensor_fault_after = value.parse().map_err(|_| "expected a whole number")?
+        }
+        "min_sample_interval" => config.min_sample_interval = parse_duration(value)?,
+        "max_sample_interval" => config.max_sample_interval = parse_duration(value)?,
+        "display_unit" => {
+            config.display_unit = value
+                .parse()
+                .map_err(|_| "expected celsius, fahrenheit or kelvin")?
+        }
+        _ => return Err("unknown key"),
+    }
+    Ok(())
+}
+
+// `5F/min` or `3C/min`
+fn parse_rate(text: &str) -> Result<RateOfChange, &'static str> {
+    let per_minute = text
+        .strip_suffix("/min")
+        .ok_or("rates look like 5F/min or 3C/min")?;
+    let number = |v: &str| {
+        v.parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or("expected a number")
+    };
+    // a rate is a difference, so parse it as one rather than as an absolute temperature
+    let delta = if let Some(f) = per_minute.strip_suffix('F') {
+        TemperatureDelta::from_fahrenheit(number(f)?)
+    } else if let Some(c) = per_minute.strip_suffix('C') {
+        TemperatureDelta::from_celsius(number(c)?)
+    } else {
+        return Err("rates look like 5F/min or 3C/min");
+    };
+    Ok(RateOfChange::new_per_minute(delta, 1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temperature::{Temperature, TemperatureUnit};
+    use core::time::Duration;
+
+    fn assert_close(a: Temperature, b: Temperature) {
+        assert!(
+            (a.fahrenheit() - b.fahrenheit()).abs() < 0.1,
+            "{} vs {}",
+            a.fahrenheit(),
+            b.fahrenheit()
+        );
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        assert_eq!(StoveConfig::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn round_trips_through_text() {
+        let config = StoveConfig {
+            idle_threshold: Temperature::from_fahrenheit(135.0),
+            active_threshold: Temperature::from_celsius(190.0),
+            falling_rate: RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(-4.5),
+                1.0,
+            ),
+            max_sample_interval: Duration::from_secs(90),
+            display_unit: TemperatureUnit::Kelvin,
+            ..StoveConfig::default()
+        };
+
+        let parsed: StoveConfig = config.to_string().parse().unwrap();
+
+        assert_close(parsed.idle_threshold, config.idle_threshold);
+        assert_close(parsed.active_threshold, config.active_threshold);
+        assert!(
+            (parsed.falling_rate.fahrenheit_per_minute() - -4.5).abs() < 0.01,
+            "{}",
+            parsed.falling_rate.fahrenheit_per_minute()
+        );
+        assert_eq!(parsed.max_sample_interval, Duration::from_secs(90));
+        assert_eq!(parsed.display_unit, TemperatureUnit::Kelvin);
+    }
+
+    #[test]
+    fn parses_display_unit() {
+        let parsed: StoveConfig = "display_unit = celsius".parse().unwrap();
+        assert_eq!(parsed.display_unit, TemperatureUnit::Celsius);
+        assert_eq!(
+            StoveConfig::default().display_unit,
+            TemperatureUnit::Fahrenheit
+        );
+
+        assert_eq!(
+            "display_unit = rankine".parse::<StoveConfig>().unwrap_err(),
+            ConfigError::Parse {
+                line: 1,
+                reason: "expected celsius, fahrenheit or kelvin"
+            }
+        );
+    }
+
+    #[test]
+    fn missing_keys_keep_defaults() {
+        let parsed: StoveConfig = "# just the probe moved\nidle_threshold = 120F\n"
+            .parse()
+            .unwrap();
+
+        assert_close(parsed.idle_threshold, Temperature::from_fahrenheit(120.0));
+        assert_eq!(
+            parsed.active_threshold,
+            StoveConfig::default().active_threshold
+        );
+    }
+
+    #[test]
+    fn reports_errors_by_line() {
+        let err = "idle_threshold = 120F\n\nactive_threshold = hot"
+            .parse::<StoveConfig>()
+            .unwrap_err();
+
+        assert!(
+            matches!(err, ConfigError::Parse { line: 3, .. }),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let err = "idel_threshold = 120F".parse::<StoveConfig>().unwrap_err();
+
+        assert_eq!(
+            err,
+            ConfigError::Parse {
+                line: 1,
+                reason: "unknown key"
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_thresholds_out_of_order() {
+        let err = "active_exit_threshold = 450F"
+            .parse::<StoveConfig>()
+            .unwrap_err();
+
+        assert_eq!(
+            err,
+            ConfigError::Order {
+                lower: "active_exit_threshold",
+                upper: "active_threshold"
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_non_finite_numbers() {
+        for text in [
+            "idle_threshold = NaNF",
+            "overheat_threshold = infF",
+            "overheat_threshold = -infC",
+            "falling_rate = NaNF/min",
+            "rising_fast_rate = infC/min",
+        ] {
+            assert!(
+                matches!(
+                    text.parse::<StoveConfig>(),
+                    Err(ConfigError::Parse { line: 1, .. })
+                ),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn validate_rejects_non_finite_values() {
+        let config = StoveConfig {
+            overheat_threshold: Temperature::from_fahrenheit(f32::INFINITY),
+            ..StoveConfig::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::NotFinite {
+                key: "overheat_threshold"
+            })
+        );
+
+        let config = StoveConfig {
+            stable_rate: RateOfChange::from_celsius_per_second(f32::NAN),
+            ..StoveConfig::default()
+        };
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::NotFinite { key: "stable_rate" })
+        );
+    }
+
+    #[test]
+    fn rejects_rising_rate_below_zero() {
+        let err = "rising_fast_rate = -1F/min"
+            .parse::<StoveConfig>()
+            .unwrap_err();
+
+        assert!(matches!(err, ConfigError::Order { .. }));
+    }
+}
@@ -0,0 +1,486 @@
+use core::{fmt::Display, time::Duration};
+
+use crate::{snapshot::crc8, state_machine::BurnState, temperature::Temperature, time::Instant};
+
+const SECONDS_PER_HOUR: f32 = 3600.0;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+// days in the rolling index
+pub const WINDOW_DAYS: usize = 30;
+const HISTORY_VERSION: u8 = 1;
+
+// Creosote condenses out of smoke in a flue cooler than `band_top`, more so the cooler it is
+pub struct CreosoteConfig {
+    pub band_top: Temperature,
+    // an hour at or below this adds a whole point to the index; an hour in the band above it
+    // adds proportionally less
+    pub full_weight_below: Temperature,
+    // index points since the chimney was last swept before suggesting an inspection
+    pub inspection_threshold: f32,
+    // intervals longer than this (e.g. a sensor outage) are not counted
+    pub max_sample_gap: Duration,
+}
+
+impl Default for CreosoteConfig {
+    fn default() -> Self {
+        Self {
+            band_top: Temperature::from_fahrenheit(250.0),
+            full_weight_below: Temperature::from_fahrenheit(150.0),
+            inspection_threshold: 50.0,
+            max_sample_gap: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreosoteNotice {
+    ConsiderInspection,
+}
+
+impl Display for CreosoteNotice {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            CreosoteNotice::ConsiderInspection => write!(f, "consider chimney inspection"),
+        }
+    }
+}
+
+// What a tracker has built up beyond the current burn, to carry it across reboots
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CreosoteHistory {
+    // one bucket per day of the rolling index, the current day at `today`
+    pub days: [f32; WINDOW_DAYS],
+    pub today: usize,
+    // when the current day started, on the clock the tracker is updated with; none starts it on
+    // the next update
+    pub day_started: Option<Duration>,
+    pub since_sweep: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryError {
+    WrongLength,
+    UnknownVersion(u8),
+    BadChecksum,
+    BadDay(u8),
+}
+
+impl Display for HistoryError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            HistoryError::WrongLength => write!(f, "wrong length"),
+            HistoryError::UnknownVersion(v) => write!(f, "unknown version {}", v),
+            HistoryError::BadChecksum => write!(f, "bad checksum"),
+            HistoryError::BadDay(day) => write!(f, "day {} outside the window", day),
+        }
+    }
+}
+
+impl CreosoteHistory {
+    // version, today, day started (ms, u64, all ones for none), since sweep (f32), the days
+    // (f32 each), CRC-8; all little endian
+    pub const ENCODED_LEN: usize = 14 + 4 * WINDOW_DAYS + 1;
+
+    pub fn encode(&self) -> [u8; Self::ENCODED_LEN] {
+        let day_started = self
+            .day_started
+            .map_or(u64::MAX, |d| d.as_millis().min(u64::MAX as u128 - 1) as u64);
+
+        let mut bytes = [0; Self::ENCODED_LEN];
+        bytes[0] = HISTORY_VERSION;
+        bytes[1] = self.today as u8;
+        bytes[2..10].copy_from_slice(&day_started.to_le_bytes());
+        bytes[10..14].copy_from_slice(&self.since_sweep.to_le_bytes());
+        for (chunk, day) in bytes[14..].chunks_exact_mut(4).zip(self.days) {
+            chunk.copy_from_slice(&day.to_le_bytes());
+        }
+        let end = Self::ENCODED_LEN - 1;
+        bytes[end] = crc8(&bytes[..end]);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<CreosoteHistory, HistoryError> {
+        let bytes: &[u8; Self::ENCODED_LEN] =
+            bytes.try_into().map_err(|_| HistoryError::WrongLength)?;
+        if bytes[0] != HISTORY_VERSION {
+            return Err(HistoryError::UnknownVersion(bytes[0]));
+        }
+        let end = Self::ENCODED_LEN - 1;
+        if crc8(&bytes[..end]) != bytes[end] {
+            return Err(HistoryError::BadChecksum);
+        }
+        if bytes[1] as usize >= WINDOW_DAYS {
+            return Err(HistoryError::BadDay(bytes[1]));
+        }
+
+        let f32_at =
+            |i: usize| f32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]]);
+        let mut day_started = [0; 8];
+        day_started.copy_from_slice(&bytes[2..10]);
+        let day_started = u64::from_le_bytes(day_started);
+        let mut days = [0.0; WINDOW_DAYS];
+        for (i, day) in days.iter_mut().enumerate() {
+            *day = f32_at(14 + 4 * i);
+        }
+
+        Ok(CreosoteHistory {
+            days,
+            today: bytes[1] as usize,
+            day_started: (day_started != u64::MAX).then(|| Duration::from_millis(day_started)),
+            since_sweep: f32_at(10),
+        })
+    }
+}
+
+// whether there's a fire making smoke; coals burn clean, and outside a burn there's no smoke
+fn smoky(state: BurnState) -> bool {
+    matches!(
+        state,
+        BurnState::Startup
+            | BurnState::ActiveBurn
+            | BurnState::LowBurn
+            | BurnState::Refueling
+            | BurnState::Overheat
+    )
+}
+
+// Integrates time with the flue in the creosote band during burns into a risk index, in
+// weighted hours, for the current burn, the last 30 days, and since the chimney was last swept
+pub struct CreosoteTracker {
+    config: CreosoteConfig,
+    last_sample: Option<(Instant, Temperature)>,
+    burn: f32,
+    burn_active: bool,
+    // one bucket per day, the current day at `today`
+    days: [f32; WINDOW_DAYS],
+    today: usize,
+    day_started: Option<Instant>,
+    since_sweep: f32,
+}
+
+impl CreosoteTracker {
+    pub fn new() -> Self {
+        CreosoteTracker::with_config(CreosoteConfig::default())
+    }
+
+    pub fn with_config(config: CreosoteConfig) -> Self {
+        CreosoteTracker {
+            config,
+            last_sample: None,
+            burn: 0.0,
+            burn_active: false,
+            days: [0.0; WINDOW_DAYS],
+            today: 0,
+            day_started: None,
+            since_sweep: 0.0,
+        }
+    }
+
+    // returns the notice when the index since the last sweep first passes the threshold
+    pub fn update(
+        &mut self,
+        now: Instant,
+        flue: Temperature,
+        state: BurnState,
+    ) -> Option<CreosoteNotice> {
+        self.roll_days(now);
+
+        // a new burn starts on the first smoky sample
+        let burning = smoky(state);
+        if burning && !self.burn_active {
+            self.burn = 0.0;
+        }
+
+        let was_due = self.inspection_due();
+        if let Some((last_time, last_flue)) = self.last_sample {
+            let elapsed = now.saturating_duration_since(last_time);
+            if burning && elapsed <= self.config.max_sample_gap {
+                // trapezoidal, like the energy estimate
+                let weight = (self.weight(last_flue) + self.weight(flue)) / 2.0;
+                let points = weight * elapsed.as_secs_f32() / SECONDS_PER_HOUR;
+                self.burn += points;
+                self.days[self.today] += points;
+                self.since_sweep += points;
+            }
+        }
+
+        self.burn_active = burning;
+        self.last_sample = Some((now, flue));
+
+        (!was_due && self.inspection_due()).then_some(CreosoteNotice::ConsiderInspection)
+    }
+
+    // The burn in progress, or the most recently finished one
+    pub fn burn(&self) -> f32 {
+        self.burn
+    }
+
+    // The last 30 days, counting today
+    pub fn rolling(&self) -> f32 {
+        self.days.iter().sum()
+    }
+
+    pub fn since_sweep(&self) -> f32 {
+        self.since_sweep
+    }
+
+    pub fn history(&self) -> CreosoteHistory {
+        CreosoteHistory {
+            days: self.days,
+            today: self.today,
+            day_started: self.day_started.map(|started| started.since_origin()),
+            since_sweep: self.since_sweep,
+        }
+    }
+
+    // carries the index over from before a reboot
+    pub fn restore(&mut self, history: CreosoteHistory) {
+        self.days = history.days;
+        self.today = history.today % WINDOW_DAYS;
+        self.day_started = history.day_started.map(Instant::from_duration);
+        self.since_sweep = history.since_sweep;
+    }
+
+    pub fn inspection_due(&self) -> bool {
+        self.since_sweep >= self.config.inspection_threshold
+    }
+
+    pub fn chimney_swept(&mut self) {
+        self.since_sweep = 0.0;
+    }
+
+    // how much an hour at this flue temperature adds to the index
+    fn weight(&self, flue: Temperature) -> f32 {
+        let depth = (self.config.band_top - flue).celsius();
+        let full = (self.config.band_top - self.config.full_weight_below).celsius();
+        (depth / full).clamp(0.0, 1.0)
+    }
+
+    fn roll_days(&mut self, now: Instant) {
+        let started = match self.day_started {
+            Some(started) if started <= now => started,
+            // the first update, or the clock has gone back past the start of the day
+            _ => {
+                self.day_started = Some(now);
+                return;
+            }
+        };
+        let days = (now - started).as_secs() / SECONDS_PER_DAY;
+        if days == 0 {
+            return;
+        }
+        // clear the days skipped over, and the new today
+        for _ in 0..days.min(WINDOW_DAYS as u64) {
+            self.today = (self.today + 1) % WINDOW_DAYS;
+            self.days[self.today] = 0.0;
+        }
+        self.day_started = Some(started + Duration::from_secs(days * SECONDS_PER_DAY));
+    }
+}
+
+impl Default for CreosoteTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::at;
+
+    const FLOAT_TOLERANCE: f32 = 0.01;
+    const SAMPLE_PERIOD: u64 = 60;
+
+    // feeds a constant flue temperature every minute for `minutes`, starting at `start`
+    fn burn(
+        tracker: &mut CreosoteTracker,
+        start: u64,
+        minutes: u64,
+        flue_f: f32,
+        state: BurnState,
+    ) -> Vec<CreosoteNotice> {
+        (0..=minutes)
+            .filter_map(|m| {
+                tracker.update(
+                    at(start + m * SAMPLE_PERIOD),
+                    Temperature::from_fahrenheit(flue_f),
+                    state,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn hot_flue_adds_nothing() {
+        let mut tracker = CreosoteTracker::new();
+        burn(&mut tracker, 0, 120, 400.0, BurnState::ActiveBurn);
+
+        assert_eq!(tracker.burn(), 0.0);
+        assert_eq!(tracker.rolling(), 0.0);
+    }
+
+    #[test]
+    fn cold_flue_counts_weighted_hours() {
+        let mut tracker = CreosoteTracker::new();
+        // two hours smoldering at 150°F, a point an hour
+        burn(&mut tracker, 0, 120, 150.0, BurnState::LowBurn);
+        assert!((tracker.burn() - 2.0).abs() < FLOAT_TOLERANCE);
+
+        // halfway into the band counts half
+        let mut tracker = CreosoteTracker::new();
+        burn(&mut tracker, 0, 120, 200.0, BurnState::LowBurn);
+        assert!((tracker.burn() - 1.0).abs() < FLOAT_TOLERANCE);
+
+        // and colder than the full weight temperature no more than a point an hour
+        let mut tracker = CreosoteTracker::new();
+        burn(&mut tracker, 0, 120, 100.0, BurnState::LowBurn);
+        assert!((tracker.burn() - 2.0).abs() < FLOAT_TOLERANCE);
+    }
+
+    #[test]
+    fn only_counts_while_there_is_smoke() {
+        let mut tracker = CreosoteTracker::new();
+        for state in [BurnState::Idle, BurnState::Coaling, BurnState::Cooling] {
+            burn(&mut tracker, 0, 60, 150.0, state);
+        }
+
+        assert_eq!(tracker.since_sweep(), 0.0);
+    }
+
+    #[test]
+    fn each_burn_starts_over() {
+        let mut tracker = CreosoteTracker::new();
+        burn(&mut tracker, 0, 60, 150.0, BurnState::LowBurn);
+        burn(&mut tracker, 3600, 60, 150.0, BurnState::Idle);
+        // the last burn is still reported once it's over
+        assert!((tracker.burn() - 1.0).abs() < FLOAT_TOLERANCE);
+
+        burn(&mut tracker, 7200, 30, 150.0, BurnState::LowBurn);
+        assert!((tracker.burn() - 0.5).abs() < FLOAT_TOLERANCE);
+        assert!((tracker.since_sweep() - 1.5).abs() < FLOAT_TOLERANCE);
+    }
+
+    #[test]
+    fn rolling_index_forgets_after_30_days() {
+        let day = SECONDS_PER_DAY;
+        let mut tracker = CreosoteTracker::new();
+        // an hour smoldering on day 0 and on day 10
+        burn(&mut tracker, 0, 60, 150.0, BurnState::LowBurn);
+        burn(&mut tracker, 10 * day, 60, 150.0, BurnState::LowBurn);
+        assert!((tracker.rolling() - 2.0).abs() < FLOAT_TOLERANCE);
+
+        // day 0 drops out on day 30, day 10 on day 40
+        burn(&mut tracker, 30 * day, 0, 400.0, BurnState::ActiveBurn);
+        assert!((tracker.rolling() - 1.0).abs() < FLOAT_TOLERANCE);
+        burn(&mut tracker, 40 * day, 0, 400.0, BurnState::ActiveBurn);
+        assert_eq!(tracker.rolling(), 0.0);
+
+        // but not since the last sweep
+        assert!((tracker.since_sweep() - 2.0).abs() < FLOAT_TOLERANCE);
+    }
+
+    #[test]
+    fn long_gap_clears_the_whole_window() {
+        let mut tracker = CreosoteTracker::new();
+        burn(&mut tracker, 0, 60, 150.0, BurnState::LowBurn);
+        burn(
+            &mut tracker,
+            90 * SECONDS_PER_DAY,
+            0,
+            150.0,
+            BurnState::LowBurn,
+        );
+
+        assert_eq!(tracker.rolling(), 0.0);
+    }
+
+    #[test]
+    fn restored_history_carries_on_the_window() {
+        let day = SECONDS_PER_DAY;
+        let mut tracker = CreosoteTracker::new();
+        burn(&mut tracker, 0, 60, 150.0, BurnState::LowBurn);
+        burn(&mut tracker, 10 * day, 60, 150.0, BurnState::LowBurn);
+
+        let bytes = tracker.history().encode();
+        let mut restored = CreosoteTracker::new();
+        restored.restore(CreosoteHistory::decode(&bytes).unwrap());
+        assert!((restored.rolling() - 2.0).abs() < FLOAT_TOLERANCE);
+        assert!((restored.since_sweep() - 2.0).abs() < FLOAT_TOLERANCE);
+
+        // the days still drop out when they would have without the reboot
+        burn(&mut restored, 30 * day, 0, 400.0, BurnState::ActiveBurn);
+        assert!((restored.rolling() - 1.0).abs() < FLOAT_TOLERANCE);
+    }
+
+    #[test]
+    fn restarted_clock_starts_the_day_over() {
+        let mut tracker = CreosoteTracker::new();
+        burn(&mut tracker, 0, 0, 400.0, BurnState::ActiveBurn);
+        burn(
+            &mut tracker,
+            5 * SECONDS_PER_DAY,
+            60,
+            150.0,
+            BurnState::LowBurn,
+        );
+
+        // a clock that started over after a power cut keeps the days it had
+        burn(&mut tracker, 60, 0, 400.0, BurnState::ActiveBurn);
+        assert!((tracker.rolling() - 1.0).abs() < FLOAT_TOLERANCE);
+        // and counts the next day from when it was first seen
+        burn(
+            &mut tracker,
+            60 + SECONDS_PER_DAY,
+            0,
+            400.0,
+            BurnState::ActiveBurn,
+        );
+        assert!((tracker.rolling() - 1.0).abs() < FLOAT_TOLERANCE);
+        assert_eq!(tracker.history().today, 6);
+    }
+
+    #[test]
+    fn history_rejects_corruption() {
+        let mut tracker = CreosoteTracker::new();
+        burn(&mut tracker, 0, 60, 150.0, BurnState::LowBurn);
+        let bytes = tracker.history().encode();
+
+        let mut corrupt = bytes;
+        corrupt[20] ^= 0x10;
+        assert_eq!(
+            CreosoteHistory::decode(&corrupt),
+            Err(HistoryError::BadChecksum)
+        );
+        assert_eq!(
+            CreosoteHistory::decode(&bytes[1..]),
+            Err(HistoryError::WrongLength)
+        );
+        let mut newer = bytes;
+        newer[0] = 2;
+        assert_eq!(
+            CreosoteHistory::decode(&newer),
+            Err(HistoryError::UnknownVersion(2))
+        );
+    }
+
+    #[test]
+    fn suggests_inspection_once_past_the_threshold() {
+        let mut tracker = CreosoteTracker::with_config(CreosoteConfig {
+            inspection_threshold: 3.0,
+            ..Default::default()
+        });
+        tracker.restore(CreosoteHistory {
+            since_sweep: 2.0,
+            ..tracker.history()
+        });
+
+        // the notice comes an hour in, and only once
+        let notices = burn(&mut tracker, 0, 120, 150.0, BurnState::LowBurn);
+        assert_eq!(notices, [CreosoteNotice::ConsiderInspection]);
+        assert!(tracker.inspection_due());
+
+        tracker.chimney_swept();
+        assert!(!tracker.inspection_due());
+        assert_eq!(tracker.since_sweep(), 0.0);
+    }
+}
@@ -0,0 +1,370 @@
+use core::{fmt::Write, time::Duration};
+
+use embedded_graphics::{
+    image::{Image, ImageRaw},
+    mono_font::{
+        MonoTextStyle,
+        iso_8859_1::{FONT_6X10, FONT_10X20},
+    },
+    pixelcolor::BinaryColor,
+    prelude::*,
+    primitives::{Line, PrimitiveStyle, Rectangle, Triangle},
+    text::{Baseline, Text},
+};
+
+use crate::{
+    state_machine::BurnState,
+    temperature::{RateOfChange, Temperature, TemperatureUnit},
+    text::TextBuf,
+};
+
+// 128x64, e.g. an SSD1306
+pub const WIDTH: u32 = 128;
+pub const HEIGHT: u32 = 64;
+
+// a slower change than 1°F a minute reads as steady
+const STEADY_CELSIUS_PER_MINUTE: f32 = 5.0 / 9.0;
+
+#[rustfmt::skip]
+const WIFI_ICON: [u8; 8] = [
+    0b00111100,
+    0b01000010,
+    0b10011001,
+    0b00100100,
+    0b01000010,
+    0b00011000,
+    0b00011000,
+    0b00000000,
+];
+
+#[rustfmt::skip]
+const MQTT_ICON: [u8; 8] = [
+    0b11111111,
+    0b10000001,
+    0b10100101,
+    0b10111101,
+    0b10100101,
+    0b10100101,
+    0b10000001,
+    0b11111111,
+];
+
+// Which way the temperature is heading
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trend {
+    Rising,
+    Steady,
+    Falling,
+}
+
+impl Trend {
+    pub fn of(rate: RateOfChange) -> Trend {
+        let per_minute = rate.celsius_per_minute();
+        if per_minute >= STEADY_CELSIUS_PER_MINUTE {
+            Trend::Rising
+        } else if per_minute <= -STEADY_CELSIUS_PER_MINUTE {
+            Trend::Falling
+        } else {
+            Trend::Steady
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReloadHint {
+    None,
+    // predicted time until a reload is due
+    In(Duration),
+    Now,
+}
+
+// Everything on the screen
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DisplayView {
+    // None when there is no good reading
+    pub temperature: Option<Temperature>,
+    pub unit: TemperatureUnit,
+    pub state: BurnState,
+    pub time_in_state: Duration,
+    pub rate: Option<RateOfChange>,
+    pub reload: ReloadHint,
+    pub wifi_connected: bool,
+    pub mqtt_connected: bool,
+}
+
+// Draws the whole screen: burn state and connection icons along the top, the temperature and
+// its trend large in the middle, then time in state and the reload hint.
+pub fn draw<D>(view: &DisplayView, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let small = MonoTextStyle::new(&FONT_6X10, BinaryColor::On);
+    let large = MonoTextStyle::new(&FONT_10X20, BinaryColor::On);
+    let mut text = TextBuf::<24>::new();
+
+    target.clear(BinaryColor::Off)?;
+
+    Text::with_baseline(label(view.state), Point::zero(), small, Baseline::Top).draw(target)?;
+    icon(&WIFI_ICON, Point::new(106, 1), view.wifi_connected, target)?;
+    icon(&MQTT_ICON, Point::new(118, 1), view.mqtt_connected, target)?;
+
+    match view.temperature {
+        Some(temp) => write!(text, "{:.0}", temp.display_in(view.unit)).ok(),
+        None => write!(text, "---").ok(),
+    };
+    Text::with_baseline(text.as_str(), Point::new(0, 16), large, Baseline::Top).draw(target)?;
+    if let Some(rate) = view.rate {
+        trend(Trend::of(rate), Point::new(108, 18), target)?;
+    }
+
+    text.clear();
+    write!(text, "for ").ok();
+    write_duration(&mut text, view.time_in_state);
+    Text::with_baseline(text.as_str(), Point::new(0, 40), small, Baseline::Top).draw(target)?;
+
+    text.clear();
+    match view.reload {
+        ReloadHint::None => {}
+        ReloadHint::In(remaining) => {
+            write!(text, "reload in ").ok();
+            write_duration(&mut text, remaining);
+            Text::with_baseline(text.as_str(), Point::new(0, 53), small, Baseline::Top)
+                .draw(target)?;
+        }
+        ReloadHint::Now => {
+            // inverted, to stand out
+            Rectangle::new(Point::new(0, 52), Size::new(WIDTH, 12))
+                .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+                .draw(target)?;
+            let inverted = MonoTextStyle::new(&FONT_6X10, BinaryColor::Off);
+            Text::with_baseline("RELOAD NOW", Point::new(34, 53), inverted, Baseline::Top)
+                .draw(target)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn label(state: BurnState) -> &'static str {
+    match state {
+        BurnState::Idle => "IDLE",
+        BurnState::Startup => "STARTUP",
+        BurnState::ActiveBurn => "ACTIVE BURN",
+        BurnState::LowBurn => "LOW BURN",
+        BurnState::Refueling => "REFUELING",
+        BurnState::Coaling => "COALING",
+        BurnState::Cooling => "COOLING",
+        BurnState::Overheat => "OVERHEAT",
+        BurnState::SensorFault => "SENSOR FAULT",
+    }
+}
+
+// struck through while disconnected
+fn icon<D>(bits: &[u8; 8], at: Point, connected: bool, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let raw = ImageRaw::<BinaryColor>::new(bits, 8);
+    Image::new(&raw, at).draw(target)?;
+    if !connected {
+        Line::new(at + Point::new(0, 7), at + Point::new(7, 0))
+            .into_styled(PrimitiveStyle::with_stroke(BinaryColor::On, 1))
+            .draw(target)?;
+    }
+    Ok(())
+}
+
+// a 16px arrow: up, down or to the right
+fn trend<D>(trend: Trend, at: Point, target: &mut D) -> Result<(), D::Error>
+where
+    D: DrawTarget<Color = BinaryColor>,
+{
+    let (a, b, c) = match trend {
+        Trend::Rising => (Point::new(0, 15), Point::new(15, 15), Point::new(7, 0)),
+        Trend::Falling => (Point::new(0, 0), Point::new(15, 0), Point::new(7, 15)),
+        Trend::Steady => (Point::new(0, 0), Point::new(0, 15), Point::new(15, 7)),
+    };
+    Triangle::new(at + a, at + b, at + c)
+        .into_styled(PrimitiveStyle::with_fill(BinaryColor::On))
+        .draw(target)
+}
+
+// e.g. 45m, or 2h05m
+fn write_duration<W: Write>(out: &mut W, duration: Duration) {
+    let minutes = duration.as_secs() / 60;
+    if minutes < 60 {
+        write!(out, "{}m", minutes).ok();
+    } else {
+        write!(out, "{}h{:02}m", minutes / 60, minutes % 60).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temperature::TemperatureDelta;
+    use core::convert::Infallible;
+
+    // A host framebuffer the size of the screen
+    struct Frame {
+        pixels: [[bool; WIDTH as usize]; HEIGHT as usize],
+    }
+
+    impl Frame {
+        fn new() -> Self {
+            Self {
+                pixels: [[false; WIDTH as usize]; HEIGHT as usize],
+            }
+        }
+
+        // one line of `#` (lit) and `.` per row
+        fn to_text(&self) -> String {
+            self.pixels
+                .iter()
+                .map(|row| {
+                    let mut line: String =
+                        row.iter().map(|&on| if on { '#' } else { '.' }).collect();
+                    line.push('\n');
+                    line
+                })
+                .collect()
+        }
+    }
+
+    impl OriginDimensions for Frame {
+        fn size(&self) -> Size {
+            Size::new(WIDTH, HEIGHT)
+        }
+    }
+
+    impl DrawTarget for Frame {
+        type Color = BinaryColor;
+        type Error = Infallible;
+
+        fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+        where
+            I: IntoIterator<Item = Pixel<Self::Color>>,
+        {
+            for Pixel(point, color) in pixels {
+                // negative coordinates wrap around to out of range
+                if let Some(row) = self.pixels.get_mut(point.y as usize)
+                    && let Some(pixel) = row.get_mut(point.x as usize)
+                {
+                    *pixel = color.is_on();
+                }
+            }
+            Ok(())
+        }
+    }
+
+    // compares against snapshots/<name>.txt; set UPDATE_SNAPSHOTS=1 to rewrite them instead
+    fn assert_snapshot(name: &str, view: &DisplayView) {
+        let mut frame = Frame::new();
+        draw(view, &mut frame).unwrap();
+        let rendered = frame.to_text();
+
+        let path = format!("{}/snapshots/{}.txt", env!("CARGO_MANIFEST_DIR"), name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::write(&path, &rendered).unwrap();
+            return;
+        }
+        let expected = std::fs::read_to_string(&path)
+            .unwrap_or_else(|e| panic!("reading {}: {} (UPDATE_SNAPSHOTS=1 creates it)", path, e));
+        assert!(
+            rendered == expected,
+            "{} doesn't match, rendered:\n{}",
+            name,
+            rendered
+        );
+    }
+
+    fn per_minute(fahrenheit: f32) -> RateOfChange {
+        RateOfChange::new_per_minute(TemperatureDelta::from_fahrenheit(fahrenheit), 1.0)
+    }
+
+    const BURNING: DisplayView = DisplayView {
+        temperature: None,
+        unit: TemperatureUnit::Fahrenheit,
+        state: BurnState::ActiveBurn,
+        time_in_state: Duration::from_secs(47 * 60),
+        rate: None,
+        reload: ReloadHint::None,
+        wifi_connected: true,
+        mqtt_connected: true,
+    };
+
+    #[test]
+    fn active_burn() {
+        assert_snapshot(
+            "display_active_burn",
+            &DisplayView {
+                temperature: Some(Temperature::from_fahrenheit(523.0)),
+                rate: Some(per_minute(4.0)),
+                ..BURNING
+            },
+        );
+    }
+
+    #[test]
+    fn coaling_reload_due() {
+        assert_snapshot(
+            "display_reload_now",
+            &DisplayView {
+                temperature: Some(Temperature::from_fahrenheit(288.0)),
+                state: BurnState::Coaling,
+                time_in_state: Duration::from_secs(2 * 60 * 60 + 5 * 60),
+                rate: Some(per_minute(-2.0)),
+                reload: ReloadHint::Now,
+                ..BURNING
+            },
+        )
+    }
+
+    #[test]
+    fn coaling_in_celsius_with_prediction() {
+        assert_snapshot(
+            "display_reload_in_celsius",
+            &DisplayView {
+                temperature: Some(Temperature::from_celsius(180.0)),
+                unit: TemperatureUnit::Celsius,
+                state: BurnState::Coaling,
+                rate: Some(per_minute(-0.5)),
+                reload: ReloadHint::In(Duration::from_secs(25 * 60)),
+                ..BURNING
+            },
+        )
+    }
+
+    #[test]
+    fn sensor_fault_offline() {
+        assert_snapshot(
+            "display_sensor_fault_offline",
+            &DisplayView {
+                state: BurnState::SensorFault,
+                time_in_state: Duration::from_secs(90),
+                wifi_connected: false,
+                mqtt_connected: false,
+                ..BURNING
+            },
+        )
+    }
+
+    #[test]
+    fn trend_has_a_dead_band() {
+        assert_eq!(Trend::of(per_minute(1.5)), Trend::Rising);
+        assert_eq!(Trend::of(per_minute(0.5)), Trend::Steady);
+        assert_eq!(Trend::of(per_minute(-0.5)), Trend::Steady);
+        assert_eq!(Trend::of(per_minute(-1.5)), Trend::Falling);
+    }
+
+    #[test]
+    fn durations_are_compact() {
+        let mut text = TextBuf::<16>::new();
+        write_duration(&mut text, Duration::from_secs(59));
+        assert_eq!(text.as_str(), "0m");
+
+        text.clear();
+        write_duration(&mut text, Duration::from_secs(2 * 60 * 60 + 5 * 60));
+        assert_eq!(text.as_str(), "2h05m");
+    }
+}
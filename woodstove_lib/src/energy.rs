@@ -0,0 +1,430 @@
+use core::{
+    ops::{Add, Sub},
+    time::Duration,
+};
+
+use crate::{state_machine::BurnState, temperature::Temperature, time::Instant};
+
+const JOULES_PER_KWH: f32 = 3_600_000.0;
+const JOULES_PER_BTU: f32 = 1_055.06;
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// An amount of energy
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+pub struct Energy {
+    joules: f32,
+}
+
+impl Energy {
+    pub fn from_joules(j: f32) -> Self {
+        Self { joules: j }
+    }
+
+    pub fn from_kilowatt_hours(kwh: f32) -> Self {
+        Self {
+            joules: kwh * JOULES_PER_KWH,
+        }
+    }
+
+    pub fn joules(&self) -> f32 {
+        self.joules
+    }
+
+    pub fn kilowatt_hours(&self) -> f32 {
+        self.joules / JOULES_PER_KWH
+    }
+
+    pub fn btu(&self) -> f32 {
+        self.joules / JOULES_PER_BTU
+    }
+}
+
+impl Add for Energy {
+    type Output = Energy;
+
+    fn add(self, other: Energy) -> Energy {
+        Energy {
+            joules: self.joules + other.joules,
+        }
+    }
+}
+
+impl Sub for Energy {
+    type Output = Energy;
+
+    fn sub(self, other: Energy) -> Energy {
+        Energy {
+            joules: self.joules - other.joules,
+        }
+    }
+}
+
+pub struct EnergyConfig {
+    // Room temperature the stove is radiating into
+    pub ambient: Temperature,
+    // Heat delivered to the room per degree above ambient, in W/°C
+    pub heat_transfer_coefficient: f32,
+    // Fraction of the wood's energy that ends up as delivered heat
+    pub efficiency: f32,
+    // Energy released by burning one kilogram of cordwood
+    pub wood_energy_per_kg: Energy,
+    // Intervals longer than this (e.g. a sensor outage) are not integrated
+    pub max_sample_gap: Duration,
+}
+
+impl Default for EnergyConfig {
+    fn default() -> Self {
+        Self {
+            ambient: Temperature::from_fahrenheit(70.0),
+            heat_transfer_coefficient: 25.0,
+            efficiency: 0.7,
+            // seasoned hardwood at ~20% moisture
+            wood_energy_per_kg: Energy::from_kilowatt_hours(4.2),
+            max_sample_gap: Duration::from_secs(5 * 60),
+        }
+    }
+}
+
+// Heat output and the wood burned to produce it
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EnergyTotals {
+    pub heat_output: Energy,
+    pub wood_kg: f32,
+}
+
+// Integrates temperature above ambient over time into an estimate of heat output,
+// split into the current burn session (leaving Idle until returning to it) and the current day.
+pub struct EnergyTracker {
+    config: EnergyConfig,
+    last_sample: Option<(Instant, Temperature)>,
+    session: Energy,
+    session_active: bool,
+    day: Energy,
+    day_started: Option<Instant>,
+}
+
+impl EnergyTracker {
+    pub fn new() -> Self {
+        EnergyTracker::with_config(EnergyConfig::default())
+    }
+
+    pub fn with_config(config: EnergyConfig) -> Self {
+        EnergyTracker {
+            config,
+            last_sample: None,
+            session: Energy::default(),
+            session_active: false,
+            day: Energy::default(),
+            day_started: None,
+        }
+    }
+
+    pub fn update(&mut self, now: Instant, temp: Temperature, state: BurnState) {
+        self.roll_day(now);
+
+        // a new session starts on the first sample out of idle
+        let burning = state != BurnState::Idle;
+        if burning && !self.session_active {
+            self.session = Energy::default();
+        }
+
+        if let Some((last_time, last_temp)) = self.last_sample {
+            // trapezoidal integration of the power between the two samples
+            let elapsed = now.saturating_duration_since(last_time);
+            if elapsed <= self.config.max_sample_gap {
+                let power = (self.power(last_temp) + self.power(temp)) / 2.0;
+                let energy = Energy::from_joules(power * elapsed.as_secs_f32());
+
+                self.day = self.day + energy;
+                if burning || self.session_active {
+                    self.session = self.session + energy;
+                }
+            }
+        }
+
+        self.session_active = burning;
+        self.last_sample = Some((now, temp));
+    }
+
+    // The burn in progress, or the most recently finished one
+    pub fn session(&self) -> EnergyTotals {
+        self.totals(self.session)
+    }
+
+    // Everything since the start of the current 24 hour period
+    pub fn day(&self) -> EnergyTotals {
+        self.totals(self.day)
+    }
+
+    pub fn session_active(&self) -> bool {
+        self.session_active
+    }
+
+    // Watts delivered to the room at a given surface temperature
+    fn power(&self, temp: Temperature) -> f32 {
+        let above_ambient = (temp - self.config.ambient).celsius().max(0.0);
+        self.config.heat_transfer_coefficient * above_ambient
+    }
+
+    fn totals(&self, heat_output: Energy) -> EnergyTotals {
+        let wood_energy = heat_output.joules() / self.config.efficiency;
+        EnergyTotals {
+            heat_output,
+            wood_kg: wood_energy / self.config.wood_energy_per_kg.joules(),
+        }
+    }
+
+    fn roll_day(&mut self, now: Instant) {
+        let day = Duration::from_secs(SECONDS_PER_DAY);
+        match self.day_started {
+            None => self.day_started = Some(now),
+            Some(started) => {
+                if now.saturating_duration_since(started) >= day {
+                    // the interval spanning the boundary is credited to the new day
+                    let days = now.saturating_duration_since(started).as_secs() / SECONDS_PER_DAY;
+                    self.day_started = Some(started + day * days as u32);
+                    self.day = Energy::default();
+                }
+            }
+        }
+    }
+}
+
+impl Default for EnergyTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FLOAT_TOLERANCE: f32 = 0.01;
+
+    fn config() -> EnergyConfig {
+        EnergyConfig {
+            ambient: Temperature::from_celsius(20.0),
+            heat_transfer_coefficient: 10.0,
+            efficiency: 0.5,
+            wood_energy_per_kg: Energy::from_kilowatt_hours(4.0),
+            max_sample_gap: Duration::from_secs(60),
+        }
+    }
+
+    // feeds `temp_at(seconds)` into the tracker every `step` seconds for `total` seconds
+    fn run_curve(
+        tracker: &mut EnergyTracker,
+        start: Instant,
+        total: u64,
+        step: u64,
+        state: BurnState,
+        temp_at: impl Fn(f32) -> f32,
+    ) {
+        let mut t = 0;
+        while t <= total {
+            let now = start + Duration::from_secs(t);
+            tracker.update(now, Temperature::from_celsius(temp_at(t as f32)), state);
+            t += step;
+        }
+    }
+
+    #[test]
+    fn energy_converts_units() {
+        let energy = Energy::from_kilowatt_hours(1.0);
+
+        assert!((energy.joules() - 3_600_000.0).abs() < 1.0);
+        assert!((energy.btu() - 3412.14).abs() < 0.1);
+    }
+
+    #[test]
+    fn single_sample_has_no_energy() {
+        let mut tracker = EnergyTracker::with_config(config());
+        tracker.update(
+            Instant::now(),
+            Temperature::from_celsius(300.0),
+            BurnState::ActiveBurn,
+        );
+
+        assert_eq!(tracker.session().heat_output, Energy::default());
+        assert_eq!(tracker.day().heat_output, Energy::default());
+    }
+
+    #[test]
+    fn constant_temperature_integrates_exactly() {
+        let mut tracker = EnergyTracker::with_config(config());
+        // 120°C above ambient at 10 W/°C is 1.2 kW, for one hour
+        run_curve(
+            &mut tracker,
+            Instant::now(),
+            3600,
+            10,
+            BurnState::ActiveBurn,
+            |_| 140.0,
+        );
+
+        let kwh = tracker.session().heat_output.kilowatt_hours();
+        assert!((kwh - 1.2).abs() < FLOAT_TOLERANCE, "got {} kWh", kwh);
+    }
+
+    #[test]
+    fn linear_ramp_integrates_to_average() {
+        let mut tracker = EnergyTracker::with_config(config());
+        // ramps from ambient to 220°C over an hour, averaging 100°C above ambient
+        run_curve(
+            &mut tracker,
+            Instant::now(),
+            3600,
+            10,
+            BurnState::Startup,
+            |t| 20.0 + 200.0 * t / 3600.0,
+        );
+
+        let kwh = tracker.session().heat_output.kilowatt_hours();
+        assert!((kwh - 1.0).abs() < FLOAT_TOLERANCE, "got {} kWh", kwh);
+    }
+
+    #[test]
+    fn below_ambient_adds_no_energy() {
+        let mut tracker = EnergyTracker::with_config(config());
+        run_curve(
+            &mut tracker,
+            Instant::now(),
+            600,
+            10,
+            BurnState::Idle,
+            |_| 5.0,
+        );
+
+        assert_eq!(tracker.day().heat_output, Energy::default());
+    }
+
+    #[test]
+    fn wood_consumption_accounts_for_efficiency() {
+        let mut tracker = EnergyTracker::with_config(config());
+        // 2 kWh delivered at 50% efficiency burns 4 kWh of wood, 1 kg at 4 kWh/kg
+        run_curve(
+            &mut tracker,
+            Instant::now(),
+            3600,
+            10,
+            BurnState::ActiveBurn,
+            |_| 220.0,
+        );
+
+        let session = tracker.session();
+        assert!((session.heat_output.kilowatt_hours() - 2.0).abs() < FLOAT_TOLERANCE);
+        assert!((session.wood_kg - 1.0).abs() < FLOAT_TOLERANCE);
+    }
+
+    #[test]
+    fn idle_warmth_is_not_part_of_a_session() {
+        let mut tracker = EnergyTracker::with_config(config());
+        let start = Instant::now();
+        run_curve(&mut tracker, start, 3600, 10, BurnState::Idle, |_| 140.0);
+
+        assert!(!tracker.session_active());
+        assert_eq!(tracker.session().heat_output, Energy::default());
+        assert!(tracker.day().heat_output.kilowatt_hours() > 1.0);
+    }
+
+    #[test]
+    fn new_session_starts_when_leaving_idle() {
+        let mut tracker = EnergyTracker::with_config(config());
+        let start = Instant::now();
+        run_curve(&mut tracker, start, 3600, 10, BurnState::ActiveBurn, |_| {
+            140.0
+        });
+        run_curve(
+            &mut tracker,
+            start + Duration::from_secs(3610),
+            3600,
+            10,
+            BurnState::Idle,
+            |_| 20.0,
+        );
+
+        // the finished session is still reported while idle
+        assert!(!tracker.session_active());
+        let finished = tracker.session().heat_output.kilowatt_hours();
+        assert!(finished > 1.2, "got {} kWh", finished);
+
+        run_curve(
+            &mut tracker,
+            start + Duration::from_secs(7220),
+            600,
+            10,
+            BurnState::Startup,
+            |_| 140.0,
+        );
+
+        assert!(tracker.session_active());
+        let second = tracker.session().heat_output.kilowatt_hours();
+        assert!((second - 0.2).abs() < FLOAT_TOLERANCE, "got {} kWh", second);
+    }
+
+    #[test]
+    fn day_total_spans_sessions() {
+        let mut tracker = EnergyTracker::with_config(config());
+        let start = Instant::now();
+        run_curve(&mut tracker, start, 3600, 10, BurnState::ActiveBurn, |_| {
+            140.0
+        });
+        run_curve(
+            &mut tracker,
+            start + Duration::from_secs(3600),
+            3600,
+            10,
+            BurnState::Coaling,
+            |_| 140.0,
+        );
+
+        let kwh = tracker.day().heat_output.kilowatt_hours();
+        assert!((kwh - 2.4).abs() < FLOAT_TOLERANCE, "got {} kWh", kwh);
+    }
+
+    #[test]
+    fn long_gaps_are_not_integrated() {
+        let mut tracker = EnergyTracker::with_config(config());
+        let start = Instant::now();
+        tracker.update(
+            start,
+            Temperature::from_celsius(140.0),
+            BurnState::ActiveBurn,
+        );
+        tracker.update(
+            start + Duration::from_secs(3600),
+            Temperature::from_celsius(140.0),
+            BurnState::ActiveBurn,
+        );
+
+        assert_eq!(tracker.session().heat_output, Energy::default());
+    }
+
+    #[test]
+    fn day_total_resets_after_24_hours() {
+        let mut tracker = EnergyTracker::with_config(config());
+        let start = Instant::now();
+        run_curve(&mut tracker, start, 3600, 10, BurnState::ActiveBurn, |_| {
+            140.0
+        });
+
+        let next_day = start + Duration::from_secs(SECONDS_PER_DAY);
+        tracker.update(
+            next_day,
+            Temperature::from_celsius(140.0),
+            BurnState::ActiveBurn,
+        );
+        run_curve(
+            &mut tracker,
+            next_day,
+            600,
+            10,
+            BurnState::ActiveBurn,
+            |_| 140.0,
+        );
+
+        let kwh = tracker.day().heat_output.kilowatt_hours();
+        assert!((kwh - 0.2).abs() < FLOAT_TOLERANCE, "got {} kWh", kwh);
+    }
+}
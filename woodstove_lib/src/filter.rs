@@ -0,0 +1,316 @@
+use crate::{
+    temperature::{RateOfChange, Temperature, TemperatureDelta},
+    time::Instant,
+    window::SampleWindow,
+};
+
+// Scales a median absolute deviation to a standard deviation for normally distributed noise
+const MAD_TO_STD: f32 = 1.4826;
+
+pub struct FilterConfig {
+    // Fastest a real stove can heat or cool; anything faster is a bad frame
+    pub max_slew_rate: RateOfChange,
+    // Hampel rejection: readings further than this many standard deviations from the
+    // median of recent readings are outliers
+    pub outlier_threshold: f32,
+    // Jitter that is always allowed, so a flat history doesn't reject tiny changes
+    pub noise_floor: TemperatureDelta,
+    // A run of rejections this long is a real step change, so the filter re-anchors on it
+    pub max_consecutive_rejections: u32,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            max_slew_rate: RateOfChange::new_per_second(TemperatureDelta::from_celsius(5.0), 1.0),
+            outlier_threshold: 3.0,
+            noise_floor: TemperatureDelta::from_celsius(5.0),
+            max_consecutive_rejections: 3,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RejectReason {
+    // changed faster than max_slew_rate since the last good reading
+    Slew,
+    // too far from the recent readings
+    Outlier,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FilterOutcome {
+    // the median of the recent good readings
+    Accepted(Temperature),
+    Rejected(RejectReason),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FilterStats {
+    pub accepted: u32,
+    pub rejected_slew: u32,
+    pub rejected_outlier: u32,
+}
+
+impl FilterStats {
+    pub fn rejected(&self) -> u32 {
+        self.rejected_slew + self.rejected_outlier
+    }
+}
+
+// Cleans up raw sensor readings before they reach the state machine: a slew rate limit,
+// Hampel outlier rejection, and a median over the last N good readings
+pub struct SampleFilter<const N: usize = 5> {
+    config: FilterConfig,
+    recent: SampleWindow<Temperature, N>,
+    last_accepted: Option<(Instant, Temperature)>,
+    consecutive_rejections: u32,
+    stats: FilterStats,
+}
+
+impl<const N: usize> SampleFilter<N> {
+    pub fn new() -> Self {
+        SampleFilter::with_config(FilterConfig::default())
+    }
+
+    pub fn with_config(config: FilterConfig) -> Self {
+        SampleFilter {
+            config,
+            recent: SampleWindow::new(),
+            last_accepted: None,
+            consecutive_rejections: 0,
+            stats: FilterStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> FilterStats {
+        self.stats
+    }
+
+    // forget recent readings, e.g. after the sensor has been disconnected
+    pub fn reset(&mut self) {
+        self.recent.clear();
+        self.last_accepted = None;
+        self.consecutive_rejections = 0;
+    }
+
+    pub fn filter(&mut self, now: Instant, temp: Temperature) -> FilterOutcome {
+        if let Some(reason) = self.check(now, temp) {
+            self.consecutive_rejections += 1;
+            if self.consecutive_rejections < self.config.max_consecutive_rejections {
+                match reason {
+                    RejectReason::Slew => self.stats.rejected_slew += 1,
+                    RejectReason::Outlier => self.stats.rejected_outlier += 1,
+                }
+                return FilterOutcome::Rejected(reason);
+            }
+            // the "glitch" has persisted, so it's the new reality
+            self.recent.clear();
+        }
+
+        self.consecutive_rejections = 0;
+        self.stats.accepted += 1;
+        self.recent.push(temp);
+        self.last_accepted = Some((now, temp));
+
+        FilterOutcome::Accepted(self.median())
+    }
+
+    fn check(&self, now: Instant, temp: Temperature) -> Option<RejectReason> {
+        let (last_time, last_temp) = self.last_accepted?;
+
+        let seconds = now.saturating_duration_since(last_time).as_secs_f32();
+        let allowed = self.config.max_slew_rate.celsius_per_second().abs() * seconds
+            + self.config.noise_floor.celsius();
+        if (temp - last_temp).celsius().abs() > allowed {
+            return Some(RejectReason::Slew);
+        }
+
+        // Hampel needs a few readings for the median and spread to mean anything
+        if self.recent.len() >= 3 {
+            let median = self.median();
+            let deviation = MAD_TO_STD * self.median_absolute_deviation(median);
+            let limit =
+                self.config.outlier_threshold * deviation.max(self.config.noise_floor.celsius());
+            if (temp - median).celsius().abs() > limit {
+                return Some(RejectReason::Outlier);
+            }
+        }
+
+        None
+    }
+
+    fn median(&self) -> Temperature {
+        let mut values = [0.0; N];
+        for (slot, temp) in values.iter_mut().zip(self.recent.iter()) {
+            *slot = temp.celsius();
+        }
+        Temperature::from_celsius(median_of(&mut values[..self.recent.len()]))
+    }
+
+    fn median_absolute_deviation(&self, median: Temperature) -> f32 {
+        let mut deviations = [0.0; N];
+        for (slot, temp) in deviations.iter_mut().zip(self.recent.iter()) {
+            *slot = (temp - median).celsius().abs();
+        }
+        median_of(&mut deviations[..self.recent.len()])
+    }
+}
+
+impl<const N: usize> Default for SampleFilter<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn median_of(values: &mut [f32]) -> f32 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_unstable_by(|a, b| a.total_cmp(b));
+    let mid = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[mid - 1] + values[mid]) / 2.0
+    } else {
+        values[mid]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::time::Duration;
+
+    const SAMPLE_PERIOD: u64 = 10;
+
+    // feeds °C readings every 10 seconds, returning every outcome
+    fn run(filter: &mut SampleFilter, readings: &[f32]) -> Vec<FilterOutcome> {
+        let start = Instant::now();
+        readings
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                filter.filter(
+                    start + Duration::from_secs(i as u64 * SAMPLE_PERIOD),
+                    Temperature::from_celsius(*c),
+                )
+            })
+            .collect()
+    }
+
+    fn accepted(outcome: &FilterOutcome) -> Option<f32> {
+        match outcome {
+            FilterOutcome::Accepted(t) => Some(t.celsius()),
+            FilterOutcome::Rejected(_) => None,
+        }
+    }
+
+    #[test]
+    fn first_reading_is_accepted() {
+        let mut filter = SampleFilter::<5>::new();
+
+        let outcomes = run(&mut filter, &[250.0]);
+
+        assert_eq!(accepted(&outcomes[0]), Some(250.0));
+    }
+
+    #[test]
+    fn steady_ramp_passes_through() {
+        let mut filter = SampleFilter::<5>::new();
+        // a brisk 8°C per sample climb
+        let ramp: Vec<f32> = (0..30).map(|i| 100.0 + 8.0 * i as f32).collect();
+
+        let outcomes = run(&mut filter, &ramp);
+
+        assert!(outcomes.iter().all(|o| accepted(o).is_some()));
+        assert_eq!(filter.stats().rejected(), 0);
+    }
+
+    #[test]
+    fn median_smooths_jitter() {
+        let mut filter = SampleFilter::<5>::new();
+
+        let outcomes = run(&mut filter, &[200.0, 203.0, 199.0, 202.0, 198.0]);
+
+        assert_eq!(accepted(&outcomes[4]), Some(200.0));
+    }
+
+    #[test]
+    fn rejects_impossible_jump() {
+        let mut filter = SampleFilter::<5>::new();
+
+        // a bad frame decoding to 0°C in the middle of a burn
+        let outcomes = run(&mut filter, &[300.0, 301.0, 0.0, 302.0]);
+
+        assert_eq!(outcomes[2], FilterOutcome::Rejected(RejectReason::Slew));
+        assert!(accepted(&outcomes[3]).is_some());
+        assert_eq!(filter.stats().rejected_slew, 1);
+    }
+
+    #[test]
+    fn rejects_plausible_outlier() {
+        let mut filter = SampleFilter::<5>::new();
+
+        // within what the stove could do in 10 seconds, but nothing like the readings around it
+        let outcomes = run(&mut filter, &[300.0, 301.0, 300.0, 299.0, 340.0, 300.0]);
+
+        assert_eq!(outcomes[4], FilterOutcome::Rejected(RejectReason::Outlier));
+        assert!(accepted(&outcomes[5]).is_some());
+        assert_eq!(filter.stats().rejected_outlier, 1);
+    }
+
+    #[test]
+    fn glitch_does_not_reach_the_output() {
+        let mut filter = SampleFilter::<5>::new();
+
+        let outcomes = run(&mut filter, &[300.0, 301.0, 300.0, 299.0, 340.0, 300.0]);
+
+        for outcome in outcomes.iter().filter_map(accepted) {
+            assert!((299.0..=301.0).contains(&outcome), "leaked {}", outcome);
+        }
+    }
+
+    #[test]
+    fn persistent_step_is_eventually_accepted() {
+        let mut filter = SampleFilter::<5>::new();
+
+        // the probe was reseated somewhere much cooler
+        let outcomes = run(
+            &mut filter,
+            &[300.0, 300.0, 300.0, 120.0, 120.0, 120.0, 121.0, 120.0],
+        );
+
+        assert!(accepted(&outcomes[3]).is_none());
+        assert!(accepted(&outcomes[4]).is_none());
+        assert_eq!(accepted(&outcomes[5]), Some(120.0));
+        assert!(accepted(&outcomes[6]).is_some());
+        assert!(accepted(&outcomes[7]).is_some());
+        assert_eq!(filter.stats().rejected_slew, 2);
+    }
+
+    #[test]
+    fn slew_allowance_grows_with_time() {
+        let mut filter = SampleFilter::<5>::new();
+        let start = Instant::now();
+        filter.filter(start, Temperature::from_celsius(100.0));
+
+        // 150°C in 60 seconds is fine, even though it would be impossible in 10
+        let outcome = filter.filter(
+            start + Duration::from_secs(60),
+            Temperature::from_celsius(250.0),
+        );
+
+        assert!(accepted(&outcome).is_some());
+    }
+
+    #[test]
+    fn reset_forgets_history() {
+        let mut filter = SampleFilter::<5>::new();
+        run(&mut filter, &[300.0, 300.0, 300.0]);
+
+        filter.reset();
+        let outcome = filter.filter(Instant::now(), Temperature::from_celsius(20.0));
+
+        assert_eq!(accepted(&outcome), Some(20.0));
+    }
+}
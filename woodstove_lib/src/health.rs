@@ -0,0 +1,359 @@
+use core::{fmt::Display, str::FromStr, time::Duration};
+
+use crate::time::Instant;
+
+// Why the supervisor asked for a reboot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RebootReason {
+    // no broker connection and no good reading for too long; the WiFi stack may be wedged
+    Offline,
+    // no good reading for too long; the SPI bus may be stuck
+    SensorSilent,
+}
+
+impl RebootReason {
+    pub const ALL: [RebootReason; 2] = [RebootReason::Offline, RebootReason::SensorSilent];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            RebootReason::Offline => "offline",
+            RebootReason::SensorSilent => "sensor_silent",
+        }
+    }
+}
+
+impl Display for RebootReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownRebootReason;
+
+impl Display for UnknownRebootReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "unknown reboot reason")
+    }
+}
+
+// inverts Display, for reading back a reason stored before the reboot
+impl FromStr for RebootReason {
+    type Err = UnknownRebootReason;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        RebootReason::ALL
+            .iter()
+            .find(|reason| reason.name() == s)
+            .copied()
+            .ok_or(UnknownRebootReason)
+    }
+}
+
+// Why the device last started, as reported after boot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResetReason {
+    PowerOn,
+    External,
+    Software,
+    // a software reset the supervisor asked for
+    Requested(RebootReason),
+    Panic,
+    InterruptWatchdog,
+    TaskWatchdog,
+    OtherWatchdog,
+    Brownout,
+    DeepSleep,
+    Unknown,
+}
+
+impl ResetReason {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResetReason::PowerOn => "power_on",
+            ResetReason::External => "external",
+            ResetReason::Software => "software",
+            ResetReason::Requested(reason) => reason.name(),
+            ResetReason::Panic => "panic",
+            ResetReason::InterruptWatchdog => "interrupt_watchdog",
+            ResetReason::TaskWatchdog => "task_watchdog",
+            ResetReason::OtherWatchdog => "other_watchdog",
+            ResetReason::Brownout => "brownout",
+            ResetReason::DeepSleep => "deep_sleep",
+            ResetReason::Unknown => "unknown",
+        }
+    }
+
+    // whether the reset was the firmware failing or recovering from a fault, rather than
+    // someone powering or resetting it
+    pub fn is_crash(&self) -> bool {
+        matches!(
+            self,
+            ResetReason::Requested(_)
+                | ResetReason::Panic
+                | ResetReason::InterruptWatchdog
+                | ResetReason::TaskWatchdog
+                | ResetReason::OtherWatchdog
+                | ResetReason::Brownout
+        )
+    }
+
+    // the crash count to store after starting for this reason
+    pub fn next_crash_count(&self, previous: u32) -> u32 {
+        if self.is_crash() {
+            previous.saturating_add(1)
+        } else {
+            previous
+        }
+    }
+}
+
+impl Display for ResetReason {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+pub struct HealthConfig {
+    // reboot after this long without a broker connection, unless the sensor is still being
+    // read; the main loop retries the connection itself, and a reboot would only interrupt the
+    // local alarms
+    pub max_offline: Duration,
+    // reboot after this long without a good sensor reading
+    pub max_sensor_silence: Duration,
+    // with this many crashes in a row, stop rebooting for faults a reboot evidently doesn't
+    // fix, e.g. a broken probe, rather than boot looping
+    pub max_crashes: u32,
+    // running this long without a crash clears the crash count
+    pub stable_after: Duration,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            max_offline: Duration::from_secs(15 * 60),
+            max_sensor_silence: Duration::from_secs(30 * 60),
+            max_crashes: 3,
+            stable_after: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+// How one pass of the main loop went
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LoopReport {
+    pub sensor_ok: bool,
+    pub connected: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthDecision {
+    // the loop is making progress; feed the watchdog
+    Feed,
+    // record the reason and restart
+    Reboot(RebootReason),
+}
+
+// Decides, once per main loop pass, whether the device is healthy enough to feed its watchdog
+// or should reboot. A hung loop never gets here, so the watchdog itself covers that.
+pub struct HealthSupervisor {
+    config: HealthConfig,
+    crash_count: u32,
+    started: Option<Instant>,
+    last_connected: Option<Instant>,
+    last_reading: Option<Instant>,
+}
+
+impl HealthSupervisor {
+    // `crash_count` is the count stored across reboots, after this boot was counted
+    pub fn new(crash_count: u32) -> Self {
+        Self::with_config(HealthConfig::default(), crash_count)
+    }
+
+    pub fn with_config(config: HealthConfig, crash_count: u32) -> Self {
+        Self {
+            config,
+            crash_count,
+            started: None,
+            last_connected: None,
+            last_reading: None,
+        }
+    }
+
+    pub fn check(&mut self, now: Instant, report: LoopReport) -> HealthDecision {
+        // the first pass starts every timer, so a slow boot doesn't count against it
+        let started = *self.started.get_or_insert(now);
+        if report.connected {
+            self.last_connected = Some(now);
+        }
+        if report.sensor_ok {
+            self.last_reading = Some(now);
+        }
+
+        if self.crash_count >= self.config.max_crashes {
+            return HealthDecision::Feed;
+        }
+
+        let offline = now - self.last_connected.unwrap_or(started);
+        if offline > self.config.max_offline && !report.sensor_ok {
+            return HealthDecision::Reboot(RebootReason::Offline);
+        }
+        let silent = now - self.last_reading.unwrap_or(started);
+        if silent > self.config.max_sensor_silence {
+            return HealthDecision::Reboot(RebootReason::SensorSilent);
+        }
+
+        HealthDecision::Feed
+    }
+
+    // whether this boot has run long enough that the crash count can be cleared
+    pub fn is_stable(&self, now: Instant) -> bool {
+        self.started
+            .is_some_and(|started| now - started >= self.config.stable_after)
+    }
+
+    pub fn crash_count(&self) -> u32 {
+        self.crash_count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::at;
+
+    const HEALTHY: LoopReport = LoopReport {
+        sensor_ok: true,
+        connected: true,
+    };
+
+    #[test]
+    fn feeds_while_healthy() {
+        let mut supervisor = HealthSupervisor::new(0);
+
+        for t in (0..7200).step_by(10) {
+            assert_eq!(supervisor.check(at(t), HEALTHY), HealthDecision::Feed);
+        }
+    }
+
+    #[test]
+    fn short_outage_is_tolerated() {
+        let mut supervisor = HealthSupervisor::new(0);
+        supervisor.check(at(0), HEALTHY);
+        let offline = LoopReport {
+            connected: false,
+            ..HEALTHY
+        };
+
+        for t in (10..600).step_by(10) {
+            assert_eq!(supervisor.check(at(t), offline), HealthDecision::Feed);
+        }
+        assert_eq!(supervisor.check(at(610), HEALTHY), HealthDecision::Feed);
+    }
+
+    #[test]
+    fn reboots_after_long_outage() {
+        let mut supervisor = HealthSupervisor::new(0);
+        supervisor.check(at(0), HEALTHY);
+        let offline = LoopReport {
+            sensor_ok: false,
+            connected: false,
+        };
+
+        assert_eq!(supervisor.check(at(15 * 60), offline), HealthDecision::Feed);
+        assert_eq!(
+            supervisor.check(at(15 * 60 + 10), offline),
+            HealthDecision::Reboot(RebootReason::Offline)
+        );
+    }
+
+    #[test]
+    fn never_connecting_counts_from_first_check() {
+        let mut supervisor = HealthSupervisor::new(0);
+        let offline = LoopReport {
+            sensor_ok: false,
+            connected: false,
+        };
+
+        assert_eq!(supervisor.check(at(100), offline), HealthDecision::Feed);
+        assert_eq!(
+            supervisor.check(at(100 + 16 * 60), offline),
+            HealthDecision::Reboot(RebootReason::Offline)
+        );
+    }
+
+    #[test]
+    fn long_outage_with_good_readings_keeps_running() {
+        let mut supervisor = HealthSupervisor::new(0);
+        supervisor.check(at(0), HEALTHY);
+        let offline = LoopReport {
+            connected: false,
+            ..HEALTHY
+        };
+
+        for t in (10..=4 * 60 * 60).step_by(10) {
+            assert_eq!(supervisor.check(at(t), offline), HealthDecision::Feed);
+        }
+    }
+
+    #[test]
+    fn reboots_when_sensor_goes_silent() {
+        let mut supervisor = HealthSupervisor::new(0);
+        supervisor.check(at(0), HEALTHY);
+        let silent = LoopReport {
+            sensor_ok: false,
+            ..HEALTHY
+        };
+
+        assert_eq!(
+            supervisor.check(at(31 * 60), silent),
+            HealthDecision::Reboot(RebootReason::SensorSilent)
+        );
+    }
+
+    #[test]
+    fn stops_rebooting_after_repeated_crashes() {
+        let mut supervisor = HealthSupervisor::new(3);
+        supervisor.check(at(0), HEALTHY);
+        let broken = LoopReport {
+            sensor_ok: false,
+            connected: false,
+        };
+
+        assert_eq!(
+            supervisor.check(at(2 * 60 * 60), broken),
+            HealthDecision::Feed
+        );
+    }
+
+    #[test]
+    fn becomes_stable_after_an_hour() {
+        let mut supervisor = HealthSupervisor::new(2);
+        assert!(!supervisor.is_stable(at(0)));
+
+        supervisor.check(at(30), HEALTHY);
+
+        assert!(!supervisor.is_stable(at(30 + 59 * 60)));
+        assert!(supervisor.is_stable(at(30 + 60 * 60)));
+    }
+
+    #[test]
+    fn counts_crashes_but_not_power_cycles() {
+        assert_eq!(ResetReason::TaskWatchdog.next_crash_count(1), 2);
+        assert_eq!(
+            ResetReason::Requested(RebootReason::Offline).next_crash_count(0),
+            1
+        );
+        assert_eq!(ResetReason::PowerOn.next_crash_count(2), 2);
+        assert_eq!(ResetReason::Software.next_crash_count(2), 2);
+    }
+
+    #[test]
+    fn reboot_reason_parses_its_name() {
+        for reason in RebootReason::ALL {
+            assert_eq!(reason.to_string().parse(), Ok(reason));
+            assert_eq!(ResetReason::Requested(reason).name(), reason.name());
+        }
+        assert_eq!("bored".parse::<RebootReason>(), Err(UnknownRebootReason));
+    }
+}
@@ -0,0 +1,307 @@
+use core::time::Duration;
+
+use embedded_hal::digital::OutputPin;
+
+use crate::{state_machine::BurnState, time::Instant};
+
+// One step of a blink pattern: hold the LED on or off for `duration`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    pub on: bool,
+    pub duration: Duration,
+}
+
+pub(crate) const fn on(ms: u64) -> Step {
+    Step {
+        on: true,
+        duration: Duration::from_millis(ms),
+    }
+}
+
+pub(crate) const fn off(ms: u64) -> Step {
+    Step {
+        on: false,
+        duration: Duration::from_millis(ms),
+    }
+}
+
+const HEARTBEAT: [Step; 2] = [on(50), off(2950)];
+const WIFI_CONNECTING: [Step; 2] = [on(100), off(100)];
+const MQTT_DISCONNECTED: [Step; 4] = [on(200), off(200), on(200), off(1400)];
+const SENSOR_FAULT: [Step; 6] = [on(200), off(200), on(200), off(200), on(200), off(1000)];
+const OVERHEAT: [Step; 2] = [on(50), off(50)];
+const RELOAD_SUGGESTED: [Step; 2] = [on(1000), off(2000)];
+
+// What the status LED shows, most urgent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LedStatus {
+    // strobe
+    Overheat,
+    // three blinks, then a pause
+    SensorFault,
+    // fast even blink
+    WifiConnecting,
+    // two blinks, then a pause
+    MqttDisconnected,
+    // one long blink, then a pause
+    ReloadSuggested,
+    // a short flash every three seconds
+    Heartbeat,
+}
+
+// What the status LED has to choose from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LedInput {
+    pub wifi_connected: bool,
+    pub mqtt_connected: bool,
+    pub state: BurnState,
+    pub reload_suggested: bool,
+}
+
+impl LedStatus {
+    // the most urgent status that applies; the stove itself comes before the network
+    pub fn select(input: LedInput) -> LedStatus {
+        if input.state == BurnState::Overheat {
+            LedStatus::Overheat
+        } else if input.state == BurnState::SensorFault {
+            LedStatus::SensorFault
+        } else if !input.wifi_connected {
+            LedStatus::WifiConnecting
+        } else if !input.mqtt_connected {
+            LedStatus::MqttDisconnected
+        } else if input.reload_suggested {
+            LedStatus::ReloadSuggested
+        } else {
+            LedStatus::Heartbeat
+        }
+    }
+
+    // repeated for as long as the status holds
+    pub fn pattern(&self) -> &'static [Step] {
+        match self {
+            LedStatus::Overheat => &OVERHEAT,
+            LedStatus::SensorFault => &SENSOR_FAULT,
+            LedStatus::WifiConnecting => &WIFI_CONNECTING,
+            LedStatus::MqttDisconnected => &MQTT_DISCONNECTED,
+            LedStatus::ReloadSuggested => &RELOAD_SUGGESTED,
+            LedStatus::Heartbeat => &HEARTBEAT,
+        }
+    }
+}
+
+// Where a repeating pattern is up to
+pub(crate) struct Sequence {
+    step: usize,
+    step_started: Instant,
+}
+
+impl Sequence {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            step: 0,
+            step_started: now,
+        }
+    }
+
+    // the step due at `now`, and how long until the next one
+    pub(crate) fn poll(&mut self, pattern: &[Step], now: Instant) -> (Step, Duration) {
+        // catch up on any steps missed since the last poll
+        loop {
+            let ends = self.step_started + pattern[self.step].duration;
+            if now < ends {
+                break;
+            }
+            self.step = (self.step + 1) % pattern.len();
+            self.step_started = ends;
+        }
+
+        let step = pattern[self.step];
+        (step, (self.step_started + step.duration) - now)
+    }
+}
+
+// drives the pin for a step
+pub(crate) fn apply<P: OutputPin>(pin: &mut P, step: Step) -> Result<(), P::Error> {
+    if step.on {
+        pin.set_high()
+    } else {
+        pin.set_low()
+    }
+}
+
+// Plays the pattern for the current status on an LED without blocking. Call `poll` whenever
+// the time it returns has passed, or sooner; a status change starts its pattern from the top.
+pub struct StatusLed<P: OutputPin> {
+    pin: P,
+    status: LedStatus,
+    sequence: Sequence,
+}
+
+impl<P: OutputPin> StatusLed<P> {
+    pub fn new(pin: P, status: LedStatus, now: Instant) -> Self {
+        Self {
+            pin,
+            status,
+            sequence: Sequence::new(now),
+        }
+    }
+
+    pub fn status(&self) -> LedStatus {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: LedStatus, now: Instant) {
+        if status != self.status {
+            self.status = status;
+            self.sequence = Sequence::new(now);
+        }
+    }
+
+    // drives the pin for the step due at `now`; returns how long until the next step
+    pub fn poll(&mut self, now: Instant) -> Result<Duration, P::Error> {
+        let (step, wait) = self.sequence.poll(self.status.pattern(), now);
+        apply(&mut self.pin, step)?;
+        Ok(wait)
+    }
+
+    pub fn release(self) -> P {
+        self.pin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TestPin;
+
+    const FINE: LedInput = LedInput {
+        wifi_connected: true,
+        mqtt_connected: true,
+        state: BurnState::ActiveBurn,
+        reload_suggested: false,
+    };
+
+    fn at(ms: u64) -> Instant {
+        Instant::from_millis(ms)
+    }
+
+    // whether the LED is lit at each of `times`
+    fn levels(led: &mut StatusLed<TestPin>, times: &[u64]) -> Vec<bool> {
+        times
+            .iter()
+            .map(|&t| {
+                led.poll(at(t)).unwrap();
+                led.pin.high
+            })
+            .collect()
+    }
+
+    #[test]
+    fn heartbeat_when_all_is_well() {
+        assert_eq!(LedStatus::select(FINE), LedStatus::Heartbeat);
+
+        let mut led = StatusLed::new(TestPin::default(), LedStatus::Heartbeat, at(0));
+        assert_eq!(
+            levels(&mut led, &[0, 49, 50, 2999, 3000, 3049, 3050]),
+            [true, true, false, false, true, true, false]
+        );
+    }
+
+    #[test]
+    fn poll_says_when_the_next_step_is_due() {
+        let mut led = StatusLed::new(TestPin::default(), LedStatus::Heartbeat, at(0));
+
+        assert_eq!(led.poll(at(20)), Ok(Duration::from_millis(30)));
+        assert_eq!(led.poll(at(50)), Ok(Duration::from_millis(2950)));
+    }
+
+    #[test]
+    fn sensor_fault_blinks_three_times() {
+        let mut led = StatusLed::new(TestPin::default(), LedStatus::SensorFault, at(0));
+
+        assert_eq!(
+            levels(&mut led, &[0, 200, 400, 600, 800, 1000, 1999, 2000]),
+            [true, false, true, false, true, false, false, true]
+        );
+    }
+
+    #[test]
+    fn catches_up_after_a_late_poll() {
+        let mut led = StatusLed::new(TestPin::default(), LedStatus::MqttDisconnected, at(0));
+
+        // one whole cycle is 2s; 2.25s in is the second step of the next one
+        assert_eq!(levels(&mut led, &[2_250]), [false]);
+        assert_eq!(led.poll(at(2_250)), Ok(Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn status_change_restarts_the_pattern() {
+        let mut led = StatusLed::new(TestPin::default(), LedStatus::Heartbeat, at(0));
+        led.poll(at(1_000)).unwrap();
+
+        led.set_status(LedStatus::ReloadSuggested, at(1_000));
+        assert_eq!(
+            levels(&mut led, &[1_000, 1_999, 2_000]),
+            [true, true, false]
+        );
+
+        // setting the same status again doesn't
+        led.set_status(LedStatus::ReloadSuggested, at(2_500));
+        assert_eq!(levels(&mut led, &[2_500]), [false]);
+    }
+
+    #[test]
+    fn stove_comes_before_the_network() {
+        let offline = LedInput {
+            wifi_connected: false,
+            mqtt_connected: false,
+            ..FINE
+        };
+        assert_eq!(LedStatus::select(offline), LedStatus::WifiConnecting);
+        assert_eq!(
+            LedStatus::select(LedInput {
+                state: BurnState::Overheat,
+                ..offline
+            }),
+            LedStatus::Overheat
+        );
+        assert_eq!(
+            LedStatus::select(LedInput {
+                state: BurnState::SensorFault,
+                ..offline
+            }),
+            LedStatus::SensorFault
+        );
+        assert_eq!(
+            LedStatus::select(LedInput {
+                mqtt_connected: false,
+                reload_suggested: true,
+                ..FINE
+            }),
+            LedStatus::MqttDisconnected
+        );
+        assert_eq!(
+            LedStatus::select(LedInput {
+                reload_suggested: true,
+                ..FINE
+            }),
+            LedStatus::ReloadSuggested
+        );
+    }
+
+    #[test]
+    fn every_pattern_cycles() {
+        for status in [
+            LedStatus::Overheat,
+            LedStatus::SensorFault,
+            LedStatus::WifiConnecting,
+            LedStatus::MqttDisconnected,
+            LedStatus::ReloadSuggested,
+            LedStatus::Heartbeat,
+        ] {
+            let pattern = status.pattern();
+            assert!(pattern.iter().any(|s| s.on) && pattern.iter().any(|s| !s.on));
+            assert!(pattern.iter().all(|s| !s.duration.is_zero()));
+        }
+    }
+}
@@ -1,7 +1,40 @@
+#![cfg_attr(not(any(test, feature = "std")), no_std)]
+
+pub mod alarm;
+pub mod buzzer;
+pub mod catalyst;
+pub mod channel;
+pub mod config;
+pub mod creosote;
+#[cfg(feature = "display")]
+pub mod display;
+pub mod energy;
+pub mod filter;
+pub mod health;
+pub mod led;
+pub mod ota;
+pub mod prediction;
+pub mod rate;
+pub mod rules;
+pub mod runaway;
 pub mod sensor;
+#[cfg(feature = "serde")]
+mod serialization;
+pub mod snapshot;
 pub mod state_machine;
 pub mod temperature;
+#[cfg(test)]
+mod test_support;
+mod text;
+pub mod time;
+pub mod window;
 
+pub use alarm::AlarmMonitor;
+pub use energy::EnergyTracker;
+pub use filter::SampleFilter;
+pub use health::HealthSupervisor;
+pub use runaway::RunawayDetector;
+#[cfg(feature = "max31855")]
 pub use sensor::max31855_sensor;
 pub use state_machine::{BurnState, StoveStateMachine};
 pub use temperature::Temperature;
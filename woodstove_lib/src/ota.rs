@@ -0,0 +1,207 @@
+use core::{fmt::Display, str::FromStr, time::Duration};
+
+use crate::{health::LoopReport, time::Instant};
+
+// New firmware gets this long to show it can reach the broker and read the sensor
+const DEFAULT_DEADLINE: Duration = Duration::from_secs(10 * 60);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationDecision {
+    // not proven either way yet
+    Pending,
+    // it connected and read the sensor; keep it
+    MarkValid,
+    // it didn't in time; go back to the previous firmware
+    RollBack,
+}
+
+// Decides whether freshly updated firmware works. The bootloader boots a new image once,
+// unverified, and rolls back on the next reset unless it's marked valid, so this only has to
+// cover firmware that runs but can't do its job.
+pub struct UpdateValidator {
+    deadline: Duration,
+    started: Option<Instant>,
+    connected: bool,
+    sensor_ok: bool,
+}
+
+impl UpdateValidator {
+    pub fn new() -> Self {
+        Self::with_deadline(DEFAULT_DEADLINE)
+    }
+
+    pub fn with_deadline(deadline: Duration) -> Self {
+        Self {
+            deadline,
+            started: None,
+            connected: false,
+            sensor_ok: false,
+        }
+    }
+
+    // called once per main loop pass; the two need not succeed on the same pass
+    pub fn check(&mut self, now: Instant, report: LoopReport) -> ValidationDecision {
+        let started = *self.started.get_or_insert(now);
+        self.connected |= report.connected;
+        self.sensor_ok |= report.sensor_ok;
+
+        if self.connected && self.sensor_ok {
+            ValidationDecision::MarkValid
+        } else if now - started > self.deadline {
+            ValidationDecision::RollBack
+        } else {
+            ValidationDecision::Pending
+        }
+    }
+}
+
+impl Default for UpdateValidator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// The SHA-256 an update's image has to hash to, so a tampered or truncated download is
+// thrown away before it's made bootable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageDigest(pub [u8; 32]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadDigest;
+
+impl Display for BadDigest {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "expected a SHA-256 digest as 64 hex digits")
+    }
+}
+
+impl Display for ImageDigest {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for byte in self.0 {
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+// inverts Display, in either case, e.g. from `sha256sum`
+impl FromStr for ImageDigest {
+    type Err = BadDigest;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.as_bytes();
+        if s.len() != 64 {
+            return Err(BadDigest);
+        }
+        let mut digest = [0; 32];
+        for (byte, pair) in digest.iter_mut().zip(s.chunks(2)) {
+            let pair = core::str::from_utf8(pair).map_err(|_| BadDigest)?;
+            // from_str_radix takes a sign, which isn't a digit
+            if pair.starts_with('+') {
+                return Err(BadDigest);
+            }
+            *byte = u8::from_str_radix(pair, 16).map_err(|_| BadDigest)?;
+        }
+        Ok(ImageDigest(digest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::at;
+
+    const NOTHING: LoopReport = LoopReport {
+        sensor_ok: false,
+        connected: false,
+    };
+
+    #[test]
+    fn valid_once_connected_and_read() {
+        let mut validator = UpdateValidator::new();
+
+        assert_eq!(validator.check(at(0), NOTHING), ValidationDecision::Pending);
+        assert_eq!(
+            validator.check(
+                at(10),
+                LoopReport {
+                    sensor_ok: true,
+                    connected: true,
+                }
+            ),
+            ValidationDecision::MarkValid
+        );
+    }
+
+    #[test]
+    fn successes_can_come_on_different_passes() {
+        let mut validator = UpdateValidator::new();
+        let read = LoopReport {
+            sensor_ok: true,
+            ..NOTHING
+        };
+        let connected = LoopReport {
+            connected: true,
+            ..NOTHING
+        };
+
+        assert_eq!(validator.check(at(0), read), ValidationDecision::Pending);
+        assert_eq!(
+            validator.check(at(10), NOTHING),
+            ValidationDecision::Pending
+        );
+        assert_eq!(
+            validator.check(at(20), connected),
+            ValidationDecision::MarkValid
+        );
+    }
+
+    #[test]
+    fn rolls_back_without_a_reading() {
+        let mut validator = UpdateValidator::new();
+        let connected = LoopReport {
+            connected: true,
+            ..NOTHING
+        };
+
+        assert_eq!(
+            validator.check(at(100), connected),
+            ValidationDecision::Pending
+        );
+        assert_eq!(
+            validator.check(at(100 + 10 * 60), connected),
+            ValidationDecision::Pending
+        );
+        assert_eq!(
+            validator.check(at(110 + 10 * 60), connected),
+            ValidationDecision::RollBack
+        );
+    }
+
+    #[test]
+    fn digest_parses_its_hex() {
+        let hex = "9F86D081884C7D659A2FEAA0C55AD015A3BF4F1B2B0B822CD15D6C15B0F00A08";
+        let digest: ImageDigest = hex.parse().unwrap();
+
+        assert_eq!(digest.0[0], 0x9f);
+        assert_eq!(digest.0[31], 0x08);
+        assert_eq!(digest.to_string(), hex.to_ascii_lowercase());
+    }
+
+    #[test]
+    fn rejects_malformed_digests() {
+        let good = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+        assert_eq!(good[..62].parse::<ImageDigest>(), Err(BadDigest));
+        assert_eq!(format!("{}00", good).parse::<ImageDigest>(), Err(BadDigest));
+        assert_eq!(
+            good.replace('9', "g").parse::<ImageDigest>(),
+            Err(BadDigest)
+        );
+        assert_eq!(
+            format!("+f{}", &good[2..]).parse::<ImageDigest>(),
+            Err(BadDigest)
+        );
+        assert_eq!("".parse::<ImageDigest>(), Err(BadDigest));
+    }
+}
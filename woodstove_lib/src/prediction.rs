@@ -0,0 +1,310 @@
+use core::time::Duration;
+
+use crate::{temperature::Temperature, time::Instant, window::SampleWindow};
+
+// 15 minutes of samples at the 10 second loop period
+pub const DECAY_WINDOW: usize = 90;
+// fewer samples than this don't say much about the shape of the curve
+const MIN_FIT_SAMPLES: usize = 6;
+// readings this close to ambient can't be log-transformed meaningfully
+const MIN_EXCESS_CELSIUS: f32 = 0.5;
+// a curve this flat is a stove holding steady, not one cooling down
+const MAX_HORIZON: Duration = Duration::from_secs(24 * 60 * 60);
+
+// A predicted time until some temperature is reached
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Prediction {
+    pub remaining: Duration,
+    // 0.0 (a guess) to 1.0 (the curve fits very well over a full window)
+    pub confidence: f32,
+}
+
+// Exponential decay toward ambient, T(t) = ambient + excess * e^(-t / tau),
+// with t measured in seconds from the predictor's origin
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DecayFit {
+    ln_excess: f32,
+    decay_per_second: f32,
+    r_squared: f32,
+}
+
+// Fits an exponential cool-down curve to recent samples and extrapolates it
+pub struct DecayPredictor<const N: usize> {
+    origin: Option<Instant>,
+    // (seconds since origin, temperature)
+    samples: SampleWindow<(f32, Temperature), N>,
+}
+
+impl<const N: usize> DecayPredictor<N> {
+    pub fn new() -> Self {
+        Self {
+            origin: None,
+            samples: SampleWindow::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.origin = None;
+        self.samples.clear();
+    }
+
+    pub fn push(&mut self, now: Instant, temp: Temperature) {
+        let origin = *self.origin.get_or_insert(now);
+        let seconds = now.saturating_duration_since(origin).as_secs_f32();
+        self.samples.push((seconds, temp));
+    }
+
+    // time from the latest sample until the curve drops to `target`; none past `MAX_HORIZON`
+    pub fn time_to(&self, target: Temperature, ambient: Temperature) -> Option<Prediction> {
+        let target_excess = (target - ambient).celsius();
+        if target_excess <= 0.0 {
+            // the curve only approaches ambient, it never gets there
+            return None;
+        }
+
+        let (latest_seconds, latest_temp) = self.samples.newest()?;
+        let fit = self.fit(ambient)?;
+        let confidence = fit.r_squared.clamp(0.0, 1.0)
+            * (self.samples.len() as f32 / self.samples.capacity() as f32);
+
+        if latest_temp <= target {
+            return Some(Prediction {
+                remaining: Duration::ZERO,
+                confidence,
+            });
+        }
+
+        let crossing = (fit.ln_excess - libm::logf(target_excess)) / fit.decay_per_second;
+        let remaining = Duration::try_from_secs_f32((crossing - latest_seconds).max(0.0)).ok()?;
+        if remaining > MAX_HORIZON {
+            return None;
+        }
+
+        Some(Prediction {
+            remaining,
+            confidence,
+        })
+    }
+
+    // least-squares line through ln(T - ambient) over time
+    fn fit(&self, ambient: Temperature) -> Option<DecayFit> {
+        if self.samples.len() < MIN_FIT_SAMPLES {
+            return None;
+        }
+
+        let points = || {
+            self.samples.iter().filter_map(|(t, temp)| {
+                let excess = (temp - ambient).celsius();
+                (excess > MIN_EXCESS_CELSIUS).then(|| (t, libm::logf(excess)))
+            })
+        };
+
+        let n = points().count();
+        if n < MIN_FIT_SAMPLES {
+            return None;
+        }
+        let n = n as f32;
+        let mean_t = points().map(|(t, _)| t).sum::<f32>() / n;
+        let mean_y = points().map(|(_, y)| y).sum::<f32>() / n;
+
+        let mut s_tt = 0.0;
+        let mut s_ty = 0.0;
+        let mut s_yy = 0.0;
+        for (t, y) in points() {
+            s_tt += (t - mean_t) * (t - mean_t);
+            s_ty += (t - mean_t) * (y - mean_y);
+            s_yy += (y - mean_y) * (y - mean_y);
+        }
+        if s_tt <= 0.0 {
+            return None;
+        }
+
+        let slope = s_ty / s_tt;
+        if slope >= 0.0 {
+            // not cooling, nothing to extrapolate
+            return None;
+        }
+
+        let r_squared = if s_yy > 0.0 {
+            (s_ty * s_ty) / (s_tt * s_yy)
+        } else {
+            1.0
+        };
+
+        Some(DecayFit {
+            ln_excess: mean_y - slope * mean_t,
+            decay_per_second: -slope,
+            r_squared,
+        })
+    }
+}
+
+impl<const N: usize> Default for DecayPredictor<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Noise;
+
+    const AMBIENT_F: f32 = 70.0;
+    const START_F: f32 = 450.0;
+    const TAU_SECONDS: f32 = 3600.0;
+
+    fn decay_at(seconds: f32) -> f32 {
+        AMBIENT_F + (START_F - AMBIENT_F) * (-seconds / TAU_SECONDS).exp()
+    }
+
+    // seconds from the start of the decay until it reaches `target_f`
+    fn true_crossing(target_f: f32) -> f32 {
+        TAU_SECONDS * ((START_F - AMBIENT_F) / (target_f - AMBIENT_F)).ln()
+    }
+
+    fn predictor_with(minutes: u64, noise_f: f32) -> DecayPredictor<DECAY_WINDOW> {
+        let mut predictor = DecayPredictor::new();
+        let mut noise = Noise(42);
+        let start = Instant::now();
+
+        for t in (0..=minutes * 60).step_by(10) {
+            let temp = decay_at(t as f32) + noise.next(noise_f);
+            predictor.push(
+                start + Duration::from_secs(t),
+                Temperature::from_fahrenheit(temp),
+            );
+        }
+
+        predictor
+    }
+
+    fn ambient() -> Temperature {
+        Temperature::from_fahrenheit(AMBIENT_F)
+    }
+
+    fn assert_close(prediction: Prediction, expected_seconds: f32, tolerance: f32) {
+        let actual = prediction.remaining.as_secs_f32();
+        let error = (actual - expected_seconds).abs() / expected_seconds;
+        assert!(
+            error < tolerance,
+            "expected ~{}s, predicted {}s ({:.1}% off)",
+            expected_seconds,
+            actual,
+            error * 100.0
+        );
+    }
+
+    #[test]
+    fn needs_a_few_samples() {
+        let predictor = predictor_with(0, 0.0);
+
+        assert_eq!(
+            predictor.time_to(Temperature::from_fahrenheit(300.0), ambient()),
+            None
+        );
+    }
+
+    #[test]
+    fn predicts_clean_decay() {
+        let predictor = predictor_with(15, 0.0);
+
+        let prediction = predictor
+            .time_to(Temperature::from_fahrenheit(300.0), ambient())
+            .unwrap();
+
+        assert_close(prediction, true_crossing(300.0) - 900.0, 0.01);
+        assert!(prediction.confidence > 0.95);
+    }
+
+    #[test]
+    fn predicts_noisy_decay() {
+        let predictor = predictor_with(15, 4.0);
+
+        let reload = predictor
+            .time_to(Temperature::from_fahrenheit(300.0), ambient())
+            .unwrap();
+        let idle = predictor
+            .time_to(Temperature::from_fahrenheit(150.0), ambient())
+            .unwrap();
+
+        assert_close(reload, true_crossing(300.0) - 900.0, 0.15);
+        assert_close(idle, true_crossing(150.0) - 900.0, 0.15);
+    }
+
+    #[test]
+    fn noise_lowers_confidence() {
+        let clean = predictor_with(15, 0.0)
+            .time_to(Temperature::from_fahrenheit(300.0), ambient())
+            .unwrap();
+        let noisy = predictor_with(15, 15.0)
+            .time_to(Temperature::from_fahrenheit(300.0), ambient())
+            .unwrap();
+
+        assert!(noisy.confidence < clean.confidence);
+    }
+
+    #[test]
+    fn short_history_lowers_confidence() {
+        let short = predictor_with(3, 0.0)
+            .time_to(Temperature::from_fahrenheit(300.0), ambient())
+            .unwrap();
+        let full = predictor_with(15, 0.0)
+            .time_to(Temperature::from_fahrenheit(300.0), ambient())
+            .unwrap();
+
+        assert!(short.confidence < full.confidence);
+    }
+
+    #[test]
+    fn already_below_target_is_zero() {
+        let predictor = predictor_with(15, 0.0);
+
+        let prediction = predictor
+            .time_to(Temperature::from_fahrenheit(420.0), ambient())
+            .unwrap();
+
+        assert_eq!(prediction.remaining, Duration::ZERO);
+    }
+
+    #[test]
+    fn never_reaches_ambient() {
+        let predictor = predictor_with(15, 0.0);
+
+        assert_eq!(predictor.time_to(ambient(), ambient()), None);
+    }
+
+    #[test]
+    fn barely_cooling_has_no_prediction() {
+        let mut predictor: DecayPredictor<DECAY_WINDOW> = DecayPredictor::new();
+        let start = Instant::now();
+        for t in (0..=900).step_by(10) {
+            predictor.push(
+                start + Duration::from_secs(t),
+                Temperature::from_fahrenheit(450.0 - t as f32 / 10_000.0),
+            );
+        }
+
+        assert_eq!(
+            predictor.time_to(Temperature::from_fahrenheit(150.0), ambient()),
+            None
+        );
+    }
+
+    #[test]
+    fn rising_curve_has_no_prediction() {
+        let mut predictor: DecayPredictor<DECAY_WINDOW> = DecayPredictor::new();
+        let start = Instant::now();
+        for t in (0..=600).step_by(10) {
+            predictor.push(
+                start + Duration::from_secs(t),
+                Temperature::from_fahrenheit(300.0 + t as f32 / 10.0),
+            );
+        }
+
+        assert_eq!(
+            predictor.time_to(Temperature::from_fahrenheit(150.0), ambient()),
+            None
+        );
+    }
+}
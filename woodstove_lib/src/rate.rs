@@ -0,0 +1,494 @@
+use core::time::Duration;
+
+use crate::{
+    temperature::{RateOfChange, Temperature},
+    time::Instant,
+    window::SampleWindow,
+};
+
+// Estimates how fast the temperature is changing from a stream of timestamped readings
+pub trait RateEstimator {
+    fn update(&mut self, now: Instant, temp: Temperature);
+
+    // None until there is enough history for an estimate
+    fn rate(&self) -> Option<RateOfChange>;
+
+    // forget all history, e.g. after a sensor outage
+    fn reset(&mut self);
+
+    // start from a known reading and rate instead of from scratch
+    fn seed(&mut self, at: Instant, temp: Temperature, rate: Option<RateOfChange>);
+}
+
+// Exponential moving average of the point-to-point slopes. `alpha` is the weight of a slope
+// measured over `nominal_interval`; slopes over shorter intervals count for less and longer
+// ones for more, so irregular sampling doesn't skew the average.
+pub struct EmaEstimator {
+    alpha: f32,
+    nominal_interval: Duration,
+    last: Option<(Instant, Temperature)>,
+    rate: Option<RateOfChange>,
+}
+
+impl EmaEstimator {
+    pub fn new(alpha: f32) -> Self {
+        // the main loop samples every 10 seconds
+        EmaEstimator::with_interval(alpha, Duration::from_secs(10))
+    }
+
+    pub fn with_interval(alpha: f32, nominal_interval: Duration) -> Self {
+        Self {
+            alpha,
+            nominal_interval,
+            last: None,
+            rate: None,
+        }
+    }
+
+    // weight of a slope measured over `elapsed`
+    fn weight(&self, elapsed: Duration) -> f32 {
+        let intervals = elapsed.as_secs_f32() / self.nominal_interval.as_secs_f32();
+        1.0 - libm::powf(1.0 - self.alpha, intervals)
+    }
+}
+
+impl Default for EmaEstimator {
+    fn default() -> Self {
+        Self::new(0.3)
+    }
+}
+
+impl RateEstimator for EmaEstimator {
+    fn update(&mut self, now: Instant, temp: Temperature) {
+        if let Some((last_time, last_temp)) = self.last {
+            let elapsed = now.saturating_duration_since(last_time);
+            if elapsed.is_zero() {
+                // no time has passed, so there's no slope to measure
+                return;
+            }
+
+            let instantaneous =
+                RateOfChange::new_per_second(temp - last_temp, elapsed.as_secs_f32());
+            self.rate = match self.rate {
+                // second update
+                None => Some(instantaneous),
+                // calculate exponential moving average
+                Some(last_roc) => {
+                    let weight = self.weight(elapsed);
+                    Some(weight * instantaneous + (1.0 - weight) * last_roc)
+                }
+            };
+        }
+        self.last = Some((now, temp));
+    }
+
+    fn rate(&self) -> Option<RateOfChange> {
+        self.rate
+    }
+
+    fn reset(&mut self) {
+        self.last = None;
+        self.rate = None;
+    }
+
+    fn seed(&mut self, at: Instant, temp: Temperature, rate: Option<RateOfChange>) {
+        self.last = Some((at, temp));
+        self.rate = rate;
+    }
+}
+
+// Least-squares slope over the last N readings. The default of 12 covers two minutes at
+// the 10 second loop period.
+pub struct RegressionEstimator<const N: usize = 12> {
+    samples: SampleWindow<(Instant, Temperature), N>,
+    // reported until the window holds enough readings of its own
+    seeded_rate: Option<RateOfChange>,
+}
+
+impl<const N: usize> RegressionEstimator<N> {
+    pub fn new() -> Self {
+        Self {
+            samples: SampleWindow::new(),
+            seeded_rate: None,
+        }
+    }
+}
+
+impl<const N: usize> Default for RegressionEstimator<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> RateEstimator for RegressionEstimator<N> {
+    fn update(&mut self, now: Instant, temp: Temperature) {
+        self.samples.push((now, temp));
+    }
+
+    fn rate(&self) -> Option<RateOfChange> {
+        let Some((newest, _)) = self.samples.newest() else {
+            return self.seeded_rate;
+        };
+        if self.samples.len() < 2 {
+            return self.seeded_rate;
+        }
+
+        // times relative to the newest reading keep the sums small
+        let points = || {
+            self.samples
+                .iter()
+                .map(move |(t, temp)| (-(newest - t).as_secs_f32(), temp.celsius()))
+        };
+        let n = self.samples.len() as f32;
+        let mean_t = points().map(|(t, _)| t).sum::<f32>() / n;
+        let mean_c = points().map(|(_, c)| c).sum::<f32>() / n;
+
+        let mut s_tt = 0.0;
+        let mut s_tc = 0.0;
+        for (t, c) in points() {
+            s_tt += (t - mean_t) * (t - mean_t);
+            s_tc += (t - mean_t) * (c - mean_c);
+        }
+        if s_tt <= 0.0 {
+            return self.seeded_rate;
+        }
+
+        Some(RateOfChange::from_celsius_per_second(s_tc / s_tt))
+    }
+
+    fn reset(&mut self) {
+        self.samples.clear();
+        self.seeded_rate = None;
+    }
+
+    fn seed(&mut self, at: Instant, temp: Temperature, rate: Option<RateOfChange>) {
+        self.samples.clear();
+        self.samples.push((at, temp));
+        self.seeded_rate = rate;
+    }
+}
+
+// Constant-velocity Kalman filter tracking temperature and slope together
+pub struct KalmanEstimator {
+    // variance of a single reading, °C²
+    measurement_variance: f32,
+    // how quickly the slope is allowed to wander, (°C/s)² per second
+    slope_variance_rate: f32,
+
+    last_time: Option<Instant>,
+    // [temperature °C, slope °C/s]
+    state: [f32; 2],
+    covariance: [[f32; 2]; 2],
+    // readings folded in since the last reset
+    updates: u32,
+}
+
+impl KalmanEstimator {
+    pub fn new(measurement_std_celsius: f32, slope_drift_celsius_per_second: f32) -> Self {
+        Self {
+            measurement_variance: measurement_std_celsius * measurement_std_celsius,
+            slope_variance_rate: slope_drift_celsius_per_second * slope_drift_celsius_per_second,
+            last_time: None,
+            state: [0.0, 0.0],
+            covariance: [[0.0, 0.0], [0.0, 0.0]],
+            updates: 0,
+        }
+    }
+
+    // a slope we know nothing about yet, in (°C/s)²
+    const UNKNOWN_SLOPE_VARIANCE: f32 = 1.0;
+
+    fn predict(&mut self, dt: f32) {
+        let [t, s] = self.state;
+        self.state = [t + s * dt, s];
+
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let q = self.slope_variance_rate;
+        self.covariance = [
+            [
+                p00 + dt * (p10 + p01) + dt * dt * p11 + q * dt * dt * dt / 3.0,
+                p01 + dt * p11 + q * dt * dt / 2.0,
+            ],
+            [p10 + dt * p11 + q * dt * dt / 2.0, p11 + q * dt],
+        ];
+    }
+
+    fn correct(&mut self, measured: f32) {
+        let [[p00, p01], [p10, p11]] = self.covariance;
+        let innovation = measured - self.state[0];
+        let innovation_variance = p00 + self.measurement_variance;
+        let gain = [p00 / innovation_variance, p10 / innovation_variance];
+
+        self.state[0] += gain[0] * innovation;
+        self.state[1] += gain[1] * innovation;
+        self.covariance = [
+            [(1.0 - gain[0]) * p00, (1.0 - gain[0]) * p01],
+            [p10 - gain[1] * p00, p11 - gain[1] * p01],
+        ];
+    }
+}
+
+impl Default for KalmanEstimator {
+    fn default() -> Self {
+        // ~1.5°C of thermocouple jitter, slope drifting by ~1°C/min each minute
+        Self::new(1.5, 0.002)
+    }
+}
+
+impl RateEstimator for KalmanEstimator {
+    fn update(&mut self, now: Instant, temp: Temperature) {
+        match self.last_time {
+            None => {
+                self.state = [temp.celsius(), 0.0];
+                self.covariance = [
+                    [self.measurement_variance, 0.0],
+                    [0.0, Self::UNKNOWN_SLOPE_VARIANCE],
+                ];
+            }
+            Some(last) => {
+                self.predict((now - last).as_secs_f32());
+                self.correct(temp.celsius());
+            }
+        }
+        self.last_time = Some(now);
+        self.updates += 1;
+    }
+
+    fn rate(&self) -> Option<RateOfChange> {
+        // one reading says nothing about the slope
+        (self.updates >= 2).then(|| RateOfChange::from_celsius_per_second(self.state[1]))
+    }
+
+    fn reset(&mut self) {
+        self.last_time = None;
+        self.updates = 0;
+    }
+
+    fn seed(&mut self, at: Instant, temp: Temperature, rate: Option<RateOfChange>) {
+        self.state = [
+            temp.celsius(),
+            rate.map(|r| r.celsius_per_second()).unwrap_or(0.0),
+        ];
+        let slope_variance = match rate {
+            Some(_) => self.slope_variance_rate * 60.0,
+            None => Self::UNKNOWN_SLOPE_VARIANCE,
+        };
+        self.covariance = [[self.measurement_variance, 0.0], [0.0, slope_variance]];
+        self.last_time = Some(at);
+        self.updates = if rate.is_some() { 2 } else { 1 };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::Noise;
+    use core::time::Duration;
+
+    const SAMPLE_PERIOD: u64 = 10;
+
+    // flat at 200°C for 10 minutes, then climbing at 6°C/min for 30 minutes
+    fn true_slope_per_minute(seconds: u64) -> f32 {
+        if seconds < 600 { 0.0 } else { 6.0 }
+    }
+
+    fn true_temp(seconds: u64) -> f32 {
+        200.0 + 6.0 * (seconds.saturating_sub(600)) as f32 / 60.0
+    }
+
+    struct Run {
+        // rate estimate in °C/min at every sample, None before the first estimate
+        estimates: Vec<(u64, Option<f32>)>,
+    }
+
+    fn run(estimator: &mut impl RateEstimator, noise_celsius: f32) -> Run {
+        let start = Instant::now();
+        let mut noise = Noise(7);
+        let mut estimates = Vec::new();
+
+        for t in (0..=2400).step_by(SAMPLE_PERIOD as usize) {
+            let temp = Temperature::from_celsius(true_temp(t) + noise.next(noise_celsius));
+            estimator.update(start + Duration::from_secs(t), temp);
+            let estimate = estimator.rate().map(|r| r.celsius_per_second() * 60.0);
+            estimates.push((t, estimate));
+        }
+
+        Run { estimates }
+    }
+
+    impl Run {
+        // seconds after the slope change until the estimate stays within 10% of the new slope
+        fn lag(&self) -> u64 {
+            let settled = self
+                .estimates
+                .iter()
+                .rposition(|(t, e)| *t >= 600 && e.is_none_or(|e| (e - 6.0).abs() > 0.6))
+                .unwrap();
+            self.estimates[settled + 1].0 - 600
+        }
+
+        // RMS error once settled on the steady climb
+        fn noise(&self) -> f32 {
+            let errors: Vec<f32> = self
+                .estimates
+                .iter()
+                .filter(|(t, _)| *t >= 1200)
+                .map(|(t, e)| e.unwrap() - true_slope_per_minute(*t))
+                .collect();
+            (errors.iter().map(|e| e * e).sum::<f32>() / errors.len() as f32).sqrt()
+        }
+    }
+
+    #[test]
+    fn all_estimators_track_a_clean_ramp() {
+        let ema = run(&mut EmaEstimator::default(), 0.0);
+        let regression = run(&mut RegressionEstimator::<12>::new(), 0.0);
+        let kalman = run(&mut KalmanEstimator::default(), 0.0);
+
+        for (name, run) in [("ema", ema), ("regression", regression), ("kalman", kalman)] {
+            assert!(run.noise() < 0.1, "{} error {}", name, run.noise());
+            assert!(run.lag() < 300, "{} lag {}s", name, run.lag());
+        }
+    }
+
+    #[test]
+    fn regression_and_kalman_are_quieter_than_ema() {
+        let ema = run(&mut EmaEstimator::default(), 2.0);
+        let regression = run(&mut RegressionEstimator::<12>::new(), 2.0);
+        let kalman = run(&mut KalmanEstimator::default(), 2.0);
+
+        assert!(
+            regression.noise() < ema.noise() / 2.0,
+            "regression {} vs ema {}",
+            regression.noise(),
+            ema.noise()
+        );
+        assert!(
+            kalman.noise() < ema.noise() / 2.0,
+            "kalman {} vs ema {}",
+            kalman.noise(),
+            ema.noise()
+        );
+    }
+
+    #[test]
+    fn ema_reacts_fastest_to_a_slope_change() {
+        let ema = run(&mut EmaEstimator::default(), 0.0);
+        let regression = run(&mut RegressionEstimator::<12>::new(), 0.0);
+        let kalman = run(&mut KalmanEstimator::default(), 0.0);
+
+        assert!(
+            ema.lag() < regression.lag(),
+            "ema {}s vs regression {}s",
+            ema.lag(),
+            regression.lag()
+        );
+        assert!(
+            ema.lag() < kalman.lag(),
+            "ema {}s vs kalman {}s",
+            ema.lag(),
+            kalman.lag()
+        );
+    }
+
+    #[test]
+    fn longer_regression_window_trades_lag_for_noise() {
+        let short = run(&mut RegressionEstimator::<6>::new(), 2.0);
+        let long = run(&mut RegressionEstimator::<24>::new(), 2.0);
+        assert!(long.noise() < short.noise());
+
+        let short = run(&mut RegressionEstimator::<6>::new(), 0.0);
+        let long = run(&mut RegressionEstimator::<24>::new(), 0.0);
+        assert!(long.lag() > short.lag());
+    }
+
+    #[test]
+    fn ema_weights_by_elapsed_time() {
+        let start = Instant::now();
+        let flat = Temperature::from_celsius(200.0);
+        let mut regular = EmaEstimator::new(0.3);
+        let mut irregular = EmaEstimator::new(0.3);
+
+        // both settle on a flat line, then see the same 5°C jump
+        regular.update(start, flat);
+        regular.update(start + Duration::from_secs(10), flat);
+        regular.update(
+            start + Duration::from_secs(20),
+            Temperature::from_celsius(205.0),
+        );
+
+        // the irregular one gets the jump after two 5 second intervals instead of one 10
+        irregular.update(start, flat);
+        irregular.update(start + Duration::from_secs(10), flat);
+        irregular.update(
+            start + Duration::from_secs(15),
+            Temperature::from_celsius(202.5),
+        );
+        irregular.update(
+            start + Duration::from_secs(20),
+            Temperature::from_celsius(205.0),
+        );
+
+        // the same half-degree-per-second slope over the same time gives a similar estimate
+        let regular = regular.rate().unwrap().celsius_per_second();
+        let irregular = irregular.rate().unwrap().celsius_per_second();
+        assert!((regular - 0.15).abs() < 0.001, "regular {}", regular);
+        assert!(
+            (irregular - regular).abs() < 0.02,
+            "irregular {} vs regular {}",
+            irregular,
+            regular
+        );
+    }
+
+    #[test]
+    fn ema_ignores_repeated_timestamp() {
+        let now = Instant::now();
+        let mut ema = EmaEstimator::default();
+        ema.update(now, Temperature::from_celsius(100.0));
+        ema.update(now, Temperature::from_celsius(150.0));
+
+        assert_eq!(ema.rate(), None);
+    }
+
+    #[test]
+    fn estimators_need_two_readings() {
+        let now = Instant::now();
+        let temp = Temperature::from_celsius(100.0);
+
+        let mut ema = EmaEstimator::default();
+        let mut regression = RegressionEstimator::<12>::new();
+        let mut kalman = KalmanEstimator::default();
+        ema.update(now, temp);
+        regression.update(now, temp);
+        kalman.update(now, temp);
+
+        assert_eq!(ema.rate(), None);
+        assert_eq!(regression.rate(), None);
+        assert_eq!(kalman.rate(), None);
+    }
+
+    #[test]
+    fn reset_forgets_history() {
+        let mut kalman = KalmanEstimator::default();
+        run(&mut kalman, 0.0);
+
+        kalman.reset();
+
+        assert_eq!(kalman.rate(), None);
+    }
+
+    #[test]
+    fn seeded_rate_is_reported_until_replaced() {
+        let now = Instant::now();
+        let rate = RateOfChange::from_celsius_per_second(0.1);
+
+        let mut regression = RegressionEstimator::<12>::new();
+        regression.seed(now, Temperature::from_celsius(300.0), Some(rate));
+        assert_eq!(regression.rate(), Some(rate));
+
+        let mut kalman = KalmanEstimator::default();
+        kalman.seed(now, Temperature::from_celsius(300.0), Some(rate));
+        assert_eq!(kalman.rate(), Some(rate));
+    }
+}
@@ -0,0 +1,729 @@
+use core::{fmt::Display, str::FromStr, time::Duration};
+
+use crate::{
+    state_machine::{BurnState, StoveConfig},
+    temperature::Temperature,
+};
+
+pub const MAX_RULES: usize = 32;
+pub const MAX_CONDITIONS: usize = 4;
+
+// The shipped rule set, which `RuleSet::default()` parses
+pub const DEFAULT_RULES: &str = include_str!("../rules/default.rules");
+
+// How fast the temperature is moving, relative to the rate thresholds in `StoveConfig`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateClass {
+    RisingFast,
+    Falling,
+    FallingFast,
+    Stable,
+}
+
+impl Display for RateClass {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RateClass::RisingFast => write!(f, "rising_fast"),
+            RateClass::Falling => write!(f, "falling"),
+            RateClass::FallingFast => write!(f, "falling_fast"),
+            RateClass::Stable => write!(f, "stable"),
+        }
+    }
+}
+
+// A temperature a condition compares against: one of the config thresholds, so rules follow
+// the config when it's tuned, or a fixed value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Threshold {
+    Idle,
+    Active,
+    ActiveExit,
+    Overheat,
+    OverheatExit,
+    Reload,
+    CoalsOut,
+    Fixed(Temperature),
+}
+
+impl Threshold {
+    pub fn resolve(&self, config: &StoveConfig) -> Temperature {
+        match self {
+            Threshold::Idle => config.idle_threshold,
+            Threshold::Active => config.active_threshold,
+            Threshold::ActiveExit => config.active_exit_threshold,
+            Threshold::Overheat => config.overheat_threshold,
+            Threshold::OverheatExit => config.overheat_exit_threshold,
+            Threshold::Reload => config.reload_threshold,
+            Threshold::CoalsOut => config.coals_out_threshold,
+            Threshold::Fixed(temp) => *temp,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Condition {
+    Above(Threshold),
+    Below(Threshold),
+    Rate(RateClass),
+    // time since the machine entered the rule's `from` state
+    InStateFor(Duration),
+}
+
+// What a rule is evaluated against on each reading
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuleInput {
+    pub temp: Temperature,
+    pub rate: RateClass,
+    pub time_in_state: Duration,
+}
+
+impl Condition {
+    pub fn holds(&self, input: &RuleInput, config: &StoveConfig) -> bool {
+        match self {
+            Condition::Above(threshold) => input.temp > threshold.resolve(config),
+            Condition::Below(threshold) => input.temp < threshold.resolve(config),
+            Condition::Rate(class) => input.rate == *class,
+            Condition::InStateFor(duration) => input.time_in_state >= *duration,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateMatch {
+    Any,
+    Only(BurnState),
+}
+
+impl StateMatch {
+    fn matches(&self, state: BurnState) -> bool {
+        match self {
+            StateMatch::Any => true,
+            StateMatch::Only(s) => *s == state,
+        }
+    }
+
+    // whether every state this matches, `other` matches too
+    fn covers(&self, other: &StateMatch) -> bool {
+        match (self, other) {
+            (StateMatch::Any, _) => true,
+            (StateMatch::Only(a), StateMatch::Only(b)) => a == b,
+            (StateMatch::Only(_), StateMatch::Any) => false,
+        }
+    }
+}
+
+// A transition to `to` from any matching state, once all of its conditions hold for its dwell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rule {
+    pub from: StateMatch,
+    pub to: BurnState,
+    conditions: [Option<Condition>; MAX_CONDITIONS],
+    pub dwell: Dwell,
+}
+
+impl Rule {
+    pub fn new(from: StateMatch, to: BurnState) -> Self {
+        Rule {
+            from,
+            to,
+            conditions: [None; MAX_CONDITIONS],
+            dwell: Dwell::IMMEDIATE,
+        }
+    }
+
+    // adds a condition; panics past MAX_CONDITIONS, as that's a mistake in the code building it
+    pub fn when(mut self, condition: Condition) -> Self {
+        let slot = self
+            .conditions
+            .iter_mut()
+            .find(|c| c.is_none())
+            .expect("too many conditions for one rule");
+        *slot = Some(condition);
+        self
+    }
+
+    pub fn with_dwell(mut self, dwell: Dwell) -> Self {
+        self.dwell = dwell;
+        self
+    }
+
+    pub fn conditions(&self) -> impl Iterator<Item = Condition> + '_ {
+        self.conditions.iter().flatten().copied()
+    }
+
+    pub fn matches(&self, state: BurnState, input: &RuleInput, config: &StoveConfig) -> bool {
+        self.from.matches(state) && self.conditions().all(|c| c.holds(input, config))
+    }
+
+    // whenever `other` matches this does too
+    fn shadows(&self, other: &Rule) -> bool {
+        self.from.covers(&other.from)
+            && self
+                .conditions()
+                .all(|c| other.conditions().any(|o| o == c))
+    }
+}
+
+// A transition only happens once its target state has been indicated for at least `hold`
+// and by at least `samples` consecutive readings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dwell {
+    pub hold: Duration,
+    pub samples: u32,
+}
+
+impl Dwell {
+    pub const IMMEDIATE: Dwell = Dwell {
+        hold: Duration::ZERO,
+        samples: 1,
+    };
+
+    pub const fn new(hold: Duration, samples: u32) -> Self {
+        Dwell { hold, samples }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleError {
+    // `line` counts from 1
+    Parse { line: usize, reason: &'static str },
+    TooManyRules,
+    // rule indices count from 0, in evaluation order
+    SelfTransition { rule: usize },
+    EntersSensorFault { rule: usize },
+    // `later` can never fire because `earlier` matches whenever it would
+    Conflict { earlier: usize, later: usize },
+    Unreachable(BurnState),
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RuleError::Parse { line, reason } => write!(f, "line {}: {}", line, reason),
+            RuleError::TooManyRules => write!(f, "more than {} rules", MAX_RULES),
+            RuleError::SelfTransition { rule } => {
+                write!(
+                    f,
+                    "rule {} transitions to the state it starts from",
+                    rule + 1
+                )
+            }
+            RuleError::EntersSensorFault { rule } => write!(
+                f,
+                "rule {} enters sensor_fault, which only failed reads can do",
+                rule + 1
+            ),
+            RuleError::Conflict { earlier, later } => write!(
+                f,
+                "rule {} can never fire, rule {} always matches first",
+                later + 1,
+                earlier + 1
+            ),
+            RuleError::Unreachable(state) => write!(f, "no rules lead to {}", state),
+        }
+    }
+}
+
+// Transition rules, evaluated in order; the first one that matches picks the next state
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RuleSet {
+    rules: [Option<Rule>; MAX_RULES],
+    len: usize,
+}
+
+impl RuleSet {
+    pub fn new() -> Self {
+        RuleSet {
+            rules: [None; MAX_RULES],
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, rule: Rule) -> Result<(), RuleError> {
+        if self.len == MAX_RULES {
+            return Err(RuleError::TooManyRules);
+        }
+        self.rules[self.len] = Some(rule);
+        self.len += 1;
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Rule> + '_ {
+        self.rules[..self.len].iter().flatten()
+    }
+
+    pub fn first_match(
+        &self,
+        state: BurnState,
+        input: &RuleInput,
+        config: &StoveConfig,
+    ) -> Option<&Rule> {
+        self.iter().find(|rule| rule.matches(state, input, config))
+    }
+
+    // checks for rules that can never fire and states nothing leads to
+    pub fn validate(&self) -> Result<(), RuleError> {
+        for (i, rule) in self.iter().enumerate() {
+            if rule.from == StateMatch::Only(rule.to) {
+                return Err(RuleError::SelfTransition { rule: i });
+            }
+            if rule.to == BurnState::SensorFault {
+                return Err(RuleError::EntersSensorFault { rule: i });
+            }
+            if let Some(earlier) = self.iter().take(i).position(|e| e.shadows(rule)) {
+                return Err(RuleError::Conflict { earlier, later: i });
+            }
+        }
+
+        // walk the transitions out from idle, where the machine starts; sensor_fault is
+        // entered from anywhere by failed reads, so it's always reachable
+        let mut reached = [false; BurnState::ALL.len()];
+        let index = |state: BurnState| BurnState::ALL.iter().position(|s| *s == state).unwrap();
+        reached[index(BurnState::Idle)] = true;
+        reached[index(BurnState::SensorFault)] = true;
+        let mut grew = true;
+        while grew {
+            grew = false;
+            for rule in self.iter() {
+                let from_reached = match rule.from {
+                    StateMatch::Any => true,
+                    StateMatch::Only(state) => reached[index(state)],
+                };
+                if from_reached && !reached[index(rule.to)] {
+                    reached[index(rule.to)] = true;
+                    grew = true;
+                }
+            }
+        }
+
+        match BurnState::ALL.iter().find(|s| !reached[index(**s)]) {
+            Some(state) => Err(RuleError::Unreachable(*state)),
+            None => Ok(()),
+        }
+    }
+}
+
+// the rules in `DEFAULT_RULES`
+impl Default for RuleSet {
+    fn default() -> Self {
+        DEFAULT_RULES.parse().expect("the shipped rules are valid")
+    }
+}
+
+// One rule per line, `#` starts a comment:
+//
+//   <from|any> -> <to> [when <condition> [and <condition>]...] [hold <duration>] [samples <n>]
+//
+// where a condition is `temp > <threshold>`, `temp < <threshold>`, `rate <class>` or
+// `in_state > <duration>`. Thresholds are the config names (idle, active, active_exit, overheat,
+// overheat_exit, reload, coals_out) or a fixed temperature like `450F` or `230C`, and durations are
+// written like `90s`, `5m` or `1h`.
+impl FromStr for RuleSet {
+    type Err = RuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = RuleSet::new();
+        for (i, line) in s.lines().enumerate() {
+            let line_no = i + 1;
+            let text = line.split('#').next().unwrap_or("").trim();
+            if text.is_empty() {
+                continue;
+            }
+            let rule = parse_rule(text).map_err(|reason| RuleError::Parse {
+                line: line_no,
+                reason,
+            })?;
+            set.push(rule)?;
+        }
+        set.validate()?;
+        Ok(set)
+    }
+}
+
+fn parse_rule(text: &str) -> Result<Rule, &'static str> {
+    let mut tokens = text.split_whitespace().peekable();
+
+    let from = match tokens.next() {
+        Some("any") => StateMatch::Any,
+        Some(name) => StateMatch::Only(parse_state(name)?),
+        None => return Err("missing source state"),
+    };
+    if tokens.next() != Some("->") {
+        return Err("expected `->` after the source state");
+    }
+    let to = parse_state(tokens.next().ok_or("missing target state")?)?;
+    let mut rule = Rule::new(from, to);
+    let mut count = 0;
+
+    if tokens.peek() == Some(&"when") {
+        tokens.next();
+        loop {
+            if count == MAX_CONDITIONS {
+                return Err("too many conditions");
+            }
+            rule = rule.when(parse_condition(&mut tokens)?);
+            count += 1;
+            if tokens.peek() != Some(&"and") {
+                break;
+            }
+            tokens.next();
+        }
+    }
+
+    while let Some(keyword) = tokens.next() {
+        let value = tokens.next().ok_or("missing value")?;
+        match keyword {
+            "hold" => rule.dwell.hold = parse_duration(value)?,
+            "samples" => {
+                rule.dwell.samples = value
+                    .parse()
+                    .ok()
+                    .filter(|n| *n > 0)
+                    .ok_or("samples must be a whole number above zero")?
+            }
+            _ => return Err("expected `and`, `hold` or `samples`"),
+        }
+    }
+
+    Ok(rule)
+}
+
+fn parse_condition<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+) -> Result<Condition, &'static str> {
+    let subject = tokens.next().ok_or("missing condition")?;
+    match subject {
+        "temp" => {
+            let op = tokens.next().ok_or("missing `>` or `<`")?;
+            let threshold = parse_threshold(tokens.next().ok_or("missing threshold")?)?;
+            match op {
+                ">" => Ok(Condition::Above(threshold)),
+                "<" => Ok(Condition::Below(threshold)),
+                _ => Err("temperatures compare with `>` or `<`"),
+            }
+        }
+        "rate" => match tokens.next() {
+            Some("rising_fast") => Ok(Condition::Rate(RateClass::RisingFast)),
+            Some("falling") => Ok(Condition::Rate(RateClass::Falling)),
+            Some("falling_fast") => Ok(Condition::Rate(RateClass::FallingFast)),
+            Some("stable") => Ok(Condition::Rate(RateClass::Stable)),
+            _ => Err("rate must be rising_fast, falling, falling_fast or stable"),
+        },
+        "in_state" => {
+            if tokens.next() != Some(">") {
+                return Err("in_state compares with `>`");
+            }
+            Ok(Condition::InStateFor(parse_duration(
+                tokens.next().ok_or("missing duration")?,
+            )?))
+        }
+        _ => Err("conditions start with temp, rate or in_state"),
+    }
+}
+
+fn parse_state(name: &str) -> Result<BurnState, &'static str> {
+    name.parse().map_err(|_| "unknown state")
+}
+
+fn parse_threshold(text: &str) -> Result<Threshold, &'static str> {
+    match text {
+        "idle" => Ok(Threshold::Idle),
+        "active" => Ok(Threshold::Active),
+        "active_exit" => Ok(Threshold::ActiveExit),
+        "overheat" => Ok(Threshold::Overheat),
+        "overheat_exit" => Ok(Threshold::OverheatExit),
+        "reload" => Ok(Threshold::Reload),
+        "coals_out" => Ok(Threshold::CoalsOut),
+        _ => parse_temperature(text)
+            .map(Threshold::Fixed)
+            .map_err(|_| "unknown threshold"),
+    }
+}
+
+// `450F` or `230C`
+pub(crate) fn parse_temperature(text: &str) -> Result<Temperature, &'static str> {
+    const ERR: &str = "temperatures look like 450F or 230C";
+    // NaN and infinity parse as numbers, but aren't temperatures
+    let value = |v: &str| v.parse::<f32>().ok().filter(|v| v.is_finite()).ok_or(ERR);
+    if let Some(f) = text.strip_suffix('F') {
+        Ok(Temperature::from_fahrenheit(value(f)?))
+    } else if let Some(c) = text.strip_suffix('C') {
+        Ok(Temperature::from_celsius(value(c)?))
+    } else {
+        Err(ERR)
+    }
+}
+
+// `90s`, `5m` or `1h`
+pub(crate) fn parse_duration(text: &str) -> Result<Duration, &'static str> {
+    const ERR: &str = "durations look like 90s, 5m or 1h";
+    // seconds, if they fit
+    let value = |v: &str, unit: u64| {
+        v.parse::<u64>()
+            .ok()
+            .and_then(|v| v.checked_mul(unit))
+            .ok_or(ERR)
+    };
+    if let Some(s) = text.strip_suffix('s') {
+        Ok(Duration::from_secs(value(s, 1)?))
+    } else if let Some(m) = text.strip_suffix('m') {
+        Ok(Duration::from_secs(value(m, 60)?))
+    } else if let Some(h) = text.strip_suffix('h') {
+        Ok(Duration::from_secs(value(h, 60 * 60)?))
+    } else {
+        Err(ERR)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINIMAL: &str = "
+        any -> overheat when temp > overheat
+        idle -> startup when temp > idle
+        startup -> active_burn when temp > active
+        active_burn -> low_burn when rate stable
+        active_burn -> refueling when rate falling_fast
+        active_burn -> coaling when temp < active_exit
+        coaling -> cooling when temp < coals_out
+        coaling -> idle when temp < idle
+    ";
+
+    fn input(temp_f: f32, rate: RateClass) -> RuleInput {
+        RuleInput {
+            temp: Temperature::from_fahrenheit(temp_f),
+            rate,
+            time_in_state: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn shipped_file_parses() {
+        assert_eq!(DEFAULT_RULES.parse::<RuleSet>(), Ok(RuleSet::default()));
+        assert!(!RuleSet::default().is_empty());
+    }
+
+    #[test]
+    fn default_rules_are_valid() {
+        assert_eq!(RuleSet::default().validate(), Ok(()));
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = RuleSet::default();
+        let config = StoveConfig::default();
+
+        let rule = rules
+            .first_match(
+                BurnState::Coaling,
+                &input(800.0, RateClass::RisingFast),
+                &config,
+            )
+            .unwrap();
+
+        assert_eq!(rule.to, BurnState::Overheat);
+    }
+
+    #[test]
+    fn no_match_leaves_state_alone() {
+        let rules = RuleSet::default();
+        let config = StoveConfig::default();
+
+        let rule = rules.first_match(
+            BurnState::ActiveBurn,
+            &input(500.0, RateClass::Stable),
+            &config,
+        );
+
+        assert_eq!(rule, None);
+    }
+
+    #[test]
+    fn parses_fixed_temperatures_durations_and_dwell() {
+        let rules: RuleSet = format!(
+            "{}\ncoaling -> idle when temp < 60C and in_state > 2h hold 90s samples 5",
+            MINIMAL
+        )
+        .parse()
+        .unwrap();
+
+        let rule = rules.iter().last().unwrap();
+        assert_eq!(rule.from, StateMatch::Only(BurnState::Coaling));
+        assert_eq!(rule.to, BurnState::Idle);
+        assert_eq!(
+            rule.conditions().collect::<Vec<_>>(),
+            vec![
+                Condition::Below(Threshold::Fixed(Temperature::from_celsius(60.0))),
+                Condition::InStateFor(Duration::from_secs(2 * 60 * 60)),
+            ]
+        );
+        assert_eq!(rule.dwell, Dwell::new(Duration::from_secs(90), 5));
+    }
+
+    #[test]
+    fn in_state_condition_waits() {
+        let rules: RuleSet = MINIMAL
+            .replace(
+                "coaling -> idle when temp < idle",
+                "coaling -> idle when in_state > 30m",
+            )
+            .parse()
+            .unwrap();
+        let config = StoveConfig::default();
+        let mut reading = input(300.0, RateClass::Stable);
+
+        reading.time_in_state = Duration::from_secs(29 * 60);
+        assert_eq!(
+            rules.first_match(BurnState::Coaling, &reading, &config),
+            None
+        );
+
+        reading.time_in_state = Duration::from_secs(30 * 60);
+        assert!(
+            rules
+                .first_match(BurnState::Coaling, &reading, &config)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn rejects_overflowing_durations() {
+        assert_eq!(
+            parse_duration("18446744073709551615s"),
+            Ok(Duration::from_secs(u64::MAX))
+        );
+        for text in ["9999999999999999h", "307445734561825861m"] {
+            assert_eq!(
+                parse_duration(text),
+                Err("durations look like 90s, 5m or 1h"),
+                "{}",
+                text
+            );
+        }
+
+        let text = MINIMAL.replace(
+            "coaling -> idle when temp < idle",
+            "coaling -> idle when in_state > 9999999999999999h",
+        );
+        assert!(matches!(
+            text.parse::<RuleSet>(),
+            Err(RuleError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn reports_parse_errors_by_line() {
+        let text = "# comment\n\nidle -> startup when temp >> idle\n";
+
+        let err = text.parse::<RuleSet>().unwrap_err();
+
+        assert!(matches!(err, RuleError::Parse { line: 3, .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn rejects_unknown_state() {
+        let err = "idle -> smouldering".parse::<RuleSet>().unwrap_err();
+
+        assert_eq!(
+            err,
+            RuleError::Parse {
+                line: 1,
+                reason: "unknown state"
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_unreachable_state() {
+        let text = MINIMAL.replace("any -> overheat when temp > overheat", "");
+
+        let err = text.parse::<RuleSet>().unwrap_err();
+
+        assert_eq!(err, RuleError::Unreachable(BurnState::Overheat));
+    }
+
+    #[test]
+    fn rejects_shadowed_rule() {
+        // the second rule needs everything the first does, so the first always wins
+        let text = format!(
+            "{}\ncoaling -> startup when temp < idle and rate rising_fast",
+            MINIMAL
+        );
+
+        let err = text.parse::<RuleSet>().unwrap_err();
+
+        assert_eq!(
+            err,
+            RuleError::Conflict {
+                earlier: 7,
+                later: 8
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_conflicting_targets() {
+        let text = format!("{}\ncoaling -> startup when temp < idle", MINIMAL);
+
+        let err = text.parse::<RuleSet>().unwrap_err();
+
+        assert!(matches!(err, RuleError::Conflict { .. }));
+    }
+
+    #[test]
+    fn any_rule_shadows_later_specific_rule() {
+        let text = format!("{}\ncoaling -> overheat when temp > overheat", MINIMAL);
+
+        let err = text.parse::<RuleSet>().unwrap_err();
+
+        assert_eq!(
+            err,
+            RuleError::Conflict {
+                earlier: 0,
+                later: 8
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_self_transition() {
+        let text = format!("{}\ncoaling -> coaling when rate stable", MINIMAL);
+
+        let err = text.parse::<RuleSet>().unwrap_err();
+
+        assert_eq!(err, RuleError::SelfTransition { rule: 8 });
+    }
+
+    #[test]
+    fn rejects_entering_sensor_fault() {
+        let text = format!("{}\nidle -> sensor_fault when temp < 0F", MINIMAL);
+
+        let err = text.parse::<RuleSet>().unwrap_err();
+
+        assert_eq!(err, RuleError::EntersSensorFault { rule: 8 });
+    }
+
+    #[test]
+    fn rejects_too_many_conditions() {
+        let text = "idle -> startup when rate stable and rate stable and rate stable and rate stable and rate stable";
+
+        let err = text.parse::<RuleSet>().unwrap_err();
+
+        assert_eq!(
+            err,
+            RuleError::Parse {
+                line: 1,
+                reason: "too many conditions"
+            }
+        );
+    }
+}
@@ -0,0 +1,214 @@
+use core::time::Duration;
+
+use crate::{
+    temperature::{RateOfChange, Temperature, TemperatureDelta},
+    time::Instant,
+};
+
+// A chimney fire or runaway burn shows as a fast rise that keeps going at temperatures
+// a normal fire only reaches slowly, so both have to hold for a while before it counts.
+pub struct RunawayConfig {
+    pub rate_threshold: RateOfChange,
+    pub absolute_threshold: Temperature,
+    pub sustain: Duration,
+}
+
+impl Default for RunawayConfig {
+    fn default() -> Self {
+        Self {
+            rate_threshold: RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(25.0),
+                1.0,
+            ),
+            absolute_threshold: Temperature::from_fahrenheit(650.0),
+            sustain: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunawayEvent {
+    Detected,
+    Cleared,
+}
+
+pub struct RunawayDetector {
+    config: RunawayConfig,
+    rising_since: Option<Instant>,
+    detected: bool,
+}
+
+impl RunawayDetector {
+    pub fn new() -> Self {
+        RunawayDetector::with_config(RunawayConfig::default())
+    }
+
+    pub fn with_config(config: RunawayConfig) -> Self {
+        RunawayDetector {
+            config,
+            rising_since: None,
+            detected: false,
+        }
+    }
+
+    pub fn is_detected(&self) -> bool {
+        self.detected
+    }
+
+    pub fn update(
+        &mut self,
+        now: Instant,
+        temp: Temperature,
+        rate: Option<RateOfChange>,
+    ) -> Option<RunawayEvent> {
+        let hot = temp > self.config.absolute_threshold;
+
+        if self.detected {
+            // a runaway plateaus once it is fully going, so only cooling off clears it
+            if !hot {
+                self.detected = false;
+                self.rising_since = None;
+                return Some(RunawayEvent::Cleared);
+            }
+            return None;
+        }
+
+        let rising = rate.is_some_and(|r| r > self.config.rate_threshold);
+        if !(hot && rising) {
+            self.rising_since = None;
+            return None;
+        }
+
+        let since = *self.rising_since.get_or_insert(now);
+        if now.saturating_duration_since(since) >= self.config.sustain {
+            self.detected = true;
+            return Some(RunawayEvent::Detected);
+        }
+        None
+    }
+}
+
+impl Default for RunawayDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        StoveStateMachine,
+        test_support::{SAMPLE_PERIOD, minute, trace},
+    };
+
+    // replays a trace of °F readings every 10 seconds through the state machine's rate estimate,
+    // returning the sample index of every detector event
+    fn replay(trace: &[f32]) -> Vec<(usize, RunawayEvent)> {
+        let mut sm = StoveStateMachine::new();
+        let mut detector = RunawayDetector::new();
+        let start = Instant::now();
+        let mut events = Vec::new();
+
+        for (i, temp_f) in trace.iter().enumerate() {
+            let now = start + Duration::from_secs(i as u64 * SAMPLE_PERIOD);
+            let temp = Temperature::from_fahrenheit(*temp_f);
+            sm.update_at(temp, now);
+            if let Some(event) = detector.update(now, temp, sm.rate_of_change()) {
+                events.push((i, event));
+            }
+        }
+
+        events
+    }
+
+    #[test]
+    fn cold_start_ramp_is_not_a_runaway() {
+        // kindling catching hard: fast rise, but well below the runaway temperature
+        let ramp = trace(&[(0.0, 70.0), (5.0, 300.0), (15.0, 550.0), (60.0, 600.0)]);
+
+        assert_eq!(replay(&ramp), vec![]);
+    }
+
+    #[test]
+    fn hot_reload_climbing_steadily_is_not_a_runaway() {
+        // a big reload on a hot bed of coals, climbing past the threshold at a normal pace
+        let reload = trace(&[(0.0, 450.0), (10.0, 550.0), (30.0, 690.0), (60.0, 650.0)]);
+
+        assert_eq!(replay(&reload), vec![]);
+    }
+
+    #[test]
+    fn door_open_flare_is_not_a_runaway() {
+        // the door is opened on a hot fire, flaring it up for half a minute
+        let mut flare = trace(&[(0.0, 640.0), (30.0, 640.0)]);
+        flare[60] = 700.0;
+        flare[61] = 760.0;
+        flare[62] = 720.0;
+
+        assert_eq!(replay(&flare), vec![]);
+    }
+
+    #[test]
+    fn chimney_fire_is_detected() {
+        let fire = trace(&[(0.0, 550.0), (5.0, 580.0), (15.0, 1100.0), (25.0, 1150.0)]);
+
+        let events = replay(&fire);
+
+        assert_eq!(events.len(), 1, "events: {:?}", events);
+        let (index, event) = events[0];
+        assert_eq!(event, RunawayEvent::Detected);
+        // crosses 650°F a little after 6 minutes, detection follows a minute or so later
+        let minute = minute(index);
+        assert!((6.5..9.0).contains(&minute), "detected at {} min", minute);
+    }
+
+    #[test]
+    fn stays_detected_while_hot_and_clears_when_cooled() {
+        let fire = trace(&[
+            (0.0, 550.0),
+            (5.0, 580.0),
+            (15.0, 1100.0),
+            (25.0, 1150.0),
+            (60.0, 500.0),
+        ]);
+
+        let events = replay(&fire);
+
+        let kinds: Vec<RunawayEvent> = events.iter().map(|(_, e)| *e).collect();
+        assert_eq!(kinds, vec![RunawayEvent::Detected, RunawayEvent::Cleared]);
+    }
+
+    #[test]
+    fn interrupted_rise_restarts_the_sustain_timer() {
+        let mut detector = RunawayDetector::new();
+        let start = Instant::now();
+        let hot = Temperature::from_fahrenheit(800.0);
+        let fast = Some(RateOfChange::new_per_minute(
+            TemperatureDelta::from_fahrenheit(40.0),
+            1.0,
+        ));
+        let flat = Some(RateOfChange::new_per_minute(
+            TemperatureDelta::from_fahrenheit(0.0),
+            1.0,
+        ));
+
+        assert_eq!(detector.update(start, hot, fast), None);
+        assert_eq!(
+            detector.update(start + Duration::from_secs(50), hot, flat),
+            None
+        );
+        assert_eq!(
+            detector.update(start + Duration::from_secs(60), hot, fast),
+            None
+        );
+        assert_eq!(
+            detector.update(start + Duration::from_secs(110), hot, fast),
+            None
+        );
+        assert_eq!(
+            detector.update(start + Duration::from_secs(120), hot, fast),
+            Some(RunawayEvent::Detected)
+        );
+    }
+}
@@ -1,8 +1,6 @@
-use crate::temperature::Temperature;
-
 #[cfg(feature = "max31855")]
 pub mod max31855_sensor {
-    use super::*;
+    use crate::temperature::Temperature;
     use embedded_hal::digital::OutputPin;
     use max31855::{Max31855, Unit};
 
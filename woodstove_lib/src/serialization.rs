@@ -0,0 +1,339 @@
+// Serde support for the core types, behind the `serde` feature.
+//
+// Quantities go over the wire as a single-entry map whose key names the unit, like
+// `{"c": 21.5}`, so a reader never has to guess whether a bare number is Celsius or
+// Fahrenheit. They are always written in the unit the type holds internally, and either
+// unit is accepted back. Burn states and temperature units are their `Display` names.
+
+use core::{fmt, marker::PhantomData, time::Duration};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, DeserializeSeed, MapAccess, Visitor},
+    ser::SerializeMap,
+};
+
+use crate::{
+    state_machine::{BurnState, StoveConfig},
+    temperature::{RateOfChange, Temperature, TemperatureDelta, TemperatureUnit},
+};
+
+const TEMPERATURE_UNITS: &[&str] = &["c", "f"];
+const RATE_UNITS: &[&str] = &["c_per_s", "f_per_min"];
+const DURATION_UNITS: &[&str] = &["s"];
+
+fn serialize_tagged<S: Serializer, T: Serialize>(
+    serializer: S,
+    unit: &'static str,
+    value: T,
+) -> Result<S::Ok, S::Error> {
+    let mut map = serializer.serialize_map(Some(1))?;
+    map.serialize_entry(unit, &value)?;
+    map.end()
+}
+
+// reads a `{"<unit>": value}` map, returning which of `units` was used and the value
+fn deserialize_tagged<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+    deserializer: D,
+    units: &'static [&'static str],
+) -> Result<(usize, T), D::Error> {
+    deserializer.deserialize_map(TaggedVisitor {
+        units,
+        value: PhantomData,
+    })
+}
+
+struct TaggedVisitor<T> {
+    units: &'static [&'static str],
+    value: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for TaggedVisitor<T> {
+    type Value = (usize, T);
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a map with one of the units {:?} as its only key",
+            self.units
+        )
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let unit = map
+            .next_key_seed(UnitKey(self.units))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let value = map.next_value()?;
+        if map.next_key_seed(UnitKey(self.units))?.is_some() {
+            return Err(de::Error::custom("expected a single unit"));
+        }
+        Ok((unit, value))
+    }
+}
+
+// the key of a tagged map, as an index into the allowed units
+struct UnitKey(&'static [&'static str]);
+
+impl<'de> DeserializeSeed<'de> for UnitKey {
+    type Value = usize;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+        deserializer.deserialize_identifier(self)
+    }
+}
+
+impl Visitor<'_> for UnitKey {
+    type Value = usize;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "one of the units {:?}", self.0)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<usize, E> {
+        self.0
+            .iter()
+            .position(|unit| *unit == v)
+            .ok_or_else(|| E::unknown_field(v, self.0))
+    }
+}
+
+impl Serialize for Temperature {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_tagged(serializer, "c", self.celsius())
+    }
+}
+
+impl<'de> Deserialize<'de> for Temperature {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match deserialize_tagged(deserializer, TEMPERATURE_UNITS)? {
+            (0, c) => Temperature::from_celsius(c),
+            (_, f) => Temperature::from_fahrenheit(f),
+        })
+    }
+}
+
+impl Serialize for TemperatureDelta {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_tagged(serializer, "c", self.celsius())
+    }
+}
+
+impl<'de> Deserialize<'de> for TemperatureDelta {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match deserialize_tagged(deserializer, TEMPERATURE_UNITS)? {
+            (0, c) => TemperatureDelta::from_celsius(c),
+            (_, f) => TemperatureDelta::from_fahrenheit(f),
+        })
+    }
+}
+
+impl Serialize for RateOfChange {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_tagged(serializer, "c_per_s", self.celsius_per_second())
+    }
+}
+
+impl<'de> Deserialize<'de> for RateOfChange {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match deserialize_tagged(deserializer, RATE_UNITS)? {
+            (0, c) => RateOfChange::from_celsius_per_second(c),
+            (_, f) => RateOfChange::new_per_minute(TemperatureDelta::from_fahrenheit(f), 1.0),
+        })
+    }
+}
+
+impl Serialize for BurnState {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for BurnState {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(BurnStateVisitor)
+    }
+}
+
+struct BurnStateVisitor;
+
+impl Visitor<'_> for BurnStateVisitor {
+    type Value = BurnState;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a burn state name")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<BurnState, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+}
+
+impl Serialize for TemperatureUnit {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for TemperatureUnit {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(TemperatureUnitVisitor)
+    }
+}
+
+struct TemperatureUnitVisitor;
+
+impl Visitor<'_> for TemperatureUnitVisitor {
+    type Value = TemperatureUnit;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "celsius, fahrenheit or kelvin")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<TemperatureUnit, E> {
+        v.parse()
+            .map_err(|_| E::invalid_value(de::Unexpected::Str(v), &self))
+    }
+}
+
+// the derived (de)serialization, with the config checked the same as one parsed from text
+impl Serialize for StoveConfig {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        StoveConfig::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for StoveConfig {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let config = StoveConfig::deserialize(deserializer)?;
+        config.validate().map_err(de::Error::custom)?;
+        Ok(config)
+    }
+}
+
+// `Duration` fields as `{"s": 90.0}`, for `#[serde(with = ...)]`
+pub(crate) mod seconds {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_tagged(serializer, "s", duration.as_secs_f64())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let (_, secs): (usize, f64) = deserialize_tagged(deserializer, DURATION_UNITS)?;
+        Duration::try_from_secs_f64(secs).map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temperature_is_tagged_with_its_unit() {
+        let json = serde_json::to_string(&Temperature::from_celsius(100.0)).unwrap();
+
+        assert_eq!(json, r#"{"c":100.0}"#);
+    }
+
+    #[test]
+    fn temperature_round_trips() {
+        let temp = Temperature::from_fahrenheit(451.0);
+
+        let json = serde_json::to_string(&temp).unwrap();
+
+        assert_eq!(serde_json::from_str::<Temperature>(&json).unwrap(), temp);
+    }
+
+    #[test]
+    fn reads_fahrenheit() {
+        let temp: Temperature = serde_json::from_str(r#"{"f": 212}"#).unwrap();
+
+        assert!((temp.celsius() - 100.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn rejects_bare_numbers_and_unknown_units() {
+        assert!(serde_json::from_str::<Temperature>("100.0").is_err());
+        assert!(serde_json::from_str::<Temperature>(r#"{"k": 373.15}"#).is_err());
+        assert!(serde_json::from_str::<Temperature>("{}").is_err());
+        assert!(serde_json::from_str::<Temperature>(r#"{"c": 1, "f": 2}"#).is_err());
+    }
+
+    #[test]
+    fn delta_reads_fahrenheit_without_offset() {
+        let delta: TemperatureDelta = serde_json::from_str(r#"{"f": 18}"#).unwrap();
+
+        assert!((delta.celsius() - 10.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn rate_round_trips() {
+        let rate = RateOfChange::new_per_minute(TemperatureDelta::from_fahrenheit(-4.5), 1.0);
+
+        let json = serde_json::to_string(&rate).unwrap();
+
+        assert_eq!(serde_json::from_str::<RateOfChange>(&json).unwrap(), rate);
+    }
+
+    #[test]
+    fn reads_rate_per_minute() {
+        let rate: RateOfChange = serde_json::from_str(r#"{"f_per_min": 5}"#).unwrap();
+
+        assert!((rate.fahrenheit_per_minute() - 5.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn burn_states_are_their_names() {
+        for state in BurnState::ALL {
+            let json = serde_json::to_string(&state).unwrap();
+
+            assert_eq!(json, format!("\"{}\"", state));
+            assert_eq!(serde_json::from_str::<BurnState>(&json).unwrap(), state);
+        }
+        assert!(serde_json::from_str::<BurnState>(r#""smoldering""#).is_err());
+    }
+
+    #[test]
+    fn config_round_trips() {
+        let config = StoveConfig {
+            idle_threshold: Temperature::from_fahrenheit(135.0),
+            max_sample_interval: Duration::from_secs(90),
+            display_unit: TemperatureUnit::Celsius,
+            ..StoveConfig::default()
+        };
+
+        let json = serde_json::to_string(&config).unwrap();
+        let parsed: StoveConfig = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.idle_threshold, config.idle_threshold);
+        assert_eq!(parsed.falling_rate, config.falling_rate);
+        assert_eq!(parsed.max_sample_interval, Duration::from_secs(90));
+        assert_eq!(parsed.sensor_fault_after, config.sensor_fault_after);
+        assert_eq!(parsed.display_unit, TemperatureUnit::Celsius);
+        assert!(json.contains(r#""display_unit":"celsius""#), "{}", json);
+    }
+
+    #[test]
+    fn config_is_validated() {
+        let err = serde_json::from_str::<StoveConfig>(
+            r#"{"idle_threshold": {"f": 500}, "active_threshold": {"f": 400}}"#,
+        )
+        .unwrap_err();
+
+        assert!(err.to_string().contains("has to be below"), "{}", err);
+    }
+
+    #[test]
+    fn config_missing_fields_keep_defaults() {
+        let parsed: StoveConfig =
+            serde_json::from_str(r#"{"idle_threshold": {"f": 120}}"#).unwrap();
+
+        assert!((parsed.idle_threshold.fahrenheit() - 120.0).abs() < 0.01);
+        assert_eq!(
+            parsed.active_threshold,
+            StoveConfig::default().active_threshold
+        );
+        assert_eq!(parsed.rules.len(), StoveConfig::default().rules.len());
+    }
+}
@@ -0,0 +1,206 @@
+use core::{fmt::Display, time::Duration};
+
+use crate::{
+    state_machine::BurnState,
+    temperature::{RateOfChange, Temperature},
+};
+
+const VERSION: u8 = 1;
+
+// Snapshots older than this say little about what the stove is doing now
+pub const MAX_AGE: Duration = Duration::from_secs(10 * 60);
+
+// What the state machine needs to pick up where it left off after a reboot
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Snapshot {
+    pub state: BurnState,
+    pub time_in_state: Duration,
+    pub temperature: Temperature,
+    pub rate: Option<RateOfChange>,
+    // when it was taken, on a clock that keeps running across resets
+    pub saved_at: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotError {
+    WrongLength,
+    UnknownVersion(u8),
+    BadChecksum,
+    UnknownState(u8),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            SnapshotError::WrongLength => write!(f, "wrong length"),
+            SnapshotError::UnknownVersion(v) => write!(f, "unknown version {}", v),
+            SnapshotError::BadChecksum => write!(f, "bad checksum"),
+            SnapshotError::UnknownState(i) => write!(f, "unknown state {}", i),
+        }
+    }
+}
+
+impl Snapshot {
+    // version, state, time in state (s, u32), temperature (°C, f32), rate (°C/s, f32, NaN for
+    // none), saved at (ms, u64), CRC-8; all little endian
+    pub const ENCODED_LEN: usize = 23;
+
+    pub fn encode(&self) -> [u8; Self::ENCODED_LEN] {
+        let state = BurnState::ALL
+            .iter()
+            .position(|s| *s == self.state)
+            .unwrap_or(0) as u8;
+        let seconds = self.time_in_state.as_secs().min(u32::MAX as u64) as u32;
+        let rate = self.rate.map_or(f32::NAN, |r| r.celsius_per_second());
+        let saved_at = self.saved_at.as_millis().min(u64::MAX as u128) as u64;
+
+        let mut bytes = [0; Self::ENCODED_LEN];
+        bytes[0] = VERSION;
+        bytes[1] = state;
+        bytes[2..6].copy_from_slice(&seconds.to_le_bytes());
+        bytes[6..10].copy_from_slice(&self.temperature.celsius().to_le_bytes());
+        bytes[10..14].copy_from_slice(&rate.to_le_bytes());
+        bytes[14..22].copy_from_slice(&saved_at.to_le_bytes());
+        bytes[22] = crc8(&bytes[..22]);
+        bytes
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        let bytes: &[u8; Self::ENCODED_LEN] =
+            bytes.try_into().map_err(|_| SnapshotError::WrongLength)?;
+        if bytes[0] != VERSION {
+            return Err(SnapshotError::UnknownVersion(bytes[0]));
+        }
+        if crc8(&bytes[..22]) != bytes[22] {
+            return Err(SnapshotError::BadChecksum);
+        }
+
+        let state = *BurnState::ALL
+            .get(bytes[1] as usize)
+            .ok_or(SnapshotError::UnknownState(bytes[1]))?;
+        let seconds = u32::from_le_bytes([bytes[2], bytes[3], bytes[4], bytes[5]]);
+        let celsius = f32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+        let rate = f32::from_le_bytes([bytes[10], bytes[11], bytes[12], bytes[13]]);
+        let mut saved_at = [0; 8];
+        saved_at.copy_from_slice(&bytes[14..22]);
+
+        Ok(Snapshot {
+            state,
+            time_in_state: Duration::from_secs(seconds as u64),
+            temperature: Temperature::from_celsius(celsius),
+            rate: (!rate.is_nan()).then(|| RateOfChange::from_celsius_per_second(rate)),
+            saved_at: Duration::from_millis(u64::from_le_bytes(saved_at)),
+        })
+    }
+
+    // how long ago it was taken, if that's no more than `max_age`. A clock reading before
+    // `saved_at` means the clock restarted, so there's no telling how old it is.
+    pub fn fresh_age(&self, clock: Duration, max_age: Duration) -> Option<Duration> {
+        clock
+            .checked_sub(self.saved_at)
+            .filter(|age| *age <= max_age)
+    }
+}
+
+// CRC-8, polynomial 0x07
+pub(crate) fn crc8(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| {
+            if crc & 0x80 != 0 {
+                (crc << 1) ^ 0x07
+            } else {
+                crc << 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temperature::TemperatureDelta;
+
+    fn burning() -> Snapshot {
+        Snapshot {
+            state: BurnState::ActiveBurn,
+            time_in_state: Duration::from_secs(47 * 60),
+            temperature: Temperature::from_fahrenheit(520.0),
+            rate: Some(RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(1.5),
+                1.0,
+            )),
+            saved_at: Duration::from_millis(3_600_123),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let snapshot = burning();
+
+        assert_eq!(Snapshot::decode(&snapshot.encode()), Ok(snapshot));
+    }
+
+    #[test]
+    fn round_trips_without_a_rate() {
+        for state in BurnState::ALL {
+            let snapshot = Snapshot {
+                state,
+                rate: None,
+                ..burning()
+            };
+
+            assert_eq!(Snapshot::decode(&snapshot.encode()), Ok(snapshot));
+        }
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let mut bytes = burning().encode();
+        bytes[7] ^= 0x10;
+
+        assert_eq!(Snapshot::decode(&bytes), Err(SnapshotError::BadChecksum));
+    }
+
+    #[test]
+    fn rejects_wrong_length_and_version() {
+        let bytes = burning().encode();
+        assert_eq!(
+            Snapshot::decode(&bytes[..22]),
+            Err(SnapshotError::WrongLength)
+        );
+
+        let mut bytes = bytes;
+        bytes[0] = 2;
+        assert_eq!(
+            Snapshot::decode(&bytes),
+            Err(SnapshotError::UnknownVersion(2))
+        );
+    }
+
+    #[test]
+    fn recent_snapshot_is_fresh() {
+        let snapshot = burning();
+
+        assert_eq!(
+            snapshot.fresh_age(snapshot.saved_at + Duration::from_secs(40), MAX_AGE),
+            Some(Duration::from_secs(40))
+        );
+    }
+
+    #[test]
+    fn old_snapshot_is_stale() {
+        let snapshot = burning();
+
+        assert_eq!(
+            snapshot.fresh_age(snapshot.saved_at + Duration::from_secs(11 * 60), MAX_AGE),
+            None
+        );
+    }
+
+    #[test]
+    fn restarted_clock_is_stale() {
+        let snapshot = burning();
+
+        assert_eq!(snapshot.fresh_age(Duration::from_secs(5), MAX_AGE), None);
+    }
+}
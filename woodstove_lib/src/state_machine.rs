@@ -1,24 +1,58 @@
-use std::{
-    fmt::Display,
-    time::{Duration, Instant},
-};
+use core::{fmt::Display, str::FromStr, time::Duration};
 
 use crate::{
     Temperature,
-    temperature::{RateOfChange, TemperatureDelta},
+    prediction::{DECAY_WINDOW, DecayPredictor, Prediction},
+    rate::{EmaEstimator, RateEstimator},
+    rules::{Dwell, RateClass, RuleInput, RuleSet},
+    snapshot::Snapshot,
+    temperature::{RateOfChange, TemperatureDelta, TemperatureUnit},
+    time::Instant,
 };
 
+#[derive(Debug, Clone)]
+// `remote = "Self"` leaves the trait impls to `serialization`, which validates what it reads
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(default, remote = "Self")
+)]
 pub struct StoveConfig {
     pub idle_threshold: Temperature,
     pub active_threshold: Temperature,
     pub active_exit_threshold: Temperature,
     pub overheat_threshold: Temperature,
     pub overheat_exit_threshold: Temperature,
+    pub reload_threshold: Temperature,
+    // Below this a cooling stove has nothing left burning in it
+    pub coals_out_threshold: Temperature,
+    // Room temperature a dying fire cools toward
+    pub ambient: Temperature,
+
+    // Consecutive failed reads before the state is no longer trusted
+    pub sensor_fault_after: u32,
+
+    // Readings closer together than this are dropped, as the rate over them is all noise
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::seconds"))]
+    pub min_sample_interval: Duration,
+    // After a longer gap the rate estimate starts over
+    #[cfg_attr(feature = "serde", serde(with = "crate::serialization::seconds"))]
+    pub max_sample_interval: Duration,
+
+    // Transitions between states, in the order they are tried; they have their own file format
+    // (see `rules`), so serde leaves them out and they come back as the defaults
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub rules: RuleSet,
 
     // Rate thresholds
     pub rising_fast_rate: RateOfChange,
     pub falling_rate: RateOfChange,
+    // Faster than a fire dies down; the door is open, usually for a reload
+    pub falling_fast_rate: RateOfChange,
     pub stable_rate: RateOfChange,
+
+    // What temperatures are shown in on the plain topics, the log and the display
+    pub display_unit: TemperatureUnit,
 }
 
 impl Default for StoveConfig {
@@ -29,6 +63,13 @@ impl Default for StoveConfig {
             active_exit_threshold: Temperature::from_fahrenheit(350.0),
             overheat_threshold: Temperature::from_fahrenheit(700.0),
             overheat_exit_threshold: Temperature::from_fahrenheit(600.0),
+            reload_threshold: Temperature::from_fahrenheit(300.0),
+            coals_out_threshold: Temperature::from_fahrenheit(200.0),
+            ambient: Temperature::from_fahrenheit(70.0),
+            sensor_fault_after: 3,
+            min_sample_interval: Duration::from_secs(1),
+            max_sample_interval: Duration::from_secs(60),
+            rules: RuleSet::default(),
             rising_fast_rate: RateOfChange::new_per_minute(
                 TemperatureDelta::from_fahrenheit(5.0),
                 1.0,
@@ -37,7 +78,12 @@ impl Default for StoveConfig {
                 TemperatureDelta::from_fahrenheit(-3.0),
                 1.0,
             ),
+            falling_fast_rate: RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(-25.0),
+                1.0,
+            ),
             stable_rate: RateOfChange::new_per_minute(TemperatureDelta::from_fahrenheit(1.2), 1.0),
+            display_unit: TemperatureUnit::Fahrenheit,
         }
     }
 }
@@ -47,37 +93,97 @@ pub enum BurnState {
     Idle,
     Startup,
     ActiveBurn,
+    // damped down to burn low and slow, e.g. overnight
+    LowBurn,
+    // the door is open for a reload
+    Refueling,
     Coaling,
+    // the coals are out and the stove is cooling off
+    Cooling,
     Overheat,
+    // the thermocouple can't be read, so the stove's state is unknown
+    SensorFault,
 }
 
-impl Display for BurnState {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl BurnState {
+    pub const ALL: [BurnState; 9] = [
+        BurnState::Idle,
+        BurnState::Startup,
+        BurnState::ActiveBurn,
+        BurnState::LowBurn,
+        BurnState::Refueling,
+        BurnState::Coaling,
+        BurnState::Cooling,
+        BurnState::Overheat,
+        BurnState::SensorFault,
+    ];
+
+    // the name used in MQTT payloads and rule files
+    pub fn name(&self) -> &'static str {
         match self {
-            BurnState::Idle => write!(f, "idle"),
-            BurnState::Startup => write!(f, "startup"),
-            BurnState::ActiveBurn => write!(f, "active_burn"),
-            BurnState::Coaling => write!(f, "coaling"),
-            BurnState::Overheat => write!(f, "overheat"),
+            BurnState::Idle => "idle",
+            BurnState::Startup => "startup",
+            BurnState::ActiveBurn => "active_burn",
+            BurnState::LowBurn => "low_burn",
+            BurnState::Refueling => "refueling",
+            BurnState::Coaling => "coaling",
+            BurnState::Cooling => "cooling",
+            BurnState::Overheat => "overheat",
+            BurnState::SensorFault => "sensor_fault",
         }
     }
 }
 
+impl Display for BurnState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownBurnState;
+
+impl Display for UnknownBurnState {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "unknown burn state")
+    }
+}
+
+// inverts Display
+impl FromStr for BurnState {
+    type Err = UnknownBurnState;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        BurnState::ALL
+            .iter()
+            .find(|state| state.name() == s)
+            .copied()
+            .ok_or(UnknownBurnState)
+    }
+}
+
+// A state the readings point to that hasn't been held long enough to move to yet
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum TempChangeType {
-    RisingFast,
-    Falling,
-    Stable,
+struct PendingTransition {
+    to: BurnState,
+    since: Instant,
+    samples: u32,
 }
 
-pub struct StoveStateMachine {
+pub struct StoveStateMachine<E: RateEstimator = EmaEstimator> {
     config: StoveConfig,
     state: BurnState,
     state_set_time: Instant,
+    // time already spent in the state before `state_set_time`, carried over from a snapshot
+    carried_time_in_state: Duration,
     last_update_time: Instant,
     last_temp: Option<Temperature>,
-    roc_alpha: f32,
-    rate_of_change: Option<RateOfChange>,
+    estimator: E,
+    decay: DecayPredictor<DECAY_WINDOW>,
+    pending: Option<PendingTransition>,
+    consecutive_faults: u32,
+    // what the stove was doing when the sensor dropped out
+    state_before_fault: BurnState,
 }
 
 impl StoveStateMachine {
@@ -86,183 +192,317 @@ impl StoveStateMachine {
     }
 
     pub fn new_roc(roc_alpha: Option<f32>) -> Self {
-        let now = Instant::now();
+        StoveStateMachine::with_estimator(
+            StoveConfig::default(),
+            EmaEstimator::new(roc_alpha.unwrap_or(0.3)),
+        )
+    }
+}
+
+impl<E: RateEstimator> StoveStateMachine<E> {
+    // starts out idle as of `Instant::ZERO`, i.e. the origin of the caller's clock
+    pub fn with_estimator(config: StoveConfig, estimator: E) -> Self {
         StoveStateMachine {
-            config: StoveConfig::default(),
+            config,
             state: BurnState::Idle, // assume idle initially
-            state_set_time: now,
-            last_update_time: now,
+            state_set_time: Instant::ZERO,
+            carried_time_in_state: Duration::ZERO,
+            last_update_time: Instant::ZERO,
             last_temp: None,
-            roc_alpha: roc_alpha.unwrap_or(0.3),
-            rate_of_change: None,
+            estimator,
+            decay: DecayPredictor::new(),
+            pending: None,
+            consecutive_faults: 0,
+            state_before_fault: BurnState::Idle,
         }
     }
 
+    #[cfg(any(test, feature = "std"))]
     pub fn update(&mut self, current_temp: Temperature) -> bool {
-        // Update rate of change
-        let now = Instant::now();
-        let last = self.last_update_time;
-        self.last_update_time = now;
-        match self.last_temp {
-            None => {
-                // first update from uninitialized
-                self.last_temp = Some(current_temp);
+        self.update_at(current_temp, Instant::now())
+    }
+
+    // update with an explicit sample time, for replaying recorded or synthetic traces
+    pub fn update_at(&mut self, current_temp: Temperature, now: Instant) -> bool {
+        self.consecutive_faults = 0;
+
+        if self.last_temp.is_some() {
+            let elapsed = now.saturating_duration_since(self.last_update_time);
+            if elapsed < self.config.min_sample_interval {
+                return false;
             }
-            Some(last_temp) => {
-                let instantaneous = RateOfChange::new_per_second(
-                    current_temp - last_temp,
-                    (now - last).as_secs_f32(),
-                );
-                match self.rate_of_change {
-                    None => {
-                        // second update
-                        self.rate_of_change = Some(instantaneous);
-                    }
-                    Some(last_roc) => {
-                        // calculate exponential moving average
-                        let new =
-                            self.roc_alpha * instantaneous + (1.0 - self.roc_alpha) * last_roc;
-                        self.rate_of_change = Some(new);
-                    }
-                }
-                self.last_temp = Some(current_temp);
+            if elapsed > self.config.max_sample_interval {
+                // e.g. a blocking reconnect; one slope across the whole stall means little
+                self.estimator.reset();
             }
         }
 
+        // Update rate of change
+        self.last_update_time = now;
+        self.last_temp = Some(current_temp);
+        self.estimator.update(now, current_temp);
+
         // determine new state
         let rate_type = self.classify_rate();
-        let new_state = self.classify_state(rate_type, current_temp);
+        let (candidate, dwell) = self.classify_state(rate_type, current_temp, now);
+
+        let changed = match self.confirm(candidate, dwell, now) {
+            Some(new_state) => self.set_state(new_state, now),
+            None => false,
+        };
+
+        // the cool-down curve is only meaningful while coaling
+        if self.state == BurnState::Coaling {
+            self.decay.push(now, current_temp);
+        }
+
+        changed
+    }
+
+    // called instead of update when the sensor couldn't be read; returns whether the state changed
+    #[cfg(any(test, feature = "std"))]
+    pub fn report_sensor_fault(&mut self) -> bool {
+        self.report_sensor_fault_at(Instant::now())
+    }
+
+    pub fn report_sensor_fault_at(&mut self, now: Instant) -> bool {
+        self.consecutive_faults = self.consecutive_faults.saturating_add(1);
+        if self.state == BurnState::SensorFault
+            || self.consecutive_faults < self.config.sensor_fault_after
+        {
+            return false;
+        }
 
-        if new_state != self.state {
-            self.state_set_time = Instant::now();
-            self.state = new_state;
-            return true;
+        // nothing measured across the outage says anything about the rate now
+        self.state_before_fault = self.state;
+        self.estimator.reset();
+        self.last_temp = None;
+        self.set_state(BurnState::SensorFault, now)
+    }
+
+    // tracks the candidate as a pending transition, returning it once its dwell is met
+    fn confirm(&mut self, candidate: BurnState, dwell: Dwell, now: Instant) -> Option<BurnState> {
+        if candidate == self.state {
+            self.pending = None;
+            return None;
+        }
+
+        let pending = match self.pending {
+            Some(p) if p.to == candidate => PendingTransition {
+                samples: p.samples.saturating_add(1),
+                ..p
+            },
+            _ => PendingTransition {
+                to: candidate,
+                since: now,
+                samples: 1,
+            },
+        };
+
+        if pending.samples >= dwell.samples
+            && now.saturating_duration_since(pending.since) >= dwell.hold
+        {
+            self.pending = None;
+            return Some(candidate);
+        }
+
+        self.pending = Some(pending);
+        None
+    }
+
+    fn set_state(&mut self, new_state: BurnState, now: Instant) -> bool {
+        self.pending = None;
+        if new_state == self.state {
+            return false;
         }
-        return false;
+        self.state_set_time = now;
+        self.carried_time_in_state = Duration::ZERO;
+        self.state = new_state;
+        self.decay.clear();
+        true
     }
 
     pub fn current_state(&self) -> BurnState {
         self.state
     }
 
+    // the state the readings point to, if it's still waiting out its dwell
+    pub fn pending_state(&self) -> Option<BurnState> {
+        self.pending.map(|p| p.to)
+    }
+
+    // the latest reading, or None before the first one and during a sensor fault
+    pub fn last_temperature(&self) -> Option<Temperature> {
+        self.last_temp
+    }
+
+    pub fn rate_of_change(&self) -> Option<RateOfChange> {
+        self.estimator.rate()
+    }
+
+    #[cfg(any(test, feature = "std"))]
     pub fn time_in_state(&self) -> Duration {
-        Instant::now() - self.state_set_time
+        self.time_in_state_at(Instant::now())
+    }
+
+    pub fn time_in_state_at(&self, now: Instant) -> Duration {
+        (now - self.state_set_time) + self.carried_time_in_state
+    }
+
+    // what to persist so a reboot can pick up where this left off; None without a reading, as
+    // after a sensor fault there is nothing worth restoring
+    pub fn snapshot_at(&self, now: Instant, saved_at: Duration) -> Option<Snapshot> {
+        Some(Snapshot {
+            state: self.state,
+            time_in_state: self.time_in_state_at(now),
+            temperature: self.last_temp?,
+            rate: self.estimator.rate(),
+            saved_at,
+        })
+    }
+
+    // picks up from a snapshot taken `age` ago, counting the gap as time spent in its state;
+    // the next reading is judged against the restored temperature and rate as usual
+    pub fn restore(&mut self, snapshot: &Snapshot, now: Instant, age: Duration) {
+        self.state = snapshot.state;
+        self.state_set_time = now;
+        self.carried_time_in_state = snapshot.time_in_state + age;
+        self.last_update_time = now;
+        self.last_temp = Some(snapshot.temperature);
+        self.estimator
+            .seed(now, snapshot.temperature, snapshot.rate);
+        self.pending = None;
+        self.consecutive_faults = 0;
+        self.decay.clear();
     }
 
+    #[cfg(any(test, feature = "std"))]
     pub fn should_reload(&self) -> bool {
+        self.should_reload_at(Instant::now())
+    }
+
+    pub fn should_reload_at(&self, now: Instant) -> bool {
         match self.state {
             BurnState::Coaling => {
                 self.last_temp.unwrap_or(Temperature::from_fahrenheit(0.0))
-                    < Temperature::from_fahrenheit(300.0)
-                    || self.time_in_state() > Duration::from_secs(30 * 60) // 30 mins
+                    < self.config.reload_threshold
+                    || self.time_in_state_at(now) > Duration::from_secs(30 * 60) // 30 mins
             }
             _ => false,
         }
     }
 
+    // estimated time until coaling drops below the reload threshold, from the cool-down so far
+    pub fn predicted_time_to_reload(&self) -> Option<Prediction> {
+        self.predict_coaling(self.config.reload_threshold)
+    }
+
+    // estimated time until coaling drops below the idle threshold, i.e. the fire is out
+    pub fn predicted_time_to_idle(&self) -> Option<Prediction> {
+        self.predict_coaling(self.config.idle_threshold)
+    }
+
+    fn predict_coaling(&self, target: Temperature) -> Option<Prediction> {
+        if self.state != BurnState::Coaling {
+            return None;
+        }
+        self.decay.time_to(target, self.config.ambient)
+    }
+
     // determines if a given rate of change is rising, stable, falling
-    fn classify_rate(&self) -> TempChangeType {
-        match self.rate_of_change {
-            None => TempChangeType::Stable,
+    fn classify_rate(&self) -> RateClass {
+        match self.estimator.rate() {
+            None => RateClass::Stable,
             Some(r) => {
                 if r > self.config.rising_fast_rate {
-                    TempChangeType::RisingFast
+                    RateClass::RisingFast
+                } else if r < self.config.falling_fast_rate {
+                    RateClass::FallingFast
                 } else if r < self.config.falling_rate {
-                    TempChangeType::Falling
+                    RateClass::Falling
                 } else {
-                    TempChangeType::Stable
+                    RateClass::Stable
                 }
             }
         }
     }
 
-    fn classify_state(&self, roc_class: TempChangeType, temp: Temperature) -> BurnState {
-        if temp > self.config.overheat_threshold {
-            return BurnState::Overheat;
-        }
-
-        match self.state {
-            // Idle can transition only to startup or itself
-            BurnState::Idle => {
-                if temp > self.config.idle_threshold && roc_class == TempChangeType::RisingFast {
-                    BurnState::Startup
-                } else {
-                    BurnState::Idle
-                }
-            }
-
-            // Startup can go to active burn (normal),
-            // coaling (going out),
-            // or remain
-            BurnState::Startup => {
-                if temp > self.config.active_threshold {
-                    BurnState::ActiveBurn
-                } else if roc_class == TempChangeType::Falling {
-                    BurnState::Coaling
-                } else {
-                    BurnState::Startup
-                }
-            }
-
-            // ActiveBurn can go to coaling (dying),
-            // or remain activeBurn (overheat handled above)
-            BurnState::ActiveBurn => {
-                if temp < self.config.active_exit_threshold && roc_class == TempChangeType::Falling
-                {
-                    BurnState::Coaling
-                } else {
-                    BurnState::ActiveBurn
-                }
-            }
-
-            // Coaling can become idle (died),
-            // startup (reloaded),
-            // or stay the same
-            BurnState::Coaling => {
-                if temp < self.config.idle_threshold && roc_class == TempChangeType::Stable {
-                    BurnState::Idle
-                } else if roc_class == TempChangeType::RisingFast {
-                    BurnState::Startup
-                } else {
-                    BurnState::Coaling
-                }
+    // the state the rules point to, and how long it has to hold before moving to it
+    fn classify_state(
+        &self,
+        rate: RateClass,
+        temp: Temperature,
+        now: Instant,
+    ) -> (BurnState, Dwell) {
+        let input = RuleInput {
+            temp,
+            rate,
+            time_in_state: self.time_in_state_at(now),
+        };
+
+        match self
+            .config
+            .rules
+            .first_match(self.state, &input, &self.config)
+        {
+            Some(rule) => (rule.to, rule.dwell),
+            // Readings are back, but there's no trend yet,
+            // so go by temperature alone
+            None if self.state == BurnState::SensorFault => {
+                (self.recovery_state(temp), Dwell::IMMEDIATE)
             }
+            None => (self.state, Dwell::IMMEDIATE),
+        }
+    }
 
-            // Overheat can decrease to active,
-            // or remain overheat
-            BurnState::Overheat => {
-                if temp < self.config.overheat_exit_threshold {
-                    // drop to 600f before recovering
-                    BurnState::ActiveBurn
-                } else {
-                    BurnState::Overheat
-                }
+    fn recovery_state(&self, temp: Temperature) -> BurnState {
+        if temp > self.config.active_threshold {
+            BurnState::ActiveBurn
+        } else if temp > self.config.idle_threshold {
+            // a fire is going; keep building it if it was, otherwise assume it's dying down
+            match self.state_before_fault {
+                BurnState::Startup => BurnState::Startup,
+                BurnState::LowBurn => BurnState::LowBurn,
+                _ => BurnState::Coaling,
             }
+        } else {
+            BurnState::Idle
         }
     }
 }
 
+impl Default for StoveStateMachine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 // tests
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::time::Duration;
+    use crate::rules::DEFAULT_RULES;
+    use core::time::Duration;
 
     const FLOAT_TOLERANCE: f32 = 0.1;
 
+    // idle as of a last update `delta` before the returned time
     fn sm_idle(
         sm: &mut StoveStateMachine,
         delta: Duration,
         rate: Option<RateOfChange>,
         last_temp: Option<Temperature>,
-    ) {
-        let now = Instant::now();
-        let before = now.checked_sub(delta);
+    ) -> Instant {
+        let before = Instant::from_millis(1_000);
 
         sm.state = BurnState::Idle;
-        sm.last_update_time = before.unwrap();
+        sm.last_update_time = before;
         sm.last_temp = last_temp;
-        sm.rate_of_change = rate;
+        match last_temp {
+            Some(temp) => sm.estimator.seed(before, temp, rate),
+            None => sm.estimator.reset(),
+        }
+        before + delta
     }
 
     #[test]
@@ -280,15 +520,15 @@ mod tests {
     #[test]
     fn new_state_machine_has_no_rate_of_change() {
         let sm = StoveStateMachine::new();
-        assert_eq!(sm.rate_of_change, None)
+        assert_eq!(sm.rate_of_change(), None)
     }
 
     #[test]
     fn first_update_sets_updated_time() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(&mut sm, Duration::from_secs(2), None, None);
+        let now = sm_idle(&mut sm, Duration::from_secs(2), None, None);
         let original = sm.last_update_time;
-        sm.update(Temperature::from_fahrenheit(20.0));
+        sm.update_at(Temperature::from_fahrenheit(20.0), now);
 
         assert_ne!(sm.last_update_time, original);
     }
@@ -306,41 +546,41 @@ mod tests {
     #[test]
     fn first_update_does_not_set_rate() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(&mut sm, Duration::from_secs(2), None, None);
+        let now = sm_idle(&mut sm, Duration::from_secs(2), None, None);
 
-        sm.update(Temperature::from_fahrenheit(20.0));
+        sm.update_at(Temperature::from_fahrenheit(20.0), now);
 
-        assert!(sm.rate_of_change.is_none());
+        assert!(sm.rate_of_change().is_none());
     }
 
     #[test]
     fn second_update_sets_roc() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(
+        let now = sm_idle(
             &mut sm,
             Duration::from_secs(2),
             None,
             Some(Temperature::from_fahrenheit(0.0)),
         );
 
-        sm.update(Temperature::from_fahrenheit(20.0));
+        sm.update_at(Temperature::from_fahrenheit(20.0), now);
 
-        assert!(sm.rate_of_change.is_some());
+        assert!(sm.rate_of_change().is_some());
     }
 
     #[test]
     fn second_update_sets_correct_roc() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(
+        let now = sm_idle(
             &mut sm,
             Duration::from_secs(2),
             None,
             Some(Temperature::from_fahrenheit(0.0)),
         ); // simulated first update
-        sm.update(Temperature::from_fahrenheit(20.0));
+        sm.update_at(Temperature::from_fahrenheit(20.0), now);
 
         let expected = RateOfChange::new_per_second(TemperatureDelta::from_fahrenheit(20.0), 2.0);
-        let actual = sm.rate_of_change.unwrap();
+        let actual = sm.rate_of_change().unwrap();
         let variance = (expected - actual).fahrenheit_per_second().abs();
         assert!(
             variance < FLOAT_TOLERANCE,
@@ -354,9 +594,10 @@ mod tests {
     #[test]
     fn third_update_updates_roc() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(
+        // over the nominal 10 second interval the new slope is weighted by alpha
+        let now = sm_idle(
             &mut sm,
-            Duration::from_secs(2),
+            Duration::from_secs(10),
             Some(RateOfChange::new_per_second(
                 TemperatureDelta::from_fahrenheit(20.0),
                 2.0,
@@ -364,13 +605,13 @@ mod tests {
             Some(Temperature::from_fahrenheit(0.0)),
         );
 
-        sm.update(Temperature::from_fahrenheit(10.0));
+        sm.update_at(Temperature::from_fahrenheit(10.0), now);
 
         let expected_rate = 0.3
-            * (RateOfChange::new_per_second(TemperatureDelta::from_fahrenheit(10.0), 2.0))
+            * (RateOfChange::new_per_second(TemperatureDelta::from_fahrenheit(10.0), 10.0))
             + 0.7 * RateOfChange::new_per_second(TemperatureDelta::from_fahrenheit(20.0), 2.0);
-        let actual = sm.rate_of_change.unwrap();
-        let variance = (expected_rate - actual).fahrenheit_per_second();
+        let actual = sm.rate_of_change().unwrap();
+        let variance = (expected_rate - actual).fahrenheit_per_second().abs();
 
         assert!(
             variance < FLOAT_TOLERANCE,
@@ -386,7 +627,7 @@ mod tests {
     fn remains_idle_under_active_threshold() {
         let mut sm = StoveStateMachine::new();
         // set idle
-        sm_idle(
+        let now = sm_idle(
             &mut sm,
             Duration::from_secs(2),
             Some(RateOfChange::new_per_minute(
@@ -399,7 +640,7 @@ mod tests {
         let threshold = sm.config.idle_threshold;
         let new_temp = Temperature::from_fahrenheit(threshold.fahrenheit() * 0.9);
 
-        sm.update(new_temp);
+        sm.update_at(new_temp, now);
 
         assert!(
             sm.state == BurnState::Idle,
@@ -412,7 +653,7 @@ mod tests {
     #[test]
     fn transitions_to_startup() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(
+        let now = sm_idle(
             &mut sm,
             Duration::from_secs(2),
             Some(RateOfChange::new_per_minute(
@@ -422,7 +663,7 @@ mod tests {
             Some(Temperature::from_fahrenheit(64.5)),
         );
 
-        sm.update(Temperature::from_fahrenheit(215.0));
+        sm.update_at(Temperature::from_fahrenheit(215.0), now);
 
         assert_eq!(sm.state, BurnState::Startup);
     }
@@ -430,7 +671,7 @@ mod tests {
     #[test]
     fn transitions_to_burn() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(
+        let now = sm_idle(
             &mut sm,
             Duration::from_secs(20),
             Some(RateOfChange::new_per_minute(
@@ -441,7 +682,7 @@ mod tests {
         );
         sm.state = BurnState::Startup;
 
-        sm.update(Temperature::from_fahrenheit(401.0));
+        sm.update_at(Temperature::from_fahrenheit(401.0), now);
 
         assert_eq!(sm.state, BurnState::ActiveBurn);
     }
@@ -449,7 +690,7 @@ mod tests {
     #[test]
     fn transitions_to_overheat() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(
+        let now = sm_idle(
             &mut sm,
             Duration::from_secs(20),
             Some(RateOfChange::new_per_minute(
@@ -460,7 +701,7 @@ mod tests {
         );
         sm.state = BurnState::ActiveBurn;
 
-        sm.update(Temperature::from_fahrenheit(813.45));
+        sm.update_at(Temperature::from_fahrenheit(813.45), now);
 
         assert_eq!(sm.state, BurnState::Overheat);
     }
@@ -468,7 +709,7 @@ mod tests {
     #[test]
     fn transitions_back_to_active_burn_from_overheat() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(
+        let now = sm_idle(
             &mut sm,
             Duration::from_secs(20),
             Some(RateOfChange::new_per_minute(
@@ -479,27 +720,590 @@ mod tests {
         );
         sm.state = BurnState::Overheat;
 
-        sm.update(Temperature::from_fahrenheit(599.0)); // Below 600°F exit threshold
+        sm.update_at(Temperature::from_fahrenheit(599.0), now); // Below 600°F exit threshold
 
         assert_eq!(sm.state, BurnState::ActiveBurn);
     }
 
     #[test]
-    fn transitions_to_coaling_from_active() {
+    fn predicts_nothing_outside_coaling() {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        sm.state = BurnState::ActiveBurn;
+        for t in (0..600).step_by(10) {
+            sm.decay.push(
+                start + Duration::from_secs(t),
+                Temperature::from_fahrenheit(500.0 - t as f32 / 10.0),
+            );
+        }
+
+        assert_eq!(sm.predicted_time_to_reload(), None);
+        assert_eq!(sm.predicted_time_to_idle(), None);
+    }
+
+    #[test]
+    fn predicts_while_coaling() {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        sm.state = BurnState::Coaling;
+        sm.last_temp = Some(Temperature::from_fahrenheit(340.0));
+        for t in (10..600).step_by(10) {
+            let temp = 70.0 + 270.0 * (-(t as f32) / 3600.0).exp();
+            sm.update_at(
+                Temperature::from_fahrenheit(temp),
+                start + Duration::from_secs(t),
+            );
+        }
+
+        let reload = sm.predicted_time_to_reload().unwrap();
+        let idle = sm.predicted_time_to_idle().unwrap();
+        assert!(reload.remaining < idle.remaining);
+    }
+
+    #[test]
+    fn entering_coaling_starts_a_fresh_curve() {
+        let mut sm = StoveStateMachine::new();
+        // left over from an earlier coaling phase
+        let earlier = Instant::now();
+        for t in (0..200).step_by(10) {
+            sm.decay.push(
+                earlier + Duration::from_secs(t),
+                Temperature::from_fahrenheit(340.0 - t as f32 / 10.0),
+            );
+        }
+        let now = sm_idle(
+            &mut sm,
+            Duration::from_secs(20),
+            Some(RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(-5.0),
+                1.0,
+            )),
+            Some(Temperature::from_fahrenheit(352.0)),
+        );
+        sm.state = BurnState::ActiveBurn;
+
+        // dying down; a sudden drop would be the door opening
+        sm.update_at(Temperature::from_fahrenheit(345.0), now);
+
+        assert_eq!(sm.state, BurnState::Coaling);
+        assert_eq!(sm.predicted_time_to_reload(), None);
+    }
+
+    #[test]
+    fn keeps_state_through_a_few_failed_reads() {
+        let mut sm = StoveStateMachine::new();
+        sm.state = BurnState::ActiveBurn;
+
+        assert!(!sm.report_sensor_fault());
+        assert!(!sm.report_sensor_fault());
+
+        assert_eq!(sm.current_state(), BurnState::ActiveBurn);
+    }
+
+    #[test]
+    fn enters_sensor_fault_after_consecutive_failures() {
         let mut sm = StoveStateMachine::new();
-        sm_idle(
+        let now = sm_idle(
             &mut sm,
             Duration::from_secs(20),
             Some(RateOfChange::new_per_minute(
                 TemperatureDelta::from_fahrenheit(6.0),
                 1.0,
             )),
-            Some(Temperature::from_fahrenheit(401.0)),
+            Some(Temperature::from_fahrenheit(500.0)),
+        );
+        sm.state = BurnState::ActiveBurn;
+        sm.state_set_time = now;
+        let now = now + Duration::from_secs(3 * 60 * 60);
+
+        sm.report_sensor_fault_at(now);
+        sm.report_sensor_fault_at(now);
+        let changed = sm.report_sensor_fault_at(now);
+
+        assert!(changed);
+        assert_eq!(sm.current_state(), BurnState::SensorFault);
+        assert!(sm.time_in_state_at(now) < Duration::from_secs(60));
+        assert_eq!(sm.rate_of_change(), None);
+    }
+
+    #[test]
+    fn successful_read_resets_failure_count() {
+        let mut sm = StoveStateMachine::new();
+        sm.report_sensor_fault();
+        sm.report_sensor_fault();
+        sm.update(Temperature::from_fahrenheit(70.0));
+
+        sm.report_sensor_fault();
+
+        assert_eq!(sm.current_state(), BurnState::Idle);
+    }
+
+    #[test]
+    fn further_failures_stay_in_sensor_fault() {
+        let mut sm = StoveStateMachine::new();
+        for _ in 0..3 {
+            sm.report_sensor_fault();
+        }
+
+        assert!(!sm.report_sensor_fault());
+        assert_eq!(sm.current_state(), BurnState::SensorFault);
+    }
+
+    fn faulted_from(state: BurnState) -> StoveStateMachine {
+        let mut sm = StoveStateMachine::new();
+        sm.state = state;
+        for _ in 0..3 {
+            sm.report_sensor_fault();
+        }
+        sm
+    }
+
+    #[test]
+    fn recovers_to_active_burn_when_hot() {
+        let mut sm = faulted_from(BurnState::Coaling);
+
+        assert!(sm.update(Temperature::from_fahrenheit(480.0)));
+
+        assert_eq!(sm.current_state(), BurnState::ActiveBurn);
+    }
+
+    #[test]
+    fn recovers_to_overheat_when_very_hot() {
+        let mut sm = faulted_from(BurnState::ActiveBurn);
+
+        sm.update(Temperature::from_fahrenheit(750.0));
+
+        assert_eq!(sm.current_state(), BurnState::Overheat);
+    }
+
+    #[test]
+    fn recovers_to_idle_when_cold() {
+        let mut sm = faulted_from(BurnState::ActiveBurn);
+
+        sm.update(Temperature::from_fahrenheit(80.0));
+
+        assert_eq!(sm.current_state(), BurnState::Idle);
+    }
+
+    #[test]
+    fn recovers_to_startup_if_it_was_starting() {
+        let mut sm = faulted_from(BurnState::Startup);
+
+        sm.update(Temperature::from_fahrenheit(250.0));
+
+        assert_eq!(sm.current_state(), BurnState::Startup);
+    }
+
+    #[test]
+    fn recovers_to_coaling_when_warm() {
+        let mut sm = faulted_from(BurnState::ActiveBurn);
+
+        sm.update(Temperature::from_fahrenheit(250.0));
+
+        assert_eq!(sm.current_state(), BurnState::Coaling);
+    }
+
+    #[test]
+    fn rate_restarts_after_recovery() {
+        let mut sm = faulted_from(BurnState::ActiveBurn);
+        let start = Instant::now();
+
+        sm.update_at(Temperature::from_fahrenheit(450.0), start);
+        assert_eq!(sm.rate_of_change(), None);
+
+        sm.update_at(
+            Temperature::from_fahrenheit(455.0),
+            start + Duration::from_secs(10),
+        );
+        let rate = sm.rate_of_change().unwrap().fahrenheit_per_minute();
+        assert!((rate - 30.0).abs() < FLOAT_TOLERANCE, "rate {}", rate);
+    }
+
+    #[test]
+    fn drops_readings_too_close_together() {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        sm.update_at(Temperature::from_fahrenheit(200.0), start);
+
+        sm.update_at(
+            Temperature::from_fahrenheit(210.0),
+            start + Duration::from_millis(1),
+        );
+
+        assert_eq!(sm.last_temp, Some(Temperature::from_fahrenheit(200.0)));
+        assert_eq!(sm.rate_of_change(), None);
+    }
+
+    #[test]
+    fn long_gap_restarts_rate() {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        sm.update_at(Temperature::from_fahrenheit(200.0), start);
+        sm.update_at(
+            Temperature::from_fahrenheit(210.0),
+            start + Duration::from_secs(10),
+        );
+        assert!(sm.rate_of_change().is_some());
+
+        // the loop stalled for five minutes
+        sm.update_at(
+            Temperature::from_fahrenheit(400.0),
+            start + Duration::from_secs(310),
+        );
+        assert_eq!(sm.rate_of_change(), None);
+
+        sm.update_at(
+            Temperature::from_fahrenheit(401.0),
+            start + Duration::from_secs(320),
+        );
+        let rate = sm.rate_of_change().unwrap().fahrenheit_per_minute();
+        assert!((rate - 6.0).abs() < FLOAT_TOLERANCE, "rate {}", rate);
+    }
+
+    #[test]
+    fn tracks_ramp_with_jittered_timestamps() {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        // 10 second nominal period, off by up to ±4 seconds
+        let jitter = [3, -4, 1, 0, -2, 4, -1, 2, -3, 0];
+        let mut seconds = 0;
+
+        for i in 0..60 {
+            let at = seconds + jitter[i % jitter.len()];
+            // climbing at 6°F/min
+            let temp = 300.0 + 6.0 * at as f32 / 60.0;
+            sm.update_at(
+                Temperature::from_fahrenheit(temp),
+                start + Duration::from_secs(at as u64 + 10),
+            );
+            seconds += 10;
+        }
+
+        let rate = sm.rate_of_change().unwrap().fahrenheit_per_minute();
+        assert!((rate - 6.0).abs() < FLOAT_TOLERANCE, "rate {}", rate);
+    }
+
+    #[test]
+    fn short_interval_noise_does_not_swing_the_rate() {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        // a steady fire
+        for i in 0..10 {
+            sm.update_at(
+                Temperature::from_fahrenheit(300.0),
+                start + Duration::from_secs(i * 10),
+            );
+        }
+
+        // 1°F of jitter a second and a half after the last reading; weighted per sample
+        // that would read as 12°F/min
+        sm.update_at(
+            Temperature::from_fahrenheit(301.0),
+            start + Duration::from_millis(91_500),
+        );
+
+        let rate = sm.rate_of_change().unwrap();
+        assert!(
+            rate < sm.config.rising_fast_rate,
+            "rate {} F/min",
+            rate.fahrenheit_per_minute()
+        );
+    }
+
+    // coaling sm that has been cooling 1°F/min from 400°F for ten minutes, returning the time
+    // of the last reading
+    fn coaling_for_ten_minutes(sm: &mut StoveStateMachine, start: Instant) -> Instant {
+        sm.state = BurnState::Coaling;
+        for i in 0..=60 {
+            sm.update_at(
+                Temperature::from_fahrenheit(400.0 - i as f32 / 6.0),
+                start + Duration::from_secs(i * 10),
+            );
+        }
+        assert_eq!(sm.current_state(), BurnState::Coaling);
+        start + Duration::from_secs(600)
+    }
+
+    #[test]
+    fn door_open_spike_does_not_look_like_a_reload() {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        let last = coaling_for_ten_minutes(&mut sm, start);
+        let coaling_since = sm.state_set_time;
+
+        // the door is opened, the coals flare up for half a minute, then settle back
+        let spike = [420.0, 440.0, 435.0, 410.0, 392.0, 390.0, 389.0, 388.0];
+        let mut saw_pending = false;
+        for (i, temp) in spike.iter().enumerate() {
+            sm.update_at(
+                Temperature::from_fahrenheit(*temp),
+                last + Duration::from_secs((i as u64 + 1) * 10),
+            );
+            saw_pending |= sm.pending_state() == Some(BurnState::Startup);
+            assert_eq!(sm.current_state(), BurnState::Coaling, "after {}°F", temp);
+        }
+
+        assert!(saw_pending);
+        assert_eq!(sm.pending_state(), None);
+        assert_eq!(sm.state_set_time, coaling_since);
+    }
+
+    #[test]
+    fn sustained_rise_is_a_reload() {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        let last = coaling_for_ten_minutes(&mut sm, start);
+
+        // fresh wood catching, climbing 15°F/min
+        let mut reloaded_at = None;
+        for i in 1..=18 {
+            let now = last + Duration::from_secs(i * 10);
+            sm.update_at(Temperature::from_fahrenheit(390.0 + 2.5 * i as f32), now);
+            if sm.current_state() == BurnState::Startup {
+                reloaded_at.get_or_insert(i);
+            }
+        }
+
+        // the rate estimate turns around within a couple of readings, then the rise has to
+        // hold for the 60 second dwell
+        assert_eq!(reloaded_at, Some(8));
+    }
+
+    #[test]
+    fn interrupted_candidate_restarts_dwell() {
+        let mut sm = StoveStateMachine::with_estimator(
+            StoveConfig {
+                rules: DEFAULT_RULES
+                    .replace(
+                        "idle -> startup when temp > idle and rate rising_fast",
+                        "idle -> startup when temp > idle and rate rising_fast hold 20s samples 3",
+                    )
+                    .parse()
+                    .unwrap(),
+                ..StoveConfig::default()
+            },
+            EmaEstimator::new(0.3),
+        );
+        let start = Instant::now();
+        let at = |s: u64| start + Duration::from_secs(s);
+
+        sm.update_at(Temperature::from_fahrenheit(150.0), at(0));
+        sm.update_at(Temperature::from_fahrenheit(170.0), at(10));
+        assert_eq!(sm.pending_state(), Some(BurnState::Startup));
+        // dropping back under the idle threshold drops the candidate
+        sm.update_at(Temperature::from_fahrenheit(140.0), at(20));
+        assert_eq!(sm.pending_state(), None);
+
+        sm.update_at(Temperature::from_fahrenheit(200.0), at(30));
+        sm.update_at(Temperature::from_fahrenheit(230.0), at(40));
+        assert_eq!(sm.current_state(), BurnState::Idle);
+        sm.update_at(Temperature::from_fahrenheit(260.0), at(50));
+        assert_eq!(sm.current_state(), BurnState::Startup);
+    }
+
+    #[test]
+    fn rules_follow_tuned_thresholds() {
+        let mut sm = StoveStateMachine::with_estimator(
+            StoveConfig {
+                overheat_threshold: Temperature::from_fahrenheit(650.0),
+                ..StoveConfig::default()
+            },
+            EmaEstimator::new(0.3),
+        );
+        sm.state = BurnState::ActiveBurn;
+
+        sm.update(Temperature::from_fahrenheit(680.0));
+
+        assert_eq!(sm.current_state(), BurnState::Overheat);
+    }
+
+    // feeds (seconds, °F) readings from `state`, returning each distinct state passed through
+    fn states_through(state: BurnState, readings: &[(u64, f32)]) -> Vec<BurnState> {
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        sm.state = state;
+        let mut states = vec![state];
+        for (seconds, temp) in readings {
+            sm.update_at(
+                Temperature::from_fahrenheit(*temp),
+                start + Duration::from_secs(*seconds),
+            );
+            if states.last() != Some(&sm.current_state()) {
+                states.push(sm.current_state());
+            }
+        }
+        states
+    }
+
+    // readings every 10 seconds, linear between (minute, °F) points
+    fn ramp(points: &[(f32, f32)]) -> Vec<(u64, f32)> {
+        let end = points.last().unwrap().0;
+        (0..=(end * 6.0) as u64)
+            .map(|i| {
+                let minute = i as f32 / 6.0;
+                let w = points
+                    .windows(2)
+                    .find(|w| minute <= w[1].0)
+                    .unwrap_or(&points[points.len() - 2..]);
+                let temp = w[0].1 + (w[1].1 - w[0].1) * (minute - w[0].0) / (w[1].0 - w[0].0);
+                (i * 10, temp)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn door_open_reload_goes_through_refueling() {
+        // burning steadily, then the door is open for two minutes and the new wood catches
+        let trace = ramp(&[(0.0, 500.0), (5.0, 500.0), (7.0, 400.0), (12.0, 500.0)]);
+
+        assert_eq!(
+            states_through(BurnState::ActiveBurn, &trace),
+            vec![
+                BurnState::ActiveBurn,
+                BurnState::Refueling,
+                BurnState::Startup,
+                BurnState::ActiveBurn
+            ]
+        );
+    }
+
+    #[test]
+    fn refueling_that_never_catches_goes_back_to_coaling() {
+        let trace = ramp(&[(0.0, 300.0), (20.0, 290.0)]);
+
+        assert_eq!(
+            states_through(BurnState::Refueling, &trace),
+            vec![BurnState::Refueling, BurnState::Coaling]
+        );
+    }
+
+    #[test]
+    fn steady_damped_burn_becomes_low_burn() {
+        let trace = ramp(&[(0.0, 340.0), (30.0, 335.0)]);
+        let mut sm = StoveStateMachine::new();
+        let start = Instant::now();
+        sm.state = BurnState::ActiveBurn;
+
+        let mut low_burn_at = None;
+        for (seconds, temp) in trace {
+            sm.update_at(
+                Temperature::from_fahrenheit(temp),
+                start + Duration::from_secs(seconds),
+            );
+            if sm.current_state() == BurnState::LowBurn {
+                low_burn_at.get_or_insert(seconds);
+            }
+        }
+
+        assert_eq!(low_burn_at, Some(15 * 60));
+    }
+
+    #[test]
+    fn low_burn_dying_down_becomes_coaling() {
+        // damped too far, dropping 5°F a minute
+        let trace = ramp(&[(0.0, 330.0), (12.0, 270.0)]);
+
+        assert_eq!(
+            states_through(BurnState::LowBurn, &trace),
+            vec![BurnState::LowBurn, BurnState::Coaling]
+        );
+    }
+
+    #[test]
+    fn burnt_out_stove_cools_then_idles() {
+        // exponential cool-down toward the room from the last of the coals
+        let trace: Vec<(u64, f32)> = (0..=360)
+            .map(|i| {
+                let seconds = i * 10;
+                (seconds, 70.0 + 150.0 * (-(seconds as f32) / 1200.0).exp())
+            })
+            .collect();
+
+        assert_eq!(
+            states_through(BurnState::Coaling, &trace),
+            vec![BurnState::Coaling, BurnState::Cooling, BurnState::Idle]
+        );
+    }
+
+    #[test]
+    fn transitions_to_coaling_from_active() {
+        let mut sm = StoveStateMachine::new();
+        let now = sm_idle(
+            &mut sm,
+            Duration::from_secs(20),
+            Some(RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(-5.0),
+                1.0,
+            )),
+            Some(Temperature::from_fahrenheit(352.0)),
         );
         sm.state = BurnState::ActiveBurn;
 
-        sm.update(Temperature::from_fahrenheit(320.0));
+        // dying down; a sudden drop would be the door opening
+        sm.update_at(Temperature::from_fahrenheit(345.0), now);
 
         assert_eq!(sm.state, BurnState::Coaling);
     }
+
+    #[test]
+    fn burn_state_parses_its_display_name() {
+        for state in BurnState::ALL {
+            assert_eq!(state.to_string().parse(), Ok(state));
+        }
+        assert_eq!("Active Burn".parse::<BurnState>(), Err(UnknownBurnState));
+    }
+
+    #[test]
+    fn restores_mid_burn_from_a_snapshot() {
+        let mut sm = StoveStateMachine::new();
+        let now = sm_idle(
+            &mut sm,
+            Duration::from_secs(20),
+            Some(RateOfChange::new_per_minute(
+                TemperatureDelta::from_fahrenheit(1.0),
+                1.0,
+            )),
+            Some(Temperature::from_fahrenheit(520.0)),
+        );
+        sm.state = BurnState::ActiveBurn;
+        sm.state_set_time = now;
+        let now = now + Duration::from_secs(40 * 60);
+        let snapshot = sm
+            .snapshot_at(now, Duration::from_secs(7_200))
+            .expect("has a reading");
+
+        // after the reboot the clock starts over
+        let mut rebooted = StoveStateMachine::new();
+        let boot = Instant::from_millis(500);
+        rebooted.restore(&snapshot, boot, Duration::from_secs(30));
+
+        assert_eq!(rebooted.current_state(), BurnState::ActiveBurn);
+        assert_eq!(rebooted.rate_of_change(), sm.rate_of_change());
+        assert_eq!(
+            rebooted.time_in_state_at(boot),
+            Duration::from_secs(40 * 60 + 30)
+        );
+
+        rebooted.update_at(
+            Temperature::from_fahrenheit(521.0),
+            boot + Duration::from_secs(20),
+        );
+        assert_eq!(rebooted.current_state(), BurnState::ActiveBurn);
+        assert_eq!(
+            rebooted.time_in_state_at(boot + Duration::from_secs(20)),
+            Duration::from_secs(40 * 60 + 50)
+        );
+    }
+
+    #[test]
+    fn no_snapshot_without_a_reading() {
+        let mut sm = StoveStateMachine::new();
+        let now = Instant::from_millis(1_000);
+        assert_eq!(sm.snapshot_at(now, Duration::ZERO), None);
+
+        for _ in 0..3 {
+            sm.report_sensor_fault_at(now);
+        }
+        assert_eq!(sm.snapshot_at(now, Duration::ZERO), None);
+    }
 }
@@ -1,4 +1,104 @@
-use std::ops::{Add, Div, Mul, Sub};
+use core::{
+    cmp::Ordering,
+    fmt::{Display, Formatter, Write},
+    ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign},
+    str::FromStr,
+    time::Duration,
+};
+
+use crate::text::{TextBuf, pad_number};
+
+const KELVIN_OFFSET: f32 = 273.15;
+// decimals shown when a format string doesn't give a precision
+const DEFAULT_PRECISION: usize = 1;
+// room for any f32 at the default precision, with its unit
+const FORMATTED_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl TemperatureUnit {
+    pub const ALL: [TemperatureUnit; 3] = [
+        TemperatureUnit::Celsius,
+        TemperatureUnit::Fahrenheit,
+        TemperatureUnit::Kelvin,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "celsius",
+            TemperatureUnit::Fahrenheit => "fahrenheit",
+            TemperatureUnit::Kelvin => "kelvin",
+        }
+    }
+
+    // also what Home Assistant expects as a `unit_of_measurement`
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            TemperatureUnit::Celsius => "°C",
+            TemperatureUnit::Fahrenheit => "°F",
+            TemperatureUnit::Kelvin => "K",
+        }
+    }
+}
+
+impl Display for TemperatureUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownTemperatureUnit;
+
+impl Display for UnknownTemperatureUnit {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "unknown unit, expected celsius, fahrenheit or kelvin")
+    }
+}
+
+// the name, or its first letter, in any case
+impl FromStr for TemperatureUnit {
+    type Err = UnknownTemperatureUnit;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        TemperatureUnit::ALL
+            .iter()
+            .find(|unit| {
+                s.eq_ignore_ascii_case(unit.name()) || s.eq_ignore_ascii_case(&unit.name()[..1])
+            })
+            .copied()
+            .ok_or(UnknownTemperatureUnit)
+    }
+}
+
+// Why a reading can't be a temperature
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TemperatureError {
+    NotANumber,
+    // outside what a type K thermocouple can measure, in °C
+    OutOfRange(f32),
+}
+
+impl Display for TemperatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TemperatureError::NotANumber => write!(f, "reading is not a number"),
+            TemperatureError::OutOfRange(c) => write!(
+                f,
+                "{:.1}°C is outside the type K range of {}..{}°C",
+                c,
+                Temperature::TYPE_K_MIN.celsius,
+                Temperature::TYPE_K_MAX.celsius
+            ),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Temperature {
@@ -6,6 +106,10 @@ pub struct Temperature {
 }
 
 impl Temperature {
+    // the range a type K thermocouple can measure
+    pub const TYPE_K_MIN: Temperature = Temperature { celsius: -270.0 };
+    pub const TYPE_K_MAX: Temperature = Temperature { celsius: 1372.0 };
+
     pub fn from_celsius(c: f32) -> Self {
         Self { celsius: c }
     }
@@ -16,6 +120,36 @@ impl Temperature {
         }
     }
 
+    pub fn from_kelvin(k: f32) -> Self {
+        Self {
+            celsius: k - KELVIN_OFFSET,
+        }
+    }
+
+    pub fn from_unit(value: f32, unit: TemperatureUnit) -> Self {
+        match unit {
+            TemperatureUnit::Celsius => Self::from_celsius(value),
+            TemperatureUnit::Fahrenheit => Self::from_fahrenheit(value),
+            TemperatureUnit::Kelvin => Self::from_kelvin(value),
+        }
+    }
+
+    // for raw sensor readings: rejects NaN and anything a type K thermocouple can't read,
+    // which is what a glitched frame or a failing amplifier tends to produce
+    pub fn try_from_celsius(c: f32) -> Result<Self, TemperatureError> {
+        if c.is_nan() {
+            return Err(TemperatureError::NotANumber);
+        }
+        if !(Self::TYPE_K_MIN.celsius..=Self::TYPE_K_MAX.celsius).contains(&c) {
+            return Err(TemperatureError::OutOfRange(c));
+        }
+        Ok(Self::from_celsius(c))
+    }
+
+    pub fn try_from_fahrenheit(f: f32) -> Result<Self, TemperatureError> {
+        Self::try_from_celsius(Self::from_fahrenheit(f).celsius)
+    }
+
     pub fn celsius(&self) -> f32 {
         self.celsius
     }
@@ -23,6 +157,28 @@ impl Temperature {
     pub fn fahrenheit(&self) -> f32 {
         self.celsius * 9.0 / 5.0 + 32.0
     }
+
+    pub fn kelvin(&self) -> f32 {
+        self.celsius + KELVIN_OFFSET
+    }
+
+    pub fn in_unit(&self, unit: TemperatureUnit) -> f32 {
+        match unit {
+            TemperatureUnit::Celsius => self.celsius(),
+            TemperatureUnit::Fahrenheit => self.fahrenheit(),
+            TemperatureUnit::Kelvin => self.kelvin(),
+        }
+    }
+
+    // orders every value, NaN included, like `f32::total_cmp`
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        self.celsius.total_cmp(&other.celsius)
+    }
+
+    // e.g. `format!("{:.0}", temp.display_in(TemperatureUnit::Fahrenheit))` gives `451°F`
+    pub fn display_in(&self, unit: TemperatureUnit) -> InUnit<Self> {
+        InUnit { value: *self, unit }
+    }
 }
 
 // Temperature - Temperature = TemperatureDelta
@@ -36,6 +192,47 @@ impl Sub for Temperature {
     }
 }
 
+// Temperature + TemperatureDelta = Temperature
+impl Add<TemperatureDelta> for Temperature {
+    type Output = Temperature;
+
+    fn add(self, delta: TemperatureDelta) -> Temperature {
+        Temperature {
+            celsius: self.celsius + delta.delta_celsius,
+        }
+    }
+}
+
+// Temperature - TemperatureDelta = Temperature
+impl Sub<TemperatureDelta> for Temperature {
+    type Output = Temperature;
+
+    fn sub(self, delta: TemperatureDelta) -> Temperature {
+        Temperature {
+            celsius: self.celsius - delta.delta_celsius,
+        }
+    }
+}
+
+impl AddAssign<TemperatureDelta> for Temperature {
+    fn add_assign(&mut self, delta: TemperatureDelta) {
+        self.celsius += delta.delta_celsius;
+    }
+}
+
+impl SubAssign<TemperatureDelta> for Temperature {
+    fn sub_assign(&mut self, delta: TemperatureDelta) {
+        self.celsius -= delta.delta_celsius;
+    }
+}
+
+// written in Celsius; the precision defaults to one decimal
+impl Display for Temperature {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.display_in(TemperatureUnit::Celsius).fmt(f)
+    }
+}
+
 // Represents a temperature *difference*
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct TemperatureDelta {
@@ -53,6 +250,11 @@ impl TemperatureDelta {
         }
     }
 
+    // a kelvin is the same size as a degree Celsius
+    pub fn from_kelvin(k: f32) -> TemperatureDelta {
+        TemperatureDelta { delta_celsius: k }
+    }
+
     pub fn celsius(&self) -> f32 {
         self.delta_celsius
     }
@@ -60,6 +262,26 @@ impl TemperatureDelta {
     pub fn fahrenheit(&self) -> f32 {
         self.delta_celsius * 9.0 / 5.0 // no +-32 for deltas
     }
+
+    pub fn kelvin(&self) -> f32 {
+        self.delta_celsius
+    }
+
+    pub fn in_unit(&self, unit: TemperatureUnit) -> f32 {
+        match unit {
+            TemperatureUnit::Celsius => self.celsius(),
+            TemperatureUnit::Fahrenheit => self.fahrenheit(),
+            TemperatureUnit::Kelvin => self.kelvin(),
+        }
+    }
+
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        self.delta_celsius.total_cmp(&other.delta_celsius)
+    }
+
+    pub fn display_in(&self, unit: TemperatureUnit) -> InUnit<Self> {
+        InUnit { value: *self, unit }
+    }
 }
 
 impl Div<f32> for TemperatureDelta {
@@ -72,6 +294,62 @@ impl Div<f32> for TemperatureDelta {
     }
 }
 
+// TemperatureDelta / Duration = RateOfChange
+impl Div<Duration> for TemperatureDelta {
+    type Output = RateOfChange;
+
+    fn div(self, elapsed: Duration) -> RateOfChange {
+        self / elapsed.as_secs_f32()
+    }
+}
+
+impl Add for TemperatureDelta {
+    type Output = TemperatureDelta;
+
+    fn add(self, other: TemperatureDelta) -> TemperatureDelta {
+        TemperatureDelta {
+            delta_celsius: self.delta_celsius + other.delta_celsius,
+        }
+    }
+}
+
+impl Sub for TemperatureDelta {
+    type Output = TemperatureDelta;
+
+    fn sub(self, other: TemperatureDelta) -> TemperatureDelta {
+        TemperatureDelta {
+            delta_celsius: self.delta_celsius - other.delta_celsius,
+        }
+    }
+}
+
+impl Neg for TemperatureDelta {
+    type Output = TemperatureDelta;
+
+    fn neg(self) -> TemperatureDelta {
+        TemperatureDelta {
+            delta_celsius: -self.delta_celsius,
+        }
+    }
+}
+
+impl Mul<f32> for TemperatureDelta {
+    type Output = TemperatureDelta;
+
+    fn mul(self, factor: f32) -> TemperatureDelta {
+        TemperatureDelta {
+            delta_celsius: self.delta_celsius * factor,
+        }
+    }
+}
+
+// written in Celsius; the precision defaults to one decimal
+impl Display for TemperatureDelta {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.display_in(TemperatureUnit::Celsius).fmt(f)
+    }
+}
+
 // Temperature rate of change
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct RateOfChange {
@@ -79,6 +357,12 @@ pub struct RateOfChange {
 }
 
 impl RateOfChange {
+    pub fn from_celsius_per_second(c: f32) -> Self {
+        Self {
+            celsius_per_second: c,
+        }
+    }
+
     pub fn new_per_second(delta: TemperatureDelta, seconds: f32) -> Self {
         Self {
             celsius_per_second: delta.celsius() / seconds,
@@ -102,6 +386,44 @@ impl RateOfChange {
     pub fn fahrenheit_per_minute(&self) -> f32 {
         self.fahrenheit_per_second() * 60.0
     }
+
+    pub fn celsius_per_minute(&self) -> f32 {
+        self.celsius_per_second * 60.0
+    }
+
+    // the change per minute, in `unit`
+    pub fn per_minute_in(&self, unit: TemperatureUnit) -> f32 {
+        TemperatureDelta::from_celsius(self.celsius_per_minute()).in_unit(unit)
+    }
+
+    pub fn total_cmp(&self, other: &Self) -> Ordering {
+        self.celsius_per_second.total_cmp(&other.celsius_per_second)
+    }
+
+    pub fn display_in(&self, unit: TemperatureUnit) -> InUnit<Self> {
+        InUnit { value: *self, unit }
+    }
+}
+
+// RateOfChange * Duration = TemperatureDelta
+impl Mul<Duration> for RateOfChange {
+    type Output = TemperatureDelta;
+
+    fn mul(self, elapsed: Duration) -> TemperatureDelta {
+        TemperatureDelta {
+            delta_celsius: self.celsius_per_second * elapsed.as_secs_f32(),
+        }
+    }
+}
+
+impl Neg for RateOfChange {
+    type Output = RateOfChange;
+
+    fn neg(self) -> RateOfChange {
+        RateOfChange {
+            celsius_per_second: -self.celsius_per_second,
+        }
+    }
 }
 
 // f32 * RateOfChange = RateOfChange
@@ -135,6 +457,54 @@ impl Sub for RateOfChange {
     }
 }
 
+// written in Celsius per minute; the precision defaults to one decimal
+impl Display for RateOfChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        self.display_in(TemperatureUnit::Celsius).fmt(f)
+    }
+}
+
+// A temperature, difference or rate to be written in a given unit, with its symbol
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InUnit<T> {
+    value: T,
+    unit: TemperatureUnit,
+}
+
+impl Display for InUnit<Temperature> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write_in_unit(f, self.value.in_unit(self.unit), self.unit, "")
+    }
+}
+
+impl Display for InUnit<TemperatureDelta> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write_in_unit(f, self.value.in_unit(self.unit), self.unit, "")
+    }
+}
+
+impl Display for InUnit<RateOfChange> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write_in_unit(f, self.value.per_minute_in(self.unit), self.unit, "/min")
+    }
+}
+
+// `value` to the format's precision, then the unit's symbol and `suffix`, padded to its width
+fn write_in_unit(
+    f: &mut Formatter<'_>,
+    value: f32,
+    unit: TemperatureUnit,
+    suffix: &str,
+) -> core::fmt::Result {
+    let precision = f.precision().unwrap_or(DEFAULT_PRECISION);
+    let mut text = TextBuf::<FORMATTED_LEN>::new();
+    match write!(text, "{:.*}{}{}", precision, value, unit.symbol(), suffix) {
+        Ok(()) => pad_number(f, text.as_str()),
+        // too long to pad, e.g. a silly precision
+        Err(_) => write!(f, "{:.*}{}{}", precision, value, unit.symbol(), suffix),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -160,4 +530,113 @@ mod tests {
 
         assert!((temp.fahrenheit() - original).abs() < 0.01);
     }
+
+    #[test]
+    fn converts_kelvin() {
+        let temp = Temperature::from_kelvin(373.15);
+
+        assert!((temp.celsius() - 100.0).abs() < 0.01);
+        assert!((Temperature::from_celsius(0.0).kelvin() - 273.15).abs() < 0.01);
+    }
+
+    #[test]
+    fn offsets_by_deltas() {
+        let temp = Temperature::from_fahrenheit(400.0);
+        let delta = TemperatureDelta::from_fahrenheit(50.0);
+
+        assert!(((temp + delta).fahrenheit() - 450.0).abs() < 0.01);
+        assert!(((temp - delta).fahrenheit() - 350.0).abs() < 0.01);
+        assert!((((temp + delta) - temp).fahrenheit() - 50.0).abs() < 0.01);
+        assert!(((delta + delta - delta).fahrenheit() - 50.0).abs() < 0.01);
+        assert!(((-delta).fahrenheit() + 50.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn rate_over_time_is_a_delta() {
+        let rate = RateOfChange::new_per_minute(TemperatureDelta::from_fahrenheit(6.0), 1.0);
+
+        let delta = rate * Duration::from_secs(10 * 60);
+
+        assert!((delta.fahrenheit() - 60.0).abs() < 0.01);
+        let back = delta / Duration::from_secs(10 * 60);
+        assert!((back.fahrenheit_per_minute() - 6.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn checks_type_k_range() {
+        assert!(Temperature::try_from_celsius(1200.0).is_ok());
+        assert_eq!(
+            Temperature::try_from_celsius(f32::NAN),
+            Err(TemperatureError::NotANumber)
+        );
+        assert_eq!(
+            Temperature::try_from_celsius(2047.75),
+            Err(TemperatureError::OutOfRange(2047.75))
+        );
+        assert!(Temperature::try_from_fahrenheit(-500.0).is_err());
+    }
+
+    #[test]
+    fn total_cmp_orders_nan() {
+        let nan = Temperature::from_celsius(f32::NAN);
+        let hot = Temperature::from_celsius(500.0);
+
+        assert_eq!(hot.total_cmp(&nan), Ordering::Less);
+        assert_eq!(hot.total_cmp(&hot), Ordering::Equal);
+    }
+
+    #[test]
+    fn parses_unit_names() {
+        for unit in TemperatureUnit::ALL {
+            assert_eq!(unit.to_string().parse(), Ok(unit));
+        }
+        assert_eq!("C".parse(), Ok(TemperatureUnit::Celsius));
+        assert_eq!(" Fahrenheit ".parse(), Ok(TemperatureUnit::Fahrenheit));
+        assert_eq!(
+            "rankine".parse::<TemperatureUnit>(),
+            Err(UnknownTemperatureUnit)
+        );
+    }
+
+    #[test]
+    fn displays_with_unit_and_precision() {
+        let temp = Temperature::from_celsius(100.0);
+
+        assert_eq!(temp.to_string(), "100.0°C");
+        assert_eq!(
+            format!("{:.0}", temp.display_in(TemperatureUnit::Fahrenheit)),
+            "212°F"
+        );
+        assert_eq!(
+            format!("{:.2}", temp.display_in(TemperatureUnit::Kelvin)),
+            "373.15K"
+        );
+        assert_eq!(
+            format!(
+                "{:.1}",
+                RateOfChange::new_per_minute(TemperatureDelta::from_fahrenheit(-4.5), 1.0)
+                    .display_in(TemperatureUnit::Fahrenheit)
+            ),
+            "-4.5°F/min"
+        );
+    }
+
+    #[test]
+    fn displays_with_width_and_alignment() {
+        let temp = Temperature::from_celsius(100.0).display_in(TemperatureUnit::Fahrenheit);
+
+        assert_eq!(format!("{:>8.1}", temp), " 212.0°F");
+        assert_eq!(format!("{:8.0}", temp), "   212°F");
+        assert_eq!(format!("{:<8.0}|", temp), "212°F   |");
+        assert_eq!(format!("{:*^9.0}", temp), "**212°F**");
+        assert_eq!(
+            format!(
+                "{:>10}",
+                TemperatureDelta::from_celsius(-2.0).display_in(TemperatureUnit::Celsius)
+            ),
+            "    -2.0°C"
+        );
+        // narrower than the text does nothing
+        assert_eq!(format!("{:3}", temp), "212.0°F");
+    }
 }
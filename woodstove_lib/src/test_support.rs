@@ -0,0 +1,72 @@
+// Fakes and helpers shared by the tests of several modules
+
+use core::{convert::Infallible, time::Duration};
+
+use embedded_hal::digital::{ErrorType, OutputPin};
+
+use crate::time::Instant;
+
+// `seconds` after the origin
+pub(crate) fn at(seconds: u64) -> Instant {
+    Instant::from_duration(Duration::from_secs(seconds))
+}
+
+// deterministic noise in [-amplitude, amplitude]
+pub(crate) struct Noise(pub(crate) u32);
+
+impl Noise {
+    pub(crate) fn next(&mut self, amplitude: f32) -> f32 {
+        self.0 = self.0.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+        let unit = (self.0 >> 8) as f32 / (1u32 << 24) as f32;
+        (unit * 2.0 - 1.0) * amplitude
+    }
+}
+
+// An LED or buzzer pin that remembers its level
+#[derive(Default)]
+pub(crate) struct TestPin {
+    pub(crate) high: bool,
+}
+
+impl ErrorType for TestPin {
+    type Error = Infallible;
+}
+
+impl OutputPin for TestPin {
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.high = false;
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.high = true;
+        Ok(())
+    }
+}
+
+// seconds between readings in a `trace`
+pub(crate) const SAMPLE_PERIOD: u64 = 10;
+
+// linear segments between (minute, °F) points, sampled every `SAMPLE_PERIOD`
+pub(crate) fn trace(points: &[(f32, f32)]) -> Vec<f32> {
+    let end = points.last().unwrap().0;
+    let samples = (end * 60.0 / SAMPLE_PERIOD as f32) as usize;
+
+    (0..=samples)
+        .map(|i| {
+            let minute = minute(i);
+            let segment = points
+                .windows(2)
+                .find(|w| minute <= w[1].0)
+                .unwrap_or(&points[points.len() - 2..]);
+            let (t0, v0) = segment[0];
+            let (t1, v1) = segment[1];
+            v0 + (v1 - v0) * (minute - t0) / (t1 - t0)
+        })
+        .collect()
+}
+
+// how far into a trace its sample `index` is
+pub(crate) fn minute(index: usize) -> f32 {
+    index as f32 * SAMPLE_PERIOD as f32 / 60.0
+}
@@ -0,0 +1,73 @@
+use core::fmt::{Alignment, Formatter, Write};
+
+// Fixed-size text to format into without allocating; anything past the end is dropped
+pub(crate) struct TextBuf<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> TextBuf<N> {
+    pub(crate) fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    #[cfg(feature = "display")]
+    pub(crate) fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
+
+impl<const N: usize> Write for TextBuf<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            let mut utf8 = [0; 4];
+            let encoded = c.encode_utf8(&mut utf8).as_bytes();
+            if self.len + encoded.len() > N {
+                return Err(core::fmt::Error);
+            }
+            self.bytes[self.len..self.len + encoded.len()].copy_from_slice(encoded);
+            self.len += encoded.len();
+        }
+        Ok(())
+    }
+}
+
+// Writes `text` padded out to the formatter's width with its fill and alignment, right-aligned
+// like a number unless asked otherwise. Unlike `Formatter::pad` this doesn't take the precision
+// as a length to cut the text to, so it can be used for numbers formatted with it.
+pub(crate) fn pad_number(f: &mut Formatter<'_>, text: &str) -> core::fmt::Result {
+    let padding = f.width().unwrap_or(0).saturating_sub(text.chars().count());
+    let (before, after) = match f.align() {
+        Some(Alignment::Left) => (0, padding),
+        Some(Alignment::Center) => (padding / 2, padding - padding / 2),
+        Some(Alignment::Right) | None => (padding, 0),
+    };
+    let fill = f.fill();
+    for _ in 0..before {
+        f.write_char(fill)?;
+    }
+    f.write_str(text)?;
+    for _ in 0..after {
+        f.write_char(fill)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn text_buf_drops_what_doesnt_fit() {
+        let mut text = TextBuf::<4>::new();
+        assert!(write!(text, "12°F").is_err());
+        assert_eq!(text.as_str(), "12°");
+    }
+}
@@ -0,0 +1,113 @@
+use core::{
+    ops::{Add, AddAssign, Sub},
+    time::Duration,
+};
+
+// A point in time, as the time since a fixed origin such as boot. Everything time-dependent in
+// the library takes one of these, so it runs wherever the caller has a monotonic clock:
+// bare-metal builds make them from a tick counter with `from_millis`/`from_micros`, and with the
+// `std` feature `Instant::now()` counts from the first time it is called.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Instant {
+    since_origin: Duration,
+}
+
+impl Instant {
+    pub const ZERO: Instant = Instant::from_duration(Duration::ZERO);
+
+    pub const fn from_duration(since_origin: Duration) -> Self {
+        Self { since_origin }
+    }
+
+    pub const fn from_millis(millis: u64) -> Self {
+        Self::from_duration(Duration::from_millis(millis))
+    }
+
+    pub const fn from_micros(micros: u64) -> Self {
+        Self::from_duration(Duration::from_micros(micros))
+    }
+
+    #[cfg(any(test, feature = "std"))]
+    pub fn now() -> Self {
+        static ORIGIN: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        Self::from_duration(ORIGIN.get_or_init(std::time::Instant::now).elapsed())
+    }
+
+    pub fn since_origin(&self) -> Duration {
+        self.since_origin
+    }
+
+    pub fn checked_duration_since(&self, earlier: Instant) -> Option<Duration> {
+        self.since_origin.checked_sub(earlier.since_origin)
+    }
+
+    // zero if `earlier` is actually later
+    pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+        self.since_origin.saturating_sub(earlier.since_origin)
+    }
+
+    pub fn checked_add(&self, duration: Duration) -> Option<Instant> {
+        self.since_origin
+            .checked_add(duration)
+            .map(Self::from_duration)
+    }
+
+    pub fn checked_sub(&self, duration: Duration) -> Option<Instant> {
+        self.since_origin
+            .checked_sub(duration)
+            .map(Self::from_duration)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Self::from_duration(self.since_origin + duration)
+    }
+}
+
+impl AddAssign<Duration> for Instant {
+    fn add_assign(&mut self, duration: Duration) {
+        self.since_origin += duration;
+    }
+}
+
+// saturates like `std::time::Instant`
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.saturating_duration_since(earlier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measures_between_ticks() {
+        let start = Instant::from_millis(1_500);
+        let later = start + Duration::from_secs(10);
+
+        assert_eq!(later - start, Duration::from_secs(10));
+        assert_eq!(later.since_origin(), Duration::from_millis(11_500));
+    }
+
+    #[test]
+    fn earlier_minus_later_is_zero() {
+        let start = Instant::from_micros(10);
+        let later = Instant::from_millis(1);
+
+        assert_eq!(start - later, Duration::ZERO);
+        assert_eq!(start.checked_duration_since(later), None);
+    }
+
+    #[test]
+    fn now_is_monotonic() {
+        let first = Instant::now();
+
+        assert!(Instant::now() >= first);
+    }
+}
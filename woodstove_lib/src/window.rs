@@ -0,0 +1,104 @@
+// Fixed-capacity ring buffer of the most recent samples, oldest first
+#[derive(Debug, Clone, Copy)]
+pub struct SampleWindow<T: Copy, const N: usize> {
+    samples: [Option<T>; N],
+    // index the next sample is written to
+    next: usize,
+    len: usize,
+}
+
+impl<T: Copy, const N: usize> SampleWindow<T, N> {
+    pub fn new() -> Self {
+        Self {
+            samples: [None; N],
+            next: 0,
+            len: 0,
+        }
+    }
+
+    pub fn push(&mut self, sample: T) {
+        self.samples[self.next] = Some(sample);
+        self.next = (self.next + 1) % N;
+        self.len = (self.len + 1).min(N);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    pub fn capacity(&self) -> usize {
+        N
+    }
+
+    pub fn newest(&self) -> Option<T> {
+        if self.len == 0 {
+            return None;
+        }
+        self.samples[(self.next + N - 1) % N]
+    }
+
+    pub fn oldest(&self) -> Option<T> {
+        self.iter().next()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        let start = (self.next + N - self.len) % N;
+        (0..self.len).filter_map(move |i| self.samples[(start + i) % N])
+    }
+}
+
+impl<T: Copy, const N: usize> Default for SampleWindow<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iterates_oldest_first() {
+        let mut window: SampleWindow<u32, 4> = SampleWindow::new();
+        window.push(1);
+        window.push(2);
+        window.push(3);
+
+        assert_eq!(window.iter().collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert_eq!(window.oldest(), Some(1));
+        assert_eq!(window.newest(), Some(3));
+    }
+
+    #[test]
+    fn drops_oldest_when_full() {
+        let mut window: SampleWindow<u32, 3> = SampleWindow::new();
+        for i in 1..=5 {
+            window.push(i);
+        }
+
+        assert!(window.is_full());
+        assert_eq!(window.iter().collect::<Vec<_>>(), vec![3, 4, 5]);
+    }
+
+    #[test]
+    fn clear_empties_window() {
+        let mut window: SampleWindow<u32, 3> = SampleWindow::new();
+        window.push(1);
+        window.clear();
+
+        assert!(window.is_empty());
+        assert_eq!(window.newest(), None);
+    }
+}